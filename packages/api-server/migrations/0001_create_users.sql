@@ -0,0 +1,12 @@
+-- Users of the platform. Ids are UUIDs stored as text and timestamps are
+-- unix seconds so the same schema runs on SQLite and PostgreSQL.
+CREATE TABLE users (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL,
+    username TEXT NOT NULL,
+    password TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE UNIQUE INDEX users_email_unique ON users (email);
+CREATE UNIQUE INDEX users_username_unique ON users (username);
@@ -0,0 +1,2 @@
+-- Passwords are stored as Argon2id PHC strings, never as plaintext.
+ALTER TABLE users RENAME COLUMN password TO password_hash;
@@ -0,0 +1,13 @@
+-- Signed-in devices. Only a SHA-256 digest of the refresh token is kept.
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    refresh_token_hash TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    revoked_at BIGINT
+);
+
+CREATE UNIQUE INDEX sessions_refresh_token_hash_unique
+    ON sessions (refresh_token_hash);
+CREATE INDEX sessions_user_id ON sessions (user_id);
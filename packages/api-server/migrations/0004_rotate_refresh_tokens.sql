@@ -0,0 +1,21 @@
+-- Refresh tokens rotate on every use. Each session (one per device) keeps
+-- every token it was issued so a replayed, already used token can be
+-- detected and the whole session revoked.
+CREATE TABLE refresh_tokens (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+    issued_at BIGINT NOT NULL,
+    used_at BIGINT
+);
+
+CREATE INDEX refresh_tokens_session_id ON refresh_tokens (session_id);
+
+INSERT INTO refresh_tokens (token_hash, session_id, issued_at)
+    SELECT refresh_token_hash, id, created_at FROM sessions;
+
+ALTER TABLE sessions ADD COLUMN last_seen_at BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+UPDATE sessions SET last_seen_at = created_at;
+
+DROP INDEX sessions_refresh_token_hash_unique;
+ALTER TABLE sessions DROP COLUMN refresh_token_hash;
@@ -0,0 +1,17 @@
+-- Accounts stay unverified until the link mailed at sign up is opened.
+-- Accounts created before verification existed are trusted as they are.
+ALTER TABLE users ADD COLUMN email_verified_at BIGINT;
+UPDATE users SET email_verified_at = created_at;
+
+-- Single-use tokens mailed to a user: email verification and password
+-- reset links. Only a SHA-256 digest of the token is kept.
+CREATE TABLE user_tokens (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    purpose TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    used_at BIGINT
+);
+
+CREATE INDEX user_tokens_user_id ON user_tokens (user_id);
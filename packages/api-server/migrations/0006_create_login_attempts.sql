@@ -0,0 +1,9 @@
+-- Failed sign-in counters, keyed by account (`account:<email>`) or by
+-- client address (`ip:<address>`). A counter starts over once its first
+-- failure falls out of the throttling window.
+CREATE TABLE login_attempts (
+    attempt_key TEXT PRIMARY KEY NOT NULL,
+    failures BIGINT NOT NULL,
+    first_failure_at BIGINT NOT NULL,
+    last_failure_at BIGINT NOT NULL
+);
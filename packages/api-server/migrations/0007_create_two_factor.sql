@@ -0,0 +1,21 @@
+-- TOTP second factor, one per user. The secret is kept as base32 since
+-- codes have to be recomputed from it. `enabled_at` stays NULL until the
+-- enrollment is confirmed with a first code; `last_used_step` is the
+-- 30-second step of the last accepted code, so a code can't be replayed.
+CREATE TABLE totp_credentials (
+    user_id TEXT PRIMARY KEY NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    secret TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    enabled_at BIGINT,
+    last_used_step BIGINT
+);
+
+-- Single-use codes that stand in for the TOTP when the device is lost.
+-- Only a SHA-256 digest of each code is kept.
+CREATE TABLE recovery_codes (
+    code_hash TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    used_at BIGINT
+);
+
+CREATE INDEX recovery_codes_user_id ON recovery_codes (user_id);
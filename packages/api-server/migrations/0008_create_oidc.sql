@@ -0,0 +1,24 @@
+-- Accounts at external OpenID Connect providers, linked to local users.
+-- `subject` is the provider's stable `sub` claim; the same person may link
+-- accounts from several providers.
+CREATE TABLE oidc_identities (
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (provider, subject)
+);
+
+CREATE INDEX oidc_identities_user_id ON oidc_identities (user_id);
+
+-- Authorization requests sent to a provider and not answered yet. Only a
+-- SHA-256 digest of the `state` parameter is kept; the PKCE verifier and
+-- the nonce are checked when the provider redirects back.
+CREATE TABLE oidc_logins (
+    state_hash TEXT PRIMARY KEY NOT NULL,
+    provider TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
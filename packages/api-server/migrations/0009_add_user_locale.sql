@@ -0,0 +1,3 @@
+-- Language the user picked for messages, a tag of one of the catalogs in
+-- api-shared/locales. Without one, the request's Accept-Language is used.
+ALTER TABLE users ADD COLUMN locale TEXT;
@@ -0,0 +1,23 @@
+-- Tasks written in the Task Editor, each owned by one user. `status` is
+-- one of open, done or archived; `completed_at` is set while it's done.
+CREATE TABLE tasks (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    content TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    completed_at BIGINT
+);
+
+CREATE INDEX tasks_user_id ON tasks (user_id, created_at);
+
+-- Tags of a task; `position` keeps the order they were entered in.
+CREATE TABLE task_tags (
+    task_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (task_id, name)
+);
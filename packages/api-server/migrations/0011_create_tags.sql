@@ -0,0 +1,42 @@
+-- Tags become entities of their own: one per user and `name_key`, the
+-- lowercased name, keeping the spelling first entered and the color of
+-- their chips. Existing tags get a neutral color and one of the spellings
+-- they were entered with.
+CREATE TABLE tags (
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name_key TEXT NOT NULL,
+    name TEXT NOT NULL,
+    color TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (user_id, name_key)
+);
+
+INSERT INTO tags (user_id, name_key, name, color, created_at)
+SELECT tasks.user_id, LOWER(task_tags.name), MIN(task_tags.name), '#64748b',
+    MIN(tasks.created_at)
+FROM task_tags
+JOIN tasks ON tasks.id = task_tags.task_id
+GROUP BY tasks.user_id, LOWER(task_tags.name);
+
+-- Tasks point to their tags, still in the order they were entered.
+CREATE TABLE task_tag_links (
+    task_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    tag_key TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (task_id, tag_key),
+    FOREIGN KEY (user_id, tag_key)
+        REFERENCES tags (user_id, name_key) ON DELETE CASCADE
+);
+
+INSERT INTO task_tag_links (task_id, user_id, tag_key, position)
+SELECT task_tags.task_id, tasks.user_id, LOWER(task_tags.name),
+    MIN(task_tags.position)
+FROM task_tags
+JOIN tasks ON tasks.id = task_tags.task_id
+GROUP BY task_tags.task_id, tasks.user_id, LOWER(task_tags.name);
+
+DROP TABLE task_tags;
+ALTER TABLE task_tag_links RENAME TO task_tags;
+
+CREATE INDEX task_tags_tag ON task_tags (user_id, tag_key);
@@ -0,0 +1,16 @@
+-- Recurring tasks repeat by an RRULE (see api-shared's `recurrence`) from
+-- `recurrence_starts_on`, an ISO date like every date column below. The
+-- skipped days are listed comma-separated in `recurrence_exceptions`.
+-- All three are NULL for one-off tasks.
+ALTER TABLE tasks ADD COLUMN recurrence_rule TEXT;
+ALTER TABLE tasks ADD COLUMN recurrence_starts_on TEXT;
+ALTER TABLE tasks ADD COLUMN recurrence_exceptions TEXT;
+
+-- Occurrences of recurring tasks marked done, one row each. Occurrences
+-- themselves are computed from the rule, never stored.
+CREATE TABLE task_completions (
+    task_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    occurs_on TEXT NOT NULL,
+    completed_at BIGINT NOT NULL,
+    PRIMARY KEY (task_id, occurs_on)
+);
@@ -0,0 +1,19 @@
+-- When a task can begin and is due, as unix seconds, and the minutes
+-- before `due_at` its owner wants to be reminded at, comma-separated.
+ALTER TABLE tasks ADD COLUMN starts_at BIGINT;
+ALTER TABLE tasks ADD COLUMN due_at BIGINT;
+ALTER TABLE tasks ADD COLUMN reminders TEXT;
+
+-- Reminders of open tasks waiting to be sent, or sent, kept in the
+-- database so they survive restarts: the scheduler sends the ones whose
+-- `remind_at` passed while it was down as soon as it starts again. Rows
+-- are rebuilt whenever their task is saved.
+CREATE TABLE task_reminders (
+    task_id TEXT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    offset_minutes INTEGER NOT NULL,
+    remind_at BIGINT NOT NULL,
+    sent_at BIGINT,
+    PRIMARY KEY (task_id, offset_minutes)
+);
+
+CREATE INDEX task_reminders_pending ON task_reminders (sent_at, remind_at);
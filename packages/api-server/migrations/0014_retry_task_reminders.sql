@@ -0,0 +1,5 @@
+-- Reminders whose notification failed are tried again at `retry_at`, later
+-- after each of their `attempts`, so they don't hold back the others. The
+-- scheduler goes by `retry_at` when set, else by `remind_at`.
+ALTER TABLE task_reminders ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE task_reminders ADD COLUMN retry_at BIGINT;
@@ -1,11 +1,25 @@
-use api_shared::prelude::LibError;
+use config::Config;
 
+pub mod config;
+pub mod repository;
 pub mod routes;
 pub mod services;
+pub mod telemetry;
 
 #[tokio::main]
-async fn main() -> Result<(), LibError> {
-    routes::run_server().await?;
+async fn main() -> miette::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args
+        .iter()
+        .any(|arg| arg == "--help" || arg == "-h")
+    {
+        print!("{}", config::usage());
+        return Ok(());
+    }
+
+    let config = Config::load(&args, |name| std::env::var(name).ok())?;
+    telemetry::init(&config.log);
+    routes::run_server(config).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
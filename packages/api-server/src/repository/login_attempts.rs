@@ -0,0 +1,228 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+// local modules
+use super::timestamp;
+use api_shared::prelude::LibError;
+
+/// Failed sign-ins counted under one key since `first_failure_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttemptCounter {
+    pub failures: u32,
+    pub first_failure_at: DateTime<Utc>,
+    pub last_failure_at: DateTime<Utc>,
+}
+
+/// Storage for failed sign-in counters, see `services::LoginThrottle`.
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    async fn find(&self, key: &str)
+        -> Result<Option<AttemptCounter>, LibError>;
+    /// Atomically counts a failure under `key`. A counter whose first
+    /// failure is older than `window_start` starts over from this one.
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    ) -> Result<AttemptCounter, LibError>;
+    async fn clear(&self, key: &str) -> Result<(), LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Process-local counters, used by tests and single-instance deployments.
+#[derive(Debug, Default)]
+pub struct InMemoryLoginAttemptRepository {
+    counters: Mutex<HashMap<String, AttemptCounter>>,
+}
+
+#[async_trait]
+impl LoginAttemptRepository for InMemoryLoginAttemptRepository {
+    async fn find(
+        &self,
+        key: &str,
+    ) -> Result<Option<AttemptCounter>, LibError> {
+        Ok(self
+            .counters
+            .lock()
+            .unwrap()
+            .get(key)
+            .copied())
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    ) -> Result<AttemptCounter, LibError> {
+        let mut counters = self.counters.lock().unwrap();
+        // keeps the map from growing with every address ever seen
+        counters.retain(|_, c| c.first_failure_at >= window_start);
+
+        let counter = counters
+            .entry(key.to_string())
+            .and_modify(|c| {
+                c.failures += 1;
+                c.last_failure_at = now;
+            })
+            .or_insert(AttemptCounter {
+                failures: 1,
+                first_failure_at: now,
+                last_failure_at: now,
+            });
+
+        Ok(*counter)
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), LibError> {
+        self.counters
+            .lock()
+            .unwrap()
+            .remove(key);
+
+        Ok(())
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Counters kept in the user store, shared by every server instance.
+#[derive(Debug, Clone)]
+pub struct SqlLoginAttemptRepository {
+    pool: AnyPool,
+}
+
+impl SqlLoginAttemptRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for SqlLoginAttemptRepository {
+    async fn find(
+        &self,
+        key: &str,
+    ) -> Result<Option<AttemptCounter>, LibError> {
+        let row = sqlx::query(
+            "SELECT failures, first_failure_at, last_failure_at \
+             FROM login_attempts WHERE attempt_key = $1",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| counter_from_row(&row))
+            .transpose()
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    ) -> Result<AttemptCounter, LibError> {
+        let row = sqlx::query(
+            "INSERT INTO login_attempts \
+             (attempt_key, failures, first_failure_at, last_failure_at) \
+             VALUES ($1, 1, $2, $2) \
+             ON CONFLICT (attempt_key) DO UPDATE SET \
+             failures = CASE WHEN login_attempts.first_failure_at < $3 \
+                THEN 1 ELSE login_attempts.failures + 1 END, \
+             first_failure_at = CASE WHEN login_attempts.first_failure_at < $3 \
+                THEN $2 ELSE login_attempts.first_failure_at END, \
+             last_failure_at = $2 \
+             RETURNING failures, first_failure_at, last_failure_at",
+        )
+        .bind(key)
+        .bind(now.timestamp())
+        .bind(window_start.timestamp())
+        .fetch_one(&self.pool)
+        .await?;
+
+        counter_from_row(&row)
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), LibError> {
+        sqlx::query("DELETE FROM login_attempts WHERE attempt_key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn counter_from_row(row: &AnyRow) -> Result<AttemptCounter, LibError> {
+    let failures: i64 = row.try_get("failures")?;
+    let first_failure_at: i64 = row.try_get("first_failure_at")?;
+    let last_failure_at: i64 = row.try_get("last_failure_at")?;
+
+    Ok(AttemptCounter {
+        failures: u32::try_from(failures).unwrap_or(u32::MAX),
+        first_failure_at: timestamp(first_failure_at),
+        last_failure_at: timestamp(last_failure_at),
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{connect, now};
+    use chrono::Duration;
+
+    async fn assert_counting(
+        repo: &dyn LoginAttemptRepository,
+    ) -> miette::Result<()> {
+        let start = now();
+        let window = Duration::minutes(15);
+        for n in 0..3 {
+            let at = start + Duration::seconds(n);
+            repo.record_failure("ip:127.0.0.1", at, at - window)
+                .await?;
+        }
+        let counter = repo.find("ip:127.0.0.1").await?;
+        miette::ensure!(
+            counter.is_some_and(|c| c.failures == 3
+                && c.first_failure_at == start
+                && c.last_failure_at == start + Duration::seconds(2)),
+            "Error: unexpected counter {counter:?}"
+        );
+
+        // the first failure fell out of the window
+        let later = start + window + Duration::seconds(1);
+        let counter = repo
+            .record_failure("ip:127.0.0.1", later, later - window)
+            .await?;
+        miette::ensure!(
+            counter.failures == 1 && counter.first_failure_at == later,
+            "Error: stale counter kept {counter:?}"
+        );
+
+        repo.clear("ip:127.0.0.1").await?;
+        miette::ensure!(
+            repo.find("ip:127.0.0.1")
+                .await?
+                .is_none(),
+            "Error: counter not cleared"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_counting() -> miette::Result<()> {
+        assert_counting(&InMemoryLoginAttemptRepository::default()).await
+    }
+
+    #[tokio::test]
+    async fn test_sql_counting() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_counting(&SqlLoginAttemptRepository::new(pool)).await
+    }
+}
@@ -0,0 +1,132 @@
+// external crates
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use sqlx::{
+    any::{AnyPoolOptions, AnyRow},
+    Any, AnyPool, Decode, Row, Type, TypeInfo, ValueRef,
+};
+use uuid::Uuid;
+// local modules
+use api_shared::prelude::LibError;
+
+mod login_attempts;
+pub use login_attempts::*;
+
+mod oidc;
+pub use oidc::*;
+
+mod sessions;
+pub use sessions::*;
+
+mod tasks;
+pub use tasks::*;
+
+mod two_factor;
+pub use two_factor::*;
+
+mod user_tokens;
+pub use user_tokens::*;
+
+mod users;
+pub use users::*;
+
+/// Versioned schema migrations, embedded at compile time from `migrations/`.
+///
+/// The SQL is kept portable so the same files run on SQLite (self-hosting)
+/// and PostgreSQL (production).
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+/// Opens a pool for `database_url` (`sqlite://...` or `postgres://...`)
+/// and brings the schema up to date.
+pub async fn connect(database_url: &str) -> Result<AnyPool, LibError> {
+    sqlx::any::install_default_drivers();
+
+    // an in-memory SQLite database only lives as long as its connection
+    let is_memory = database_url.contains(":memory:");
+    let options = if is_memory {
+        AnyPoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        AnyPoolOptions::new()
+    };
+
+    let pool = options.connect(database_url).await?;
+    MIGRATOR
+        .run(&pool)
+        .await
+        .map_err(LibError::internal)?;
+
+    Ok(pool)
+}
+
+/// Checks that the database behind `pool` answers.
+pub async fn ping(pool: &AnyPool) -> Result<(), LibError> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the violated constraint (or the driver message, for SQLite)
+/// when `err` is a unique violation.
+pub(crate) fn unique_violation(err: &sqlx::Error) -> Option<String> {
+    let sqlx::Error::Database(db_err) = err else {
+        return None;
+    };
+    if !db_err.is_unique_violation() {
+        return None;
+    }
+
+    Some(
+        db_err
+            .constraint()
+            .map(str::to_string)
+            .unwrap_or_else(|| db_err.message().to_string()),
+    )
+}
+
+/// Reads a nullable column.
+///
+/// The Any driver never reports a value as null (`AnyValueRef::is_null`
+/// is always false in sqlx 0.7), so `row.try_get::<Option<_>, _>` fails on
+/// NULL; the value kind is checked by hand instead.
+pub(crate) fn try_get_optional<'r, T>(
+    row: &'r AnyRow,
+    column: &str,
+) -> Result<Option<T>, LibError>
+where
+    T: Decode<'r, Any> + Type<Any>,
+{
+    let value = row.try_get_raw(column)?;
+    if value.type_info().name() == "NULL" {
+        return Ok(None);
+    }
+
+    Ok(Some(row.try_get(column)?))
+}
+
+/// Ids are stored as text, see `migrations/`.
+pub(crate) fn parse_uuid(value: &str) -> Result<Uuid, LibError> {
+    Uuid::parse_str(value).map_err(LibError::internal)
+}
+
+/// Dates are stored as ISO text, `2026-10-18`, see `migrations/`.
+pub(crate) fn parse_date(value: &str) -> Result<NaiveDate, LibError> {
+    value
+        .parse()
+        .map_err(LibError::internal)
+}
+
+/// Timestamps are stored as unix seconds, see `migrations/`.
+pub fn timestamp(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds, 0)
+        .single()
+        .unwrap_or_default()
+}
+
+/// Current time truncated to what the store keeps (whole seconds).
+pub fn now() -> DateTime<Utc> {
+    timestamp(Utc::now().timestamp())
+}
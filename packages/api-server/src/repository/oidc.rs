@@ -0,0 +1,332 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+// local modules
+use super::{parse_uuid, timestamp};
+use api_shared::prelude::LibError;
+
+/// An account at an OpenID Connect provider, linked to a local user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcIdentity {
+    /// name of the provider in the server config
+    pub provider: String,
+    /// `sub` claim of the provider's ID tokens
+    pub subject: String,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An authorization request waiting for the provider to redirect back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcLogin {
+    /// SHA-256 of the `state` parameter
+    pub state_hash: String,
+    pub provider: String,
+    /// PKCE secret whose digest was sent as `code_challenge`
+    pub code_verifier: String,
+    pub nonce: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Storage for linked provider accounts and pending provider sign-ins.
+#[async_trait]
+pub trait OidcRepository: Send + Sync {
+    async fn insert_login(&self, login: &OidcLogin) -> Result<(), LibError>;
+    /// Removes and returns the login with `state_hash` if it hasn't expired,
+    /// so each `state` is accepted once.
+    async fn take_login(
+        &self,
+        state_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OidcLogin>, LibError>;
+
+    async fn find_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<OidcIdentity>, LibError>;
+    async fn insert_identity(
+        &self,
+        identity: &OidcIdentity,
+    ) -> Result<(), LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Process-local store, used by tests and throwaway instances.
+#[derive(Debug, Default)]
+pub struct InMemoryOidcRepository {
+    logins: Mutex<HashMap<String, OidcLogin>>,
+    identities: Mutex<HashMap<(String, String), OidcIdentity>>,
+}
+
+#[async_trait]
+impl OidcRepository for InMemoryOidcRepository {
+    async fn insert_login(&self, login: &OidcLogin) -> Result<(), LibError> {
+        let mut logins = self.logins.lock().unwrap();
+        // abandoned logins are dropped as new ones come in
+        logins.retain(|_, pending| pending.expires_at > login.created_at);
+        logins.insert(login.state_hash.clone(), login.clone());
+
+        Ok(())
+    }
+
+    async fn take_login(
+        &self,
+        state_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OidcLogin>, LibError> {
+        Ok(self
+            .logins
+            .lock()
+            .unwrap()
+            .remove(state_hash)
+            .filter(|login| login.expires_at > now))
+    }
+
+    async fn find_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<OidcIdentity>, LibError> {
+        let key = (provider.to_string(), subject.to_string());
+        Ok(self
+            .identities
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned())
+    }
+
+    async fn insert_identity(
+        &self,
+        identity: &OidcIdentity,
+    ) -> Result<(), LibError> {
+        let key = (identity.provider.clone(), identity.subject.clone());
+        self.identities
+            .lock()
+            .unwrap()
+            .insert(key, identity.clone());
+
+        Ok(())
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Store backed by SQLite or PostgreSQL through `sqlx`'s `Any` driver.
+#[derive(Debug, Clone)]
+pub struct SqlOidcRepository {
+    pool: AnyPool,
+}
+
+impl SqlOidcRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OidcRepository for SqlOidcRepository {
+    async fn insert_login(&self, login: &OidcLogin) -> Result<(), LibError> {
+        // abandoned logins are dropped as new ones come in
+        sqlx::query("DELETE FROM oidc_logins WHERE expires_at <= $1")
+            .bind(login.created_at.timestamp())
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO oidc_logins \
+             (state_hash, provider, code_verifier, nonce, created_at, \
+             expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&login.state_hash)
+        .bind(&login.provider)
+        .bind(&login.code_verifier)
+        .bind(&login.nonce)
+        .bind(login.created_at.timestamp())
+        .bind(login.expires_at.timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn take_login(
+        &self,
+        state_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<OidcLogin>, LibError> {
+        // deleting is what makes the state single-use under concurrent
+        // callbacks
+        let row = sqlx::query(
+            "DELETE FROM oidc_logins WHERE state_hash = $1 \
+             RETURNING state_hash, provider, code_verifier, nonce, \
+             created_at, expires_at",
+        )
+        .bind(state_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let login = row
+            .map(|row| login_from_row(&row))
+            .transpose()?;
+        Ok(login.filter(|login| login.expires_at > now))
+    }
+
+    async fn find_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<OidcIdentity>, LibError> {
+        let row = sqlx::query(
+            "SELECT provider, subject, user_id, created_at \
+             FROM oidc_identities WHERE provider = $1 AND subject = $2",
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| identity_from_row(&row))
+            .transpose()
+    }
+
+    async fn insert_identity(
+        &self,
+        identity: &OidcIdentity,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "INSERT INTO oidc_identities \
+             (provider, subject, user_id, created_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .bind(identity.user_id.to_string())
+        .bind(identity.created_at.timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn login_from_row(row: &AnyRow) -> Result<OidcLogin, LibError> {
+    let created_at: i64 = row.try_get("created_at")?;
+    let expires_at: i64 = row.try_get("expires_at")?;
+
+    Ok(OidcLogin {
+        state_hash: row.try_get("state_hash")?,
+        provider: row.try_get("provider")?,
+        code_verifier: row.try_get("code_verifier")?,
+        nonce: row.try_get("nonce")?,
+        created_at: timestamp(created_at),
+        expires_at: timestamp(expires_at),
+    })
+}
+
+fn identity_from_row(row: &AnyRow) -> Result<OidcIdentity, LibError> {
+    let user_id: String = row.try_get("user_id")?;
+    let created_at: i64 = row.try_get("created_at")?;
+
+    Ok(OidcIdentity {
+        provider: row.try_get("provider")?,
+        subject: row.try_get("subject")?,
+        user_id: parse_uuid(&user_id)?,
+        created_at: timestamp(created_at),
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{
+        connect, now, InMemoryUserRepository, SqlUserRepository, User,
+        UserRepository,
+    };
+    use chrono::Duration;
+
+    async fn assert_logins_and_identities(
+        users: &dyn UserRepository,
+        oidc: &dyn OidcRepository,
+    ) -> miette::Result<()> {
+        let mock_login = |hash: &str, expires_in: Duration| OidcLogin {
+            state_hash: hash.to_string(),
+            provider: "company".to_string(),
+            code_verifier: "verifier".to_string(),
+            nonce: "nonce".to_string(),
+            created_at: now(),
+            expires_at: now() + expires_in,
+        };
+        oidc.insert_login(&mock_login("fresh", Duration::minutes(10)))
+            .await?;
+        oidc.insert_login(&mock_login("stale", Duration::minutes(-1)))
+            .await?;
+
+        let first = oidc.take_login("fresh", now()).await?;
+        miette::ensure!(
+            first.is_some_and(|l| l.code_verifier == "verifier"),
+            "Error: pending login lost"
+        );
+        let second = oidc.take_login("fresh", now()).await?;
+        miette::ensure!(second.is_none(), "Error: state accepted twice");
+        let stale = oidc.take_login("stale", now()).await?;
+        miette::ensure!(stale.is_none(), "Error: expired state accepted");
+
+        let user = User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: "$argon2id$v=19$m=256,t=1,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        };
+        users.insert(&user).await?;
+        let identity = OidcIdentity {
+            provider: "company".to_string(),
+            subject: "248289761001".to_string(),
+            user_id: user.id,
+            created_at: now(),
+        };
+        oidc.insert_identity(&identity).await?;
+
+        let found = oidc
+            .find_identity("company", "248289761001")
+            .await?;
+        miette::ensure!(found == Some(identity), "Error: identity not linked");
+        let other = oidc
+            .find_identity("other", "248289761001")
+            .await?;
+        miette::ensure!(
+            other.is_none(),
+            "Error: identity leaked across providers"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_logins_and_identities() -> miette::Result<()> {
+        assert_logins_and_identities(
+            &InMemoryUserRepository::default(),
+            &InMemoryOidcRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_logins_and_identities() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_logins_and_identities(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlOidcRepository::new(pool),
+        )
+        .await
+    }
+}
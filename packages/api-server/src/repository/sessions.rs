@@ -0,0 +1,514 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+// local modules
+use super::{parse_uuid, timestamp, try_get_optional};
+use api_shared::prelude::LibError;
+
+/// A signed-in device. Its refresh tokens rotate, the session id doesn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+/// A refresh token issued to a session, identified by its digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub session_id: Uuid,
+    pub issued_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Storage for sign-in sessions and their refresh tokens.
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    async fn insert(&self, session: &Session) -> Result<(), LibError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, LibError>;
+    /// Sessions of `user_id` that are neither revoked nor expired, most
+    /// recently seen first.
+    async fn list_active(
+        &self,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, LibError>;
+    /// Records activity of the session's device.
+    async fn touch(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+        user_agent: Option<&str>,
+    ) -> Result<(), LibError>;
+    /// Marks the session as revoked. Revoking twice keeps the first time.
+    async fn revoke(&self, id: Uuid, at: DateTime<Utc>)
+        -> Result<(), LibError>;
+
+    async fn insert_refresh_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<(), LibError>;
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, LibError>;
+    /// Atomically marks the token as used. Returns `false` when it had
+    /// already been used, which means it was replayed.
+    async fn use_refresh_token(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Process-local store, used by tests and throwaway instances.
+#[derive(Debug, Default)]
+pub struct InMemorySessionRepository {
+    sessions: Mutex<HashMap<Uuid, Session>>,
+    refresh_tokens: Mutex<HashMap<String, RefreshToken>>,
+}
+
+#[async_trait]
+impl SessionRepository for InMemorySessionRepository {
+    async fn insert(&self, session: &Session) -> Result<(), LibError> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.id, session.clone());
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, LibError> {
+        Ok(self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned())
+    }
+
+    async fn list_active(
+        &self,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, LibError> {
+        let mut active: Vec<Session> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|s| s.user_id == user_id && s.is_active(now))
+            .cloned()
+            .collect();
+        active.sort_by_key(|s| std::cmp::Reverse(s.last_seen_at));
+
+        Ok(active)
+    }
+
+    async fn touch(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+        user_agent: Option<&str>,
+    ) -> Result<(), LibError> {
+        if let Some(session) = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(&id)
+        {
+            session.last_seen_at = at;
+            if let Some(user_agent) = user_agent {
+                session.user_agent = Some(user_agent.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn revoke(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        if let Some(session) = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(&id)
+        {
+            session.revoked_at.get_or_insert(at);
+        }
+
+        Ok(())
+    }
+
+    async fn insert_refresh_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<(), LibError> {
+        self.refresh_tokens
+            .lock()
+            .unwrap()
+            .insert(token.token_hash.clone(), token.clone());
+
+        Ok(())
+    }
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, LibError> {
+        Ok(self
+            .refresh_tokens
+            .lock()
+            .unwrap()
+            .get(token_hash)
+            .cloned())
+    }
+
+    async fn use_refresh_token(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let mut tokens = self.refresh_tokens.lock().unwrap();
+        match tokens.get_mut(token_hash) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Store backed by SQLite or PostgreSQL through `sqlx`'s `Any` driver.
+#[derive(Debug, Clone)]
+pub struct SqlSessionRepository {
+    pool: AnyPool,
+}
+
+impl SqlSessionRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+const SESSION_COLUMNS: &str = "id, user_id, created_at, expires_at, \
+                               last_seen_at, user_agent, revoked_at";
+
+#[async_trait]
+impl SessionRepository for SqlSessionRepository {
+    async fn insert(&self, session: &Session) -> Result<(), LibError> {
+        let query = format!(
+            "INSERT INTO sessions ({SESSION_COLUMNS}) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)"
+        );
+        sqlx::query(&query)
+            .bind(session.id.to_string())
+            .bind(session.user_id.to_string())
+            .bind(session.created_at.timestamp())
+            .bind(session.expires_at.timestamp())
+            .bind(session.last_seen_at.timestamp())
+            .bind(session.user_agent.clone())
+            .bind(
+                session
+                    .revoked_at
+                    .map(|at| at.timestamp()),
+            )
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, LibError> {
+        let query =
+            format!("SELECT {SESSION_COLUMNS} FROM sessions WHERE id = $1");
+        let row = sqlx::query(&query)
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| session_from_row(&row))
+            .transpose()
+    }
+
+    async fn list_active(
+        &self,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, LibError> {
+        let query = format!(
+            "SELECT {SESSION_COLUMNS} FROM sessions \
+             WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > $2 \
+             ORDER BY last_seen_at DESC"
+        );
+        let rows = sqlx::query(&query)
+            .bind(user_id.to_string())
+            .bind(now.timestamp())
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(session_from_row)
+            .collect()
+    }
+
+    async fn touch(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+        user_agent: Option<&str>,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "UPDATE sessions SET last_seen_at = $1, \
+             user_agent = COALESCE($2, user_agent) WHERE id = $3",
+        )
+        .bind(at.timestamp())
+        .bind(user_agent.map(str::to_string))
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = $1 \
+             WHERE id = $2 AND revoked_at IS NULL",
+        )
+        .bind(at.timestamp())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_refresh_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens \
+             (token_hash, session_id, issued_at, used_at) \
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&token.token_hash)
+        .bind(token.session_id.to_string())
+        .bind(token.issued_at.timestamp())
+        .bind(token.used_at.map(|at| at.timestamp()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, LibError> {
+        let row = sqlx::query(
+            "SELECT token_hash, session_id, issued_at, used_at \
+             FROM refresh_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| refresh_token_from_row(&row))
+            .transpose()
+    }
+
+    async fn use_refresh_token(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = $1 \
+             WHERE token_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at.timestamp())
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+fn session_from_row(row: &AnyRow) -> Result<Session, LibError> {
+    let id: String = row.try_get("id")?;
+    let user_id: String = row.try_get("user_id")?;
+    let created_at: i64 = row.try_get("created_at")?;
+    let expires_at: i64 = row.try_get("expires_at")?;
+    let last_seen_at: i64 = row.try_get("last_seen_at")?;
+    let revoked_at: Option<i64> = try_get_optional(row, "revoked_at")?;
+
+    Ok(Session {
+        id: parse_uuid(&id)?,
+        user_id: parse_uuid(&user_id)?,
+        created_at: timestamp(created_at),
+        expires_at: timestamp(expires_at),
+        last_seen_at: timestamp(last_seen_at),
+        user_agent: try_get_optional(row, "user_agent")?,
+        revoked_at: revoked_at.map(timestamp),
+    })
+}
+
+fn refresh_token_from_row(row: &AnyRow) -> Result<RefreshToken, LibError> {
+    let session_id: String = row.try_get("session_id")?;
+    let issued_at: i64 = row.try_get("issued_at")?;
+    let used_at: Option<i64> = try_get_optional(row, "used_at")?;
+
+    Ok(RefreshToken {
+        token_hash: row.try_get("token_hash")?,
+        session_id: parse_uuid(&session_id)?,
+        issued_at: timestamp(issued_at),
+        used_at: used_at.map(timestamp),
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{
+        connect, now, InMemoryUserRepository, SqlUserRepository, User,
+        UserRepository,
+    };
+    use chrono::Duration;
+
+    fn mock_user() -> User {
+        User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: "$argon2id$v=19$m=256,t=1,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        }
+    }
+
+    fn mock_session(user_id: Uuid, last_seen_at: DateTime<Utc>) -> Session {
+        Session {
+            id: Uuid::new_v4(),
+            user_id,
+            created_at: last_seen_at,
+            expires_at: last_seen_at + Duration::days(1),
+            last_seen_at,
+            user_agent: None,
+            revoked_at: None,
+        }
+    }
+
+    async fn assert_rotation(
+        users: &dyn UserRepository,
+        sessions: &dyn SessionRepository,
+    ) -> miette::Result<()> {
+        let user = mock_user();
+        users.insert(&user).await?;
+        let older = mock_session(user.id, now() - Duration::hours(1));
+        let newer = mock_session(user.id, now());
+        let revoked = mock_session(user.id, now());
+        for session in [&older, &newer, &revoked] {
+            sessions.insert(session).await?;
+        }
+        sessions
+            .revoke(revoked.id, now())
+            .await?;
+
+        let active = sessions
+            .list_active(user.id, now())
+            .await?;
+        let active_ids: Vec<Uuid> = active.iter().map(|s| s.id).collect();
+        miette::ensure!(
+            active_ids == vec![newer.id, older.id],
+            "Error: unexpected active sessions {active_ids:?}"
+        );
+
+        sessions
+            .touch(older.id, now(), Some("Firefox"))
+            .await?;
+        let touched = sessions
+            .find_by_id(older.id)
+            .await?
+            .unwrap();
+        miette::ensure!(
+            touched.user_agent.as_deref() == Some("Firefox"),
+            "Error: user agent not recorded"
+        );
+
+        let token = RefreshToken {
+            token_hash: "hash".to_string(),
+            session_id: older.id,
+            issued_at: now(),
+            used_at: None,
+        };
+        sessions
+            .insert_refresh_token(&token)
+            .await?;
+        miette::ensure!(
+            sessions
+                .use_refresh_token("hash", now())
+                .await?,
+            "Error: fresh token refused"
+        );
+        miette::ensure!(
+            !sessions
+                .use_refresh_token("hash", now())
+                .await?,
+            "Error: token used twice"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_rotation() -> miette::Result<()> {
+        assert_rotation(
+            &InMemoryUserRepository::default(),
+            &InMemorySessionRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_rotation() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_rotation(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlSessionRepository::new(pool),
+        )
+        .await
+    }
+}
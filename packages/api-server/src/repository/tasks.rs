@@ -0,0 +1,1610 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+// local modules
+use super::{parse_date, parse_uuid, timestamp, try_get_optional};
+use api_shared::{
+    dto::{Recurrence, Tag, Task, TaskStatus},
+    prelude::LibError,
+};
+
+/// Storage for the users' tasks and their tags. Every call is scoped by
+/// the owner: the task or tag of another user reads as missing. The
+/// reminder queue alone spans every user, for `ReminderScheduler`.
+///
+/// Tags are matched by `Tag::key`; tasks name them as they were first
+/// entered, whatever spelling they are given later.
+#[async_trait]
+pub trait TaskRepository: Send + Sync {
+    /// Stores `task`, creating the tags the user doesn't have yet with
+    /// `Tag::default_color`, and queues its reminders, see
+    /// `plan_reminders`.
+    async fn insert(&self, user_id: Uuid, task: &Task) -> Result<(), LibError>;
+    /// Tasks of `user_id` in one of `statuses`, newest first; with a
+    /// `tag_key`, only the ones carrying that tag.
+    async fn list(
+        &self,
+        user_id: Uuid,
+        statuses: &[TaskStatus],
+        tag_key: Option<&str>,
+    ) -> Result<Vec<Task>, LibError>;
+    async fn find(
+        &self,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Task>, LibError>;
+    /// Replaces every field but the creation time, creating tags and
+    /// queuing reminders like `insert`. Returns `false` when the user has
+    /// no such task.
+    async fn update(
+        &self,
+        user_id: Uuid,
+        task: &Task,
+    ) -> Result<bool, LibError>;
+    /// Returns `false` when the user has no such task.
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool, LibError>;
+
+    /// When the occurrences of the user's tasks from `from` to `to`, both
+    /// included, were done, by task id and date.
+    async fn list_completions(
+        &self,
+        user_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashMap<(Uuid, NaiveDate), DateTime<Utc>>, LibError>;
+    /// Marks the occurrence of `task_id` on `date` done at `completed_at`,
+    /// keeping the time it was first done, or not done with `None`.
+    /// Returns `false` when the user has no such task.
+    async fn set_completion(
+        &self,
+        user_id: Uuid,
+        task_id: Uuid,
+        date: NaiveDate,
+        completed_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, LibError>;
+
+    /// Reminders not sent yet whose time came by `now`, of every user,
+    /// oldest first and at most `limit`. A postponed reminder goes by its
+    /// retry time.
+    async fn due_reminders(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<DueReminder>, LibError>;
+    /// When the earliest reminder not sent yet is due, maybe already,
+    /// counting retry times.
+    async fn next_reminder_at(&self)
+        -> Result<Option<DateTime<Utc>>, LibError>;
+    /// Records that `reminder` was sent. Returns `false` when its task was
+    /// saved in the meantime with another time for it, or deleted.
+    async fn mark_reminder_sent(
+        &self,
+        reminder: &DueReminder,
+        sent_at: DateTime<Utc>,
+    ) -> Result<bool, LibError>;
+    /// Counts a failed attempt at sending `reminder` and holds it back
+    /// until `retry_at`. Returns `false` like `mark_reminder_sent`.
+    async fn postpone_reminder(
+        &self,
+        reminder: &DueReminder,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool, LibError>;
+
+    /// Tags of `user_id`, most used first. Tags no task carries anymore
+    /// are kept, with their color.
+    async fn list_tags(&self, user_id: Uuid) -> Result<Vec<Tag>, LibError>;
+    /// Returns `false` when the user has no such tag.
+    async fn set_tag_color(
+        &self,
+        user_id: Uuid,
+        tag_key: &str,
+        color: &str,
+    ) -> Result<bool, LibError>;
+}
+
+/// A reminder whose time came, with what its notification needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueReminder {
+    pub task_id: Uuid,
+    pub user_id: Uuid,
+    pub title: String,
+    pub due_at: DateTime<Utc>,
+    /// minutes before `due_at`
+    pub offset_minutes: u32,
+    pub remind_at: DateTime<Utc>,
+    /// failed sends so far
+    pub attempts: u32,
+}
+
+/// A reminder in the queue of its task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedReminder {
+    pub remind_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    /// failed sends so far, see `TaskRepository::postpone_reminder`
+    pub attempts: u32,
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+impl QueuedReminder {
+    fn new(remind_at: DateTime<Utc>) -> Self {
+        Self {
+            remind_at,
+            sent_at: None,
+            attempts: 0,
+            retry_at: None,
+        }
+    }
+
+    /// When the scheduler should send it, unless it was sent.
+    fn send_at(&self) -> DateTime<Utc> {
+        self.retry_at.unwrap_or(self.remind_at)
+    }
+}
+
+/// Queued reminders of a task, by offset.
+type QueuedReminders = HashMap<u32, QueuedReminder>;
+
+/// The reminders to queue for `task` as it's saved, replacing `queued`.
+///
+/// Only open tasks that are due have some. A reminder keeps its row while
+/// its time stays the same, so it isn't sent twice, nor forgotten when it
+/// was missed while the scheduler was down, nor retried sooner after a
+/// failure. New ones whose time already passed when the task is saved are
+/// dropped rather than sent late.
+pub fn plan_reminders(
+    task: &Task,
+    queued: &QueuedReminders,
+) -> QueuedReminders {
+    let Some(due_at) = task.due_at else {
+        return HashMap::new();
+    };
+    if task.status != TaskStatus::Open {
+        return HashMap::new();
+    }
+
+    task.reminders
+        .iter()
+        .filter_map(|offset| {
+            let remind_at = due_at - Duration::minutes(*offset as i64);
+            match queued.get(offset) {
+                Some(queued) if queued.remind_at == remind_at => {
+                    Some((*offset, *queued))
+                }
+                _ if remind_at > task.updated_at => {
+                    Some((*offset, QueuedReminder::new(remind_at)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Process-local store, used by tests and throwaway instances.
+#[derive(Debug, Default)]
+pub struct InMemoryTaskRepository {
+    store: Mutex<InMemoryTasks>,
+}
+
+#[derive(Debug, Default)]
+struct InMemoryTasks {
+    /// owner and task, by task id; the task holds tag keys
+    tasks: HashMap<Uuid, (Uuid, Task)>,
+    /// name and color, by owner and tag key
+    tags: HashMap<(Uuid, String), (String, String)>,
+    /// completion time, by task id and occurrence date
+    completions: HashMap<(Uuid, NaiveDate), DateTime<Utc>>,
+    /// queued reminders, by task id
+    reminders: HashMap<Uuid, QueuedReminders>,
+}
+
+impl InMemoryTasks {
+    /// `task` holding the keys of its tags, created if needed.
+    fn link_tags(&mut self, user_id: Uuid, task: &Task) -> Task {
+        let mut keys = Vec::with_capacity(task.tags.len());
+        for name in &task.tags {
+            let key = Tag::key(name);
+            self.tags
+                .entry((user_id, key.clone()))
+                .or_insert_with(|| {
+                    (name.clone(), Tag::default_color(name).to_string())
+                });
+            keys.push(key);
+        }
+
+        Task {
+            tags: keys,
+            ..task.clone()
+        }
+    }
+
+    fn schedule_reminders(&mut self, task: &Task) {
+        let queued = self
+            .reminders
+            .remove(&task.id)
+            .unwrap_or_default();
+        self.reminders
+            .insert(task.id, plan_reminders(task, &queued));
+    }
+
+    /// `task` naming its tags.
+    fn resolve_tags(&self, user_id: Uuid, task: &Task) -> Task {
+        let names = task
+            .tags
+            .iter()
+            .filter_map(|key| self.tags.get(&(user_id, key.clone())))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        Task {
+            tags: names,
+            ..task.clone()
+        }
+    }
+}
+
+#[async_trait]
+impl TaskRepository for InMemoryTaskRepository {
+    async fn insert(&self, user_id: Uuid, task: &Task) -> Result<(), LibError> {
+        let mut store = self.store.lock().unwrap();
+        let linked = store.link_tags(user_id, task);
+        store
+            .tasks
+            .insert(task.id, (user_id, linked));
+        store.schedule_reminders(task);
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        user_id: Uuid,
+        statuses: &[TaskStatus],
+        tag_key: Option<&str>,
+    ) -> Result<Vec<Task>, LibError> {
+        let store = self.store.lock().unwrap();
+        let mut tasks: Vec<Task> = store
+            .tasks
+            .values()
+            .filter(|(owner, task)| {
+                *owner == user_id
+                    && statuses.contains(&task.status)
+                    && tag_key.is_none_or(|key| {
+                        task.tags.iter().any(|tag| tag == key)
+                    })
+            })
+            .map(|(_, task)| store.resolve_tags(user_id, task))
+            .collect();
+        tasks.sort_by_key(|task| (std::cmp::Reverse(task.created_at), task.id));
+
+        Ok(tasks)
+    }
+
+    async fn find(
+        &self,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Task>, LibError> {
+        let store = self.store.lock().unwrap();
+
+        Ok(store
+            .tasks
+            .get(&id)
+            .filter(|(owner, _)| *owner == user_id)
+            .map(|(_, task)| store.resolve_tags(user_id, task)))
+    }
+
+    async fn update(
+        &self,
+        user_id: Uuid,
+        task: &Task,
+    ) -> Result<bool, LibError> {
+        let mut store = self.store.lock().unwrap();
+        let created_at = match store.tasks.get(&task.id) {
+            Some((owner, stored)) if *owner == user_id => stored.created_at,
+            _ => return Ok(false),
+        };
+        let linked = store.link_tags(user_id, task);
+        store.tasks.insert(
+            task.id,
+            (
+                user_id,
+                Task {
+                    created_at,
+                    ..linked
+                },
+            ),
+        );
+        store.schedule_reminders(task);
+
+        Ok(true)
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool, LibError> {
+        let mut store = self.store.lock().unwrap();
+        match store.tasks.get(&id) {
+            Some((owner, _)) if *owner == user_id => {
+                store.tasks.remove(&id);
+                store
+                    .completions
+                    .retain(|(task_id, _), _| *task_id != id);
+                store.reminders.remove(&id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_completions(
+        &self,
+        user_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashMap<(Uuid, NaiveDate), DateTime<Utc>>, LibError> {
+        let store = self.store.lock().unwrap();
+
+        Ok(store
+            .completions
+            .iter()
+            .filter(|((task_id, date), _)| {
+                (from..=to).contains(date)
+                    && store
+                        .tasks
+                        .get(task_id)
+                        .is_some_and(|(owner, _)| *owner == user_id)
+            })
+            .map(|(key, completed_at)| (*key, *completed_at))
+            .collect())
+    }
+
+    async fn set_completion(
+        &self,
+        user_id: Uuid,
+        task_id: Uuid,
+        date: NaiveDate,
+        completed_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, LibError> {
+        let mut store = self.store.lock().unwrap();
+        match store.tasks.get(&task_id) {
+            Some((owner, _)) if *owner == user_id => {}
+            _ => return Ok(false),
+        }
+        match completed_at {
+            Some(completed_at) => {
+                store
+                    .completions
+                    .entry((task_id, date))
+                    .or_insert(completed_at);
+            }
+            None => {
+                store
+                    .completions
+                    .remove(&(task_id, date));
+            }
+        }
+
+        Ok(true)
+    }
+
+    async fn due_reminders(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<DueReminder>, LibError> {
+        let store = self.store.lock().unwrap();
+        let mut due: Vec<(DateTime<Utc>, DueReminder)> = store
+            .reminders
+            .iter()
+            .flat_map(|(task_id, queued)| {
+                queued
+                    .iter()
+                    .map(move |(offset, queued)| (*task_id, *offset, *queued))
+            })
+            .filter(|(_, _, queued)| {
+                queued.sent_at.is_none() && queued.send_at() <= now
+            })
+            .filter_map(|(task_id, offset, queued)| {
+                let (user_id, task) = store.tasks.get(&task_id)?;
+                let reminder = DueReminder {
+                    task_id,
+                    user_id: *user_id,
+                    title: task.title.clone(),
+                    due_at: task.due_at?,
+                    offset_minutes: offset,
+                    remind_at: queued.remind_at,
+                    attempts: queued.attempts,
+                };
+                Some((queued.send_at(), reminder))
+            })
+            .collect();
+        due.sort_by_key(|(send_at, reminder)| (*send_at, reminder.task_id));
+
+        Ok(due
+            .into_iter()
+            .take(limit)
+            .map(|(_, reminder)| reminder)
+            .collect())
+    }
+
+    async fn next_reminder_at(
+        &self,
+    ) -> Result<Option<DateTime<Utc>>, LibError> {
+        let store = self.store.lock().unwrap();
+
+        Ok(store
+            .reminders
+            .values()
+            .flat_map(|queued| queued.values())
+            .filter(|queued| queued.sent_at.is_none())
+            .map(QueuedReminder::send_at)
+            .min())
+    }
+
+    async fn mark_reminder_sent(
+        &self,
+        reminder: &DueReminder,
+        sent_at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let mut store = self.store.lock().unwrap();
+        let queued = store
+            .reminders
+            .get_mut(&reminder.task_id)
+            .and_then(|queued| queued.get_mut(&reminder.offset_minutes));
+        match queued {
+            Some(queued)
+                if queued.remind_at == reminder.remind_at
+                    && queued.sent_at.is_none() =>
+            {
+                queued.sent_at = Some(sent_at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn postpone_reminder(
+        &self,
+        reminder: &DueReminder,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let mut store = self.store.lock().unwrap();
+        let queued = store
+            .reminders
+            .get_mut(&reminder.task_id)
+            .and_then(|queued| queued.get_mut(&reminder.offset_minutes));
+        match queued {
+            Some(queued)
+                if queued.remind_at == reminder.remind_at
+                    && queued.sent_at.is_none() =>
+            {
+                queued.attempts += 1;
+                queued.retry_at = Some(retry_at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_tags(&self, user_id: Uuid) -> Result<Vec<Tag>, LibError> {
+        let store = self.store.lock().unwrap();
+        let mut tags: Vec<(String, Tag)> = store
+            .tags
+            .iter()
+            .filter(|((owner, _), _)| *owner == user_id)
+            .map(|((_, key), (name, color))| {
+                let usage_count = store
+                    .tasks
+                    .values()
+                    .filter(|(owner, task)| {
+                        *owner == user_id && task.tags.contains(key)
+                    })
+                    .count();
+                let tag = Tag {
+                    name: name.clone(),
+                    color: color.clone(),
+                    usage_count: usage_count as u32,
+                };
+                (key.clone(), tag)
+            })
+            .collect();
+        tags.sort_by(|(a_key, a), (b_key, b)| {
+            b.usage_count
+                .cmp(&a.usage_count)
+                .then_with(|| a_key.cmp(b_key))
+        });
+
+        Ok(tags
+            .into_iter()
+            .map(|(_, tag)| tag)
+            .collect())
+    }
+
+    async fn set_tag_color(
+        &self,
+        user_id: Uuid,
+        tag_key: &str,
+        color: &str,
+    ) -> Result<bool, LibError> {
+        let mut store = self.store.lock().unwrap();
+        match store
+            .tags
+            .get_mut(&(user_id, tag_key.to_string()))
+        {
+            Some((_, stored)) => {
+                *stored = color.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Store backed by SQLite or PostgreSQL through `sqlx`'s `Any` driver.
+#[derive(Debug, Clone)]
+pub struct SqlTaskRepository {
+    pool: AnyPool,
+}
+
+impl SqlTaskRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+
+    /// Names of the tags of the user's tasks, or of `task_id` alone, by
+    /// task id.
+    async fn tag_names(
+        &self,
+        user_id: Uuid,
+        task_id: Option<Uuid>,
+    ) -> Result<HashMap<String, Vec<String>>, LibError> {
+        let by_task = match task_id {
+            Some(_) => " AND task_tags.task_id = $2",
+            None => "",
+        };
+        let sql = format!(
+            "SELECT task_tags.task_id, tags.name FROM task_tags \
+             JOIN tags ON tags.user_id = task_tags.user_id \
+             AND tags.name_key = task_tags.tag_key \
+             WHERE task_tags.user_id = $1{by_task} \
+             ORDER BY task_tags.position"
+        );
+        let mut query = sqlx::query(&sql).bind(user_id.to_string());
+        if let Some(task_id) = task_id {
+            query = query.bind(task_id.to_string());
+        }
+
+        let mut names: HashMap<String, Vec<String>> = HashMap::new();
+        for row in query.fetch_all(&self.pool).await? {
+            names
+                .entry(row.try_get("task_id")?)
+                .or_default()
+                .push(row.try_get("name")?);
+        }
+        Ok(names)
+    }
+}
+
+const TASK_COLUMNS: &str = "id, title, summary, content, status, \
+                            created_at, updated_at, completed_at, \
+                            recurrence_rule, recurrence_starts_on, \
+                            recurrence_exceptions, starts_at, due_at, \
+                            reminders";
+
+#[async_trait]
+impl TaskRepository for SqlTaskRepository {
+    async fn insert(&self, user_id: Uuid, task: &Task) -> Result<(), LibError> {
+        let (rule, starts_on, exceptions) = recurrence_columns(task);
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO tasks \
+             (id, user_id, title, summary, content, status, created_at, \
+             updated_at, completed_at, recurrence_rule, \
+             recurrence_starts_on, recurrence_exceptions, starts_at, due_at, \
+             reminders) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, \
+             $14, $15)",
+        )
+        .bind(task.id.to_string())
+        .bind(user_id.to_string())
+        .bind(&task.title)
+        .bind(&task.summary)
+        .bind(&task.content)
+        .bind(task.status.as_str())
+        .bind(task.created_at.timestamp())
+        .bind(task.updated_at.timestamp())
+        .bind(
+            task.completed_at
+                .map(|at| at.timestamp()),
+        )
+        .bind(rule)
+        .bind(starts_on)
+        .bind(exceptions)
+        .bind(task.starts_at.map(|at| at.timestamp()))
+        .bind(task.due_at.map(|at| at.timestamp()))
+        .bind(reminders_column(task))
+        .execute(&mut *tx)
+        .await?;
+        link_tags(&mut tx, user_id, task).await?;
+        schedule_reminders(&mut tx, task).await?;
+
+        Ok(tx.commit().await?)
+    }
+
+    async fn list(
+        &self,
+        user_id: Uuid,
+        statuses: &[TaskStatus],
+        tag_key: Option<&str>,
+    ) -> Result<Vec<Task>, LibError> {
+        if statuses.is_empty() {
+            return Ok(Vec::new());
+        }
+        // $1 is the user, then the tag if any, then the statuses
+        let first_status = if tag_key.is_some() { 3 } else { 2 };
+        let by_tag = match tag_key {
+            Some(_) => {
+                " AND id IN (SELECT task_id FROM task_tags \
+                 WHERE user_id = $1 AND tag_key = $2)"
+            }
+            None => "",
+        };
+        let placeholders: Vec<String> = (0..statuses.len())
+            .map(|i| format!("${}", first_status + i))
+            .collect();
+        let sql = format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE user_id = $1{by_tag} \
+             AND status IN ({}) ORDER BY created_at DESC, id",
+            placeholders.join(", ")
+        );
+        let mut query = sqlx::query(&sql).bind(user_id.to_string());
+        if let Some(tag_key) = tag_key {
+            query = query.bind(tag_key);
+        }
+        for status in statuses {
+            query = query.bind(status.as_str());
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        let mut names = self.tag_names(user_id, None).await?;
+        rows.iter()
+            .map(|row| task_from_row(row, &mut names))
+            .collect()
+    }
+
+    async fn find(
+        &self,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Task>, LibError> {
+        let row = sqlx::query(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE id = $1 AND user_id = $2"
+        ))
+        .bind(id.to_string())
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let mut names = self
+            .tag_names(user_id, Some(id))
+            .await?;
+        task_from_row(&row, &mut names).map(Some)
+    }
+
+    async fn update(
+        &self,
+        user_id: Uuid,
+        task: &Task,
+    ) -> Result<bool, LibError> {
+        let (rule, starts_on, exceptions) = recurrence_columns(task);
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE tasks SET title = $1, summary = $2, content = $3, \
+             status = $4, updated_at = $5, completed_at = $6, \
+             recurrence_rule = $7, recurrence_starts_on = $8, \
+             recurrence_exceptions = $9, starts_at = $10, due_at = $11, \
+             reminders = $12 \
+             WHERE id = $13 AND user_id = $14",
+        )
+        .bind(&task.title)
+        .bind(&task.summary)
+        .bind(&task.content)
+        .bind(task.status.as_str())
+        .bind(task.updated_at.timestamp())
+        .bind(
+            task.completed_at
+                .map(|at| at.timestamp()),
+        )
+        .bind(rule)
+        .bind(starts_on)
+        .bind(exceptions)
+        .bind(task.starts_at.map(|at| at.timestamp()))
+        .bind(task.due_at.map(|at| at.timestamp()))
+        .bind(reminders_column(task))
+        .bind(task.id.to_string())
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query("DELETE FROM task_tags WHERE task_id = $1")
+            .bind(task.id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        link_tags(&mut tx, user_id, task).await?;
+        schedule_reminders(&mut tx, task).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool, LibError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM task_tags WHERE task_id = $1 AND user_id = $2",
+        )
+        .bind(id.to_string())
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+        for table in ["task_completions", "task_reminders"] {
+            sqlx::query(&format!(
+                "DELETE FROM {table} WHERE task_id IN \
+                 (SELECT id FROM tasks WHERE id = $1 AND user_id = $2)"
+            ))
+            .bind(id.to_string())
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+        let result =
+            sqlx::query("DELETE FROM tasks WHERE id = $1 AND user_id = $2")
+                .bind(id.to_string())
+                .bind(user_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn list_completions(
+        &self,
+        user_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashMap<(Uuid, NaiveDate), DateTime<Utc>>, LibError> {
+        // ISO dates sort like the days they name
+        let rows = sqlx::query(
+            "SELECT task_completions.task_id, task_completions.occurs_on, \
+             task_completions.completed_at FROM task_completions \
+             JOIN tasks ON tasks.id = task_completions.task_id \
+             WHERE tasks.user_id = $1 \
+             AND task_completions.occurs_on BETWEEN $2 AND $3",
+        )
+        .bind(user_id.to_string())
+        .bind(from.to_string())
+        .bind(to.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let task_id: String = row.try_get("task_id")?;
+                let occurs_on: String = row.try_get("occurs_on")?;
+                let completed_at: i64 = row.try_get("completed_at")?;
+                Ok((
+                    (parse_uuid(&task_id)?, parse_date(&occurs_on)?),
+                    timestamp(completed_at),
+                ))
+            })
+            .collect()
+    }
+
+    async fn set_completion(
+        &self,
+        user_id: Uuid,
+        task_id: Uuid,
+        date: NaiveDate,
+        completed_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, LibError> {
+        let mut tx = self.pool.begin().await?;
+        let owned =
+            sqlx::query("SELECT 1 FROM tasks WHERE id = $1 AND user_id = $2")
+                .bind(task_id.to_string())
+                .bind(user_id.to_string())
+                .fetch_optional(&mut *tx)
+                .await?;
+        if owned.is_none() {
+            return Ok(false);
+        }
+        match completed_at {
+            Some(completed_at) => {
+                sqlx::query(
+                    "INSERT INTO task_completions \
+                     (task_id, occurs_on, completed_at) VALUES ($1, $2, $3) \
+                     ON CONFLICT (task_id, occurs_on) DO NOTHING",
+                )
+                .bind(task_id.to_string())
+                .bind(date.to_string())
+                .bind(completed_at.timestamp())
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query(
+                    "DELETE FROM task_completions \
+                     WHERE task_id = $1 AND occurs_on = $2",
+                )
+                .bind(task_id.to_string())
+                .bind(date.to_string())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn due_reminders(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<DueReminder>, LibError> {
+        let rows = sqlx::query(
+            "SELECT task_reminders.task_id, task_reminders.offset_minutes, \
+             task_reminders.remind_at, task_reminders.attempts, \
+             tasks.user_id, tasks.title, tasks.due_at FROM task_reminders \
+             JOIN tasks ON tasks.id = task_reminders.task_id \
+             WHERE task_reminders.sent_at IS NULL \
+             AND COALESCE(task_reminders.retry_at, task_reminders.remind_at) \
+             <= $1 \
+             ORDER BY \
+             COALESCE(task_reminders.retry_at, task_reminders.remind_at), \
+             task_reminders.task_id \
+             LIMIT $2",
+        )
+        .bind(now.timestamp())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let task_id: String = row.try_get("task_id")?;
+                let user_id: String = row.try_get("user_id")?;
+                let offset_minutes: i64 = row.try_get("offset_minutes")?;
+                let remind_at: i64 = row.try_get("remind_at")?;
+                let attempts: i64 = row.try_get("attempts")?;
+                let due_at: i64 = row.try_get("due_at")?;
+                Ok(DueReminder {
+                    task_id: parse_uuid(&task_id)?,
+                    user_id: parse_uuid(&user_id)?,
+                    title: row.try_get("title")?,
+                    due_at: timestamp(due_at),
+                    offset_minutes: u32::try_from(offset_minutes)
+                        .map_err(LibError::internal)?,
+                    remind_at: timestamp(remind_at),
+                    attempts: u32::try_from(attempts)
+                        .map_err(LibError::internal)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn next_reminder_at(
+        &self,
+    ) -> Result<Option<DateTime<Utc>>, LibError> {
+        let row = sqlx::query(
+            "SELECT MIN(COALESCE(retry_at, remind_at)) AS remind_at \
+             FROM task_reminders WHERE sent_at IS NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(try_get_optional::<i64>(&row, "remind_at")?.map(timestamp))
+    }
+
+    async fn mark_reminder_sent(
+        &self,
+        reminder: &DueReminder,
+        sent_at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let result = sqlx::query(
+            "UPDATE task_reminders SET sent_at = $1 \
+             WHERE task_id = $2 AND offset_minutes = $3 AND remind_at = $4 \
+             AND sent_at IS NULL",
+        )
+        .bind(sent_at.timestamp())
+        .bind(reminder.task_id.to_string())
+        .bind(reminder.offset_minutes as i64)
+        .bind(reminder.remind_at.timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn postpone_reminder(
+        &self,
+        reminder: &DueReminder,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let result = sqlx::query(
+            "UPDATE task_reminders SET attempts = attempts + 1, \
+             retry_at = $1 \
+             WHERE task_id = $2 AND offset_minutes = $3 AND remind_at = $4 \
+             AND sent_at IS NULL",
+        )
+        .bind(retry_at.timestamp())
+        .bind(reminder.task_id.to_string())
+        .bind(reminder.offset_minutes as i64)
+        .bind(reminder.remind_at.timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn list_tags(&self, user_id: Uuid) -> Result<Vec<Tag>, LibError> {
+        let rows = sqlx::query(
+            "SELECT tags.name, tags.color, \
+             COUNT(task_tags.task_id) AS usage_count FROM tags \
+             LEFT JOIN task_tags ON task_tags.user_id = tags.user_id \
+             AND task_tags.tag_key = tags.name_key \
+             WHERE tags.user_id = $1 \
+             GROUP BY tags.name_key, tags.name, tags.color \
+             ORDER BY usage_count DESC, tags.name_key",
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let usage_count: i64 = row.try_get("usage_count")?;
+                Ok(Tag {
+                    name: row.try_get("name")?,
+                    color: row.try_get("color")?,
+                    usage_count: u32::try_from(usage_count)
+                        .map_err(LibError::internal)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn set_tag_color(
+        &self,
+        user_id: Uuid,
+        tag_key: &str,
+        color: &str,
+    ) -> Result<bool, LibError> {
+        let result = sqlx::query(
+            "UPDATE tags SET color = $1 WHERE user_id = $2 AND name_key = $3",
+        )
+        .bind(color)
+        .bind(user_id.to_string())
+        .bind(tag_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+/// Points `task` to its tags, creating the ones the user doesn't have.
+async fn link_tags(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    user_id: Uuid,
+    task: &Task,
+) -> Result<(), LibError> {
+    for (position, name) in task.tags.iter().enumerate() {
+        let key = Tag::key(name);
+        sqlx::query(
+            "INSERT INTO tags (user_id, name_key, name, color, created_at) \
+             VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (user_id, name_key) DO NOTHING",
+        )
+        .bind(user_id.to_string())
+        .bind(&key)
+        .bind(name)
+        .bind(Tag::default_color(name))
+        .bind(task.updated_at.timestamp())
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO task_tags (task_id, user_id, tag_key, position) \
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(task.id.to_string())
+        .bind(user_id.to_string())
+        .bind(&key)
+        .bind(position as i64)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the queued reminders of `task`, see `plan_reminders`.
+async fn schedule_reminders(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    task: &Task,
+) -> Result<(), LibError> {
+    let rows = sqlx::query(
+        "SELECT offset_minutes, remind_at, sent_at, attempts, retry_at \
+         FROM task_reminders WHERE task_id = $1",
+    )
+    .bind(task.id.to_string())
+    .fetch_all(&mut **tx)
+    .await?;
+    let mut queued = QueuedReminders::new();
+    for row in &rows {
+        let offset_minutes: i64 = row.try_get("offset_minutes")?;
+        let remind_at: i64 = row.try_get("remind_at")?;
+        let sent_at: Option<i64> = try_get_optional(row, "sent_at")?;
+        let attempts: i64 = row.try_get("attempts")?;
+        let retry_at: Option<i64> = try_get_optional(row, "retry_at")?;
+        queued.insert(
+            u32::try_from(offset_minutes).map_err(LibError::internal)?,
+            QueuedReminder {
+                remind_at: timestamp(remind_at),
+                sent_at: sent_at.map(timestamp),
+                attempts: u32::try_from(attempts)
+                    .map_err(LibError::internal)?,
+                retry_at: retry_at.map(timestamp),
+            },
+        );
+    }
+
+    sqlx::query("DELETE FROM task_reminders WHERE task_id = $1")
+        .bind(task.id.to_string())
+        .execute(&mut **tx)
+        .await?;
+    for (offset_minutes, queued) in plan_reminders(task, &queued) {
+        sqlx::query(
+            "INSERT INTO task_reminders \
+             (task_id, offset_minutes, remind_at, sent_at, attempts, \
+             retry_at) \
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(task.id.to_string())
+        .bind(offset_minutes as i64)
+        .bind(queued.remind_at.timestamp())
+        .bind(queued.sent_at.map(|at| at.timestamp()))
+        .bind(queued.attempts as i64)
+        .bind(queued.retry_at.map(|at| at.timestamp()))
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Reminder offsets of `task`, comma-separated.
+fn reminders_column(task: &Task) -> String {
+    let offsets: Vec<String> = task
+        .reminders
+        .iter()
+        .map(u32::to_string)
+        .collect();
+
+    offsets.join(",")
+}
+
+/// Rule, first day and comma-separated exceptions of a recurring `task`.
+fn recurrence_columns(
+    task: &Task,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let Some(recurrence) = &task.recurrence else {
+        return (None, None, None);
+    };
+    let exceptions: Vec<String> = recurrence
+        .exceptions
+        .iter()
+        .map(NaiveDate::to_string)
+        .collect();
+
+    (
+        Some(recurrence.rule.clone()),
+        Some(recurrence.starts_on.to_string()),
+        Some(exceptions.join(",")),
+    )
+}
+
+/// The recurrence of `row`, if its task repeats.
+fn recurrence_from_row(row: &AnyRow) -> Result<Option<Recurrence>, LibError> {
+    let Some(rule) = try_get_optional::<String>(row, "recurrence_rule")? else {
+        return Ok(None);
+    };
+    let starts_on: String = row.try_get("recurrence_starts_on")?;
+    let exceptions: String = row.try_get("recurrence_exceptions")?;
+
+    Ok(Some(Recurrence {
+        rule,
+        starts_on: parse_date(&starts_on)?,
+        exceptions: exceptions
+            .split(',')
+            .filter(|date| !date.is_empty())
+            .map(parse_date)
+            .collect::<Result<_, _>>()?,
+    }))
+}
+
+/// The task of `row`, taking its tag names out of `names`, by task id.
+fn task_from_row(
+    row: &AnyRow,
+    names: &mut HashMap<String, Vec<String>>,
+) -> Result<Task, LibError> {
+    let id: String = row.try_get("id")?;
+    let status: String = row.try_get("status")?;
+    let created_at: i64 = row.try_get("created_at")?;
+    let updated_at: i64 = row.try_get("updated_at")?;
+    let completed_at: Option<i64> = try_get_optional(row, "completed_at")?;
+    let starts_at: Option<i64> = try_get_optional(row, "starts_at")?;
+    let due_at: Option<i64> = try_get_optional(row, "due_at")?;
+    // NULL for the tasks saved before reminders existed
+    let reminders: Option<String> = try_get_optional(row, "reminders")?;
+
+    Ok(Task {
+        id: parse_uuid(&id)?,
+        title: row.try_get("title")?,
+        summary: row.try_get("summary")?,
+        content: row.try_get("content")?,
+        tags: names.remove(&id).unwrap_or_default(),
+        status: TaskStatus::parse(&status).ok_or_else(|| {
+            LibError::internal(std::io::Error::other(format!(
+                "unknown task status {status:?}"
+            )))
+        })?,
+        created_at: timestamp(created_at),
+        updated_at: timestamp(updated_at),
+        completed_at: completed_at.map(timestamp),
+        recurrence: recurrence_from_row(row)?,
+        starts_at: starts_at.map(timestamp),
+        due_at: due_at.map(timestamp),
+        reminders: reminders
+            .unwrap_or_default()
+            .split(',')
+            .filter(|offset| !offset.is_empty())
+            .map(|offset| {
+                offset
+                    .parse()
+                    .map_err(LibError::internal)
+            })
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{
+        connect, now, InMemoryUserRepository, SqlUserRepository, User,
+        UserRepository,
+    };
+
+    async fn mock_user(users: &dyn UserRepository, name: &str) -> Uuid {
+        let user = User {
+            id: Uuid::new_v4(),
+            email: format!("{name}@email.com"),
+            username: name.to_string(),
+            password_hash: "$argon2id$v=19$m=256,t=1,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        };
+        users.insert(&user).await.unwrap();
+        user.id
+    }
+
+    async fn assert_scoped_by_owner(
+        users: &dyn UserRepository,
+        tasks: &dyn TaskRepository,
+    ) -> miette::Result<()> {
+        let owner = mock_user(users, "owner").await;
+        let other = mock_user(users, "other").await;
+        let mut task = Task {
+            id: Uuid::new_v4(),
+            title: "Water the plants".to_string(),
+            summary: String::new(),
+            content: "The ones on the balcony".to_string(),
+            tags: vec!["home".to_string(), "daily".to_string()],
+            status: TaskStatus::Open,
+            created_at: now(),
+            updated_at: now(),
+            completed_at: None,
+            recurrence: None,
+            starts_at: None,
+            due_at: None,
+            reminders: Vec::new(),
+        };
+        tasks.insert(owner, &task).await?;
+
+        let found = tasks.find(owner, task.id).await?;
+        let hidden = tasks.find(other, task.id).await?;
+        miette::ensure!(
+            found.as_ref() == Some(&task) && hidden.is_none(),
+            "Error: unexpected task {found:?}, other user saw {hidden:?}"
+        );
+
+        task.status = TaskStatus::Done;
+        task.completed_at = Some(now());
+        task.tags = vec!["daily".to_string()];
+        let updated_by_other = tasks.update(other, &task).await?;
+        let updated = tasks.update(owner, &task).await?;
+        let done = tasks
+            .list(owner, &[TaskStatus::Done], None)
+            .await?;
+        let open = tasks
+            .list(owner, &[TaskStatus::Open], None)
+            .await?;
+        miette::ensure!(
+            !updated_by_other
+                && updated
+                && done == [task.clone()]
+                && open.is_empty(),
+            "Error: unexpected lists {done:?} {open:?}"
+        );
+
+        let deleted_by_other = tasks.delete(other, task.id).await?;
+        let deleted = tasks.delete(owner, task.id).await?;
+        miette::ensure!(
+            !deleted_by_other
+                && deleted
+                && tasks
+                    .find(owner, task.id)
+                    .await?
+                    .is_none(),
+            "Error: task not deleted by its owner alone"
+        );
+        Ok(())
+    }
+
+    async fn assert_tags(
+        users: &dyn UserRepository,
+        tasks: &dyn TaskRepository,
+    ) -> miette::Result<()> {
+        let owner = mock_user(users, "owner").await;
+        let task = |tags: &[&str]| Task {
+            id: Uuid::new_v4(),
+            title: "Task".to_string(),
+            summary: String::new(),
+            content: String::new(),
+            tags: tags
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect(),
+            status: TaskStatus::Open,
+            created_at: now(),
+            updated_at: now(),
+            completed_at: None,
+            recurrence: None,
+            starts_at: None,
+            due_at: None,
+            reminders: Vec::new(),
+        };
+        let running = task(&["Health", "daily"]);
+        tasks.insert(owner, &running).await?;
+        let reading = task(&["DAILY"]);
+        tasks.insert(owner, &reading).await?;
+        let recolored = tasks
+            .set_tag_color(owner, "health", "#000000")
+            .await?;
+        let unknown = tasks
+            .set_tag_color(owner, "work", "#000000")
+            .await?;
+
+        let tags = tasks.list_tags(owner).await?;
+        let daily = tasks
+            .list(owner, &[TaskStatus::Open], Some("daily"))
+            .await?;
+        let reading = tasks
+            .find(owner, reading.id)
+            .await?
+            .unwrap();
+        let summary: Vec<(&str, &str, u32)> = tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.color.as_str(), tag.usage_count))
+            .collect();
+        miette::ensure!(
+            recolored
+                && !unknown
+                && summary
+                    == [
+                        ("daily", Tag::default_color("daily"), 2),
+                        ("Health", "#000000", 1),
+                    ]
+                && daily.len() == 2
+                && reading.tags == ["daily"],
+            "Error: unexpected tags {tags:?} {reading:?}"
+        );
+        Ok(())
+    }
+
+    async fn assert_completions(
+        users: &dyn UserRepository,
+        tasks: &dyn TaskRepository,
+    ) -> miette::Result<()> {
+        let owner = mock_user(users, "owner").await;
+        let other = mock_user(users, "other").await;
+        let date =
+            |value| NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap();
+        let task = Task {
+            id: Uuid::new_v4(),
+            title: "Daily work".to_string(),
+            summary: String::new(),
+            content: String::new(),
+            tags: Vec::new(),
+            status: TaskStatus::Open,
+            created_at: now(),
+            updated_at: now(),
+            completed_at: None,
+            recurrence: Some(Recurrence {
+                rule: "FREQ=DAILY".to_string(),
+                starts_on: date("2026-10-01"),
+                exceptions: vec![date("2026-10-03"), date("2026-10-04")],
+            }),
+            starts_at: None,
+            due_at: None,
+            reminders: Vec::new(),
+        };
+        tasks.insert(owner, &task).await?;
+        let found = tasks.find(owner, task.id).await?;
+        miette::ensure!(
+            found.as_ref() == Some(&task),
+            "Error: unexpected recurring task {found:?}"
+        );
+
+        let done_at = now();
+        let by_other = tasks
+            .set_completion(other, task.id, date("2026-10-01"), Some(done_at))
+            .await?;
+        for day in ["2026-10-01", "2026-10-02", "2026-10-05"] {
+            tasks
+                .set_completion(owner, task.id, date(day), Some(done_at))
+                .await?;
+        }
+        // done again later, still done at first
+        tasks
+            .set_completion(
+                owner,
+                task.id,
+                date("2026-10-01"),
+                Some(timestamp(0)),
+            )
+            .await?;
+        tasks
+            .set_completion(owner, task.id, date("2026-10-02"), None)
+            .await?;
+        let completions = tasks
+            .list_completions(owner, date("2026-10-01"), date("2026-10-04"))
+            .await?;
+        let others = tasks
+            .list_completions(other, date("2026-10-01"), date("2026-10-31"))
+            .await?;
+        miette::ensure!(
+            !by_other
+                && completions
+                    == HashMap::from([(
+                        (task.id, date("2026-10-01")),
+                        done_at
+                    )])
+                && others.is_empty(),
+            "Error: unexpected completions {completions:?}"
+        );
+
+        tasks.delete(owner, task.id).await?;
+        let completions = tasks
+            .list_completions(owner, date("2026-10-01"), date("2026-10-31"))
+            .await?;
+        miette::ensure!(
+            completions.is_empty(),
+            "Error: completions kept after the task {completions:?}"
+        );
+        Ok(())
+    }
+
+    async fn assert_reminders(
+        users: &dyn UserRepository,
+        tasks: &dyn TaskRepository,
+    ) -> miette::Result<()> {
+        let owner = mock_user(users, "owner").await;
+        let saved_at = now();
+        let hours = |hours: i64| saved_at + Duration::hours(hours);
+        let mut task = Task {
+            id: Uuid::new_v4(),
+            title: "Pay the rent".to_string(),
+            summary: String::new(),
+            content: String::new(),
+            tags: Vec::new(),
+            status: TaskStatus::Open,
+            created_at: saved_at,
+            updated_at: saved_at,
+            completed_at: None,
+            recurrence: None,
+            starts_at: None,
+            due_at: Some(hours(2)),
+            // three hours before is already past, so it's dropped
+            reminders: vec![180, 60, 10],
+        };
+        tasks.insert(owner, &task).await?;
+
+        let next = tasks.next_reminder_at().await?;
+        let due = tasks
+            .due_reminders(hours(1), 10)
+            .await?;
+        miette::ensure!(
+            next == Some(hours(1))
+                && due
+                    == [DueReminder {
+                        task_id: task.id,
+                        user_id: owner,
+                        title: task.title.clone(),
+                        due_at: hours(2),
+                        offset_minutes: 60,
+                        remind_at: hours(1),
+                        attempts: 0,
+                    }],
+            "Error: unexpected reminders {next:?} {due:?}"
+        );
+
+        let marked = tasks
+            .mark_reminder_sent(&due[0], hours(1))
+            .await?;
+        let marked_twice = tasks
+            .mark_reminder_sent(&due[0], hours(1))
+            .await?;
+        // saving the task again doesn't queue the sent reminder again
+        task.title = "Pay the rent today".to_string();
+        task.updated_at = hours(1);
+        tasks.update(owner, &task).await?;
+        let due = tasks
+            .due_reminders(hours(3), 10)
+            .await?;
+        miette::ensure!(
+            marked
+                && !marked_twice
+                && due.len() == 1
+                && due[0].offset_minutes == 10
+                && due[0].title == task.title,
+            "Error: unexpected reminders after sending {due:?}"
+        );
+
+        // a failed one waits for its retry, even when the task is saved
+        let postponed = tasks
+            .postpone_reminder(&due[0], hours(5))
+            .await?;
+        tasks.update(owner, &task).await?;
+        let held = tasks
+            .due_reminders(hours(3), 10)
+            .await?;
+        let retried = tasks
+            .due_reminders(hours(5), 10)
+            .await?;
+        let next = tasks.next_reminder_at().await?;
+        miette::ensure!(
+            postponed
+                && held.is_empty()
+                && retried.len() == 1
+                && retried[0].attempts == 1
+                && next == Some(hours(5)),
+            "Error: unexpected reminders after a failure {retried:?}"
+        );
+
+        // postponing it queues them again
+        task.due_at = Some(hours(4));
+        tasks.update(owner, &task).await?;
+        let due = tasks.due_reminders(hours(4), 1).await?;
+        let next = tasks.next_reminder_at().await?;
+        miette::ensure!(
+            due.len() == 1
+                && due[0].remind_at == hours(3)
+                && next == Some(hours(3)),
+            "Error: unexpected reminders after postponing {due:?}"
+        );
+
+        task.status = TaskStatus::Done;
+        tasks.update(owner, &task).await?;
+        let next = tasks.next_reminder_at().await?;
+        let stale = tasks
+            .mark_reminder_sent(&due[0], hours(4))
+            .await?;
+        miette::ensure!(
+            next.is_none() && !stale,
+            "Error: reminders of a done task kept {next:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_reminders() -> miette::Result<()> {
+        assert_reminders(
+            &InMemoryUserRepository::default(),
+            &InMemoryTaskRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_reminders() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_reminders(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlTaskRepository::new(pool),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_completions() -> miette::Result<()> {
+        assert_completions(
+            &InMemoryUserRepository::default(),
+            &InMemoryTaskRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_completions() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_completions(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlTaskRepository::new(pool),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_tags() -> miette::Result<()> {
+        assert_tags(
+            &InMemoryUserRepository::default(),
+            &InMemoryTaskRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_tags() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_tags(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlTaskRepository::new(pool),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_scoped_by_owner() -> miette::Result<()> {
+        assert_scoped_by_owner(
+            &InMemoryUserRepository::default(),
+            &InMemoryTaskRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_scoped_by_owner() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_scoped_by_owner(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlTaskRepository::new(pool),
+        )
+        .await
+    }
+}
@@ -0,0 +1,481 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+// local modules
+use super::{parse_uuid, timestamp, try_get_optional};
+use api_shared::prelude::LibError;
+
+/// A TOTP authenticator bound to an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotpCredential {
+    pub user_id: Uuid,
+    /// base32 shared secret
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` while the enrollment awaits its first code
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+}
+
+impl TotpCredential {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+}
+
+/// Storage for second factors: TOTP secrets and recovery codes.
+#[async_trait]
+pub trait TwoFactorRepository: Send + Sync {
+    async fn find_totp(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<TotpCredential>, LibError>;
+    /// Stores `credential`, replacing any other of the same user.
+    async fn save_totp(
+        &self,
+        credential: &TotpCredential,
+    ) -> Result<(), LibError>;
+    async fn enable_totp(
+        &self,
+        user_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError>;
+    /// Atomically records `step` as the last accepted one. Returns `false`
+    /// when a code of this step or a later one was already accepted.
+    async fn use_totp_step(
+        &self,
+        user_id: Uuid,
+        step: i64,
+    ) -> Result<bool, LibError>;
+    /// Removes the TOTP secret and every recovery code of the user.
+    async fn delete_all(&self, user_id: Uuid) -> Result<(), LibError>;
+
+    /// Replaces every recovery code of the user by `code_hashes`.
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+    ) -> Result<(), LibError>;
+    /// Atomically marks an unused recovery code as used. Returns `false`
+    /// when the user has no such unused code.
+    async fn use_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+#[derive(Debug, Clone)]
+struct RecoveryCode {
+    user_id: Uuid,
+    used_at: Option<DateTime<Utc>>,
+}
+
+/// Process-local store, used by tests and throwaway instances.
+#[derive(Debug, Default)]
+pub struct InMemoryTwoFactorRepository {
+    totp: Mutex<HashMap<Uuid, TotpCredential>>,
+    recovery_codes: Mutex<HashMap<String, RecoveryCode>>,
+}
+
+#[async_trait]
+impl TwoFactorRepository for InMemoryTwoFactorRepository {
+    async fn find_totp(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<TotpCredential>, LibError> {
+        Ok(self
+            .totp
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .cloned())
+    }
+
+    async fn save_totp(
+        &self,
+        credential: &TotpCredential,
+    ) -> Result<(), LibError> {
+        self.totp
+            .lock()
+            .unwrap()
+            .insert(credential.user_id, credential.clone());
+
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &self,
+        user_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        if let Some(credential) = self
+            .totp
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+        {
+            credential.enabled_at.get_or_insert(at);
+        }
+
+        Ok(())
+    }
+
+    async fn use_totp_step(
+        &self,
+        user_id: Uuid,
+        step: i64,
+    ) -> Result<bool, LibError> {
+        let mut totp = self.totp.lock().unwrap();
+        match totp.get_mut(&user_id) {
+            Some(credential)
+                if credential
+                    .last_used_step
+                    .is_none_or(|last| last < step) =>
+            {
+                credential.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_all(&self, user_id: Uuid) -> Result<(), LibError> {
+        self.totp
+            .lock()
+            .unwrap()
+            .remove(&user_id);
+        self.recovery_codes
+            .lock()
+            .unwrap()
+            .retain(|_, code| code.user_id != user_id);
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+    ) -> Result<(), LibError> {
+        let mut codes = self.recovery_codes.lock().unwrap();
+        codes.retain(|_, code| code.user_id != user_id);
+        for code_hash in code_hashes {
+            codes.insert(
+                code_hash.clone(),
+                RecoveryCode {
+                    user_id,
+                    used_at: None,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let mut codes = self.recovery_codes.lock().unwrap();
+        match codes.get_mut(code_hash) {
+            Some(code) if code.user_id == user_id && code.used_at.is_none() => {
+                code.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Store backed by SQLite or PostgreSQL through `sqlx`'s `Any` driver.
+#[derive(Debug, Clone)]
+pub struct SqlTwoFactorRepository {
+    pool: AnyPool,
+}
+
+impl SqlTwoFactorRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for SqlTwoFactorRepository {
+    async fn find_totp(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<TotpCredential>, LibError> {
+        let row = sqlx::query(
+            "SELECT user_id, secret, created_at, enabled_at, last_used_step \
+             FROM totp_credentials WHERE user_id = $1",
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| totp_from_row(&row))
+            .transpose()
+    }
+
+    async fn save_totp(
+        &self,
+        credential: &TotpCredential,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "INSERT INTO totp_credentials \
+             (user_id, secret, created_at, enabled_at, last_used_step) \
+             VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (user_id) DO UPDATE SET \
+             secret = $2, created_at = $3, enabled_at = $4, \
+             last_used_step = $5",
+        )
+        .bind(credential.user_id.to_string())
+        .bind(&credential.secret)
+        .bind(credential.created_at.timestamp())
+        .bind(
+            credential
+                .enabled_at
+                .map(|at| at.timestamp()),
+        )
+        .bind(credential.last_used_step)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &self,
+        user_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "UPDATE totp_credentials SET enabled_at = $1 \
+             WHERE user_id = $2 AND enabled_at IS NULL",
+        )
+        .bind(at.timestamp())
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn use_totp_step(
+        &self,
+        user_id: Uuid,
+        step: i64,
+    ) -> Result<bool, LibError> {
+        let result = sqlx::query(
+            "UPDATE totp_credentials SET last_used_step = $1 \
+             WHERE user_id = $2 \
+             AND (last_used_step IS NULL OR last_used_step < $1)",
+        )
+        .bind(step)
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete_all(&self, user_id: Uuid) -> Result<(), LibError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM totp_credentials WHERE user_id = $1")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(tx.commit().await?)
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+    ) -> Result<(), LibError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        for code_hash in code_hashes {
+            sqlx::query(
+                "INSERT INTO recovery_codes (code_hash, user_id) \
+                 VALUES ($1, $2)",
+            )
+            .bind(code_hash)
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(tx.commit().await?)
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, LibError> {
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = $1 \
+             WHERE code_hash = $2 AND user_id = $3 AND used_at IS NULL",
+        )
+        .bind(at.timestamp())
+        .bind(code_hash)
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+fn totp_from_row(row: &AnyRow) -> Result<TotpCredential, LibError> {
+    let user_id: String = row.try_get("user_id")?;
+    let created_at: i64 = row.try_get("created_at")?;
+    let enabled_at: Option<i64> = try_get_optional(row, "enabled_at")?;
+
+    Ok(TotpCredential {
+        user_id: parse_uuid(&user_id)?,
+        secret: row.try_get("secret")?,
+        created_at: timestamp(created_at),
+        enabled_at: enabled_at.map(timestamp),
+        last_used_step: try_get_optional(row, "last_used_step")?,
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{
+        connect, now, InMemoryUserRepository, SqlUserRepository, User,
+        UserRepository,
+    };
+
+    async fn assert_single_use(
+        users: &dyn UserRepository,
+        two_factor: &dyn TwoFactorRepository,
+    ) -> miette::Result<()> {
+        let user = User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: "$argon2id$v=19$m=256,t=1,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        };
+        users.insert(&user).await?;
+        let credential = TotpCredential {
+            user_id: user.id,
+            secret: "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP".to_string(),
+            created_at: now(),
+            enabled_at: None,
+            last_used_step: None,
+        };
+        two_factor
+            .save_totp(&credential)
+            .await?;
+        two_factor
+            .enable_totp(user.id, now())
+            .await?;
+        let stored = two_factor.find_totp(user.id).await?;
+        miette::ensure!(
+            stored.is_some_and(|c| c.is_enabled()),
+            "Error: TOTP not enabled"
+        );
+
+        let steps = [
+            two_factor
+                .use_totp_step(user.id, 100)
+                .await?,
+            two_factor
+                .use_totp_step(user.id, 100)
+                .await?,
+            two_factor
+                .use_totp_step(user.id, 99)
+                .await?,
+            two_factor
+                .use_totp_step(user.id, 101)
+                .await?,
+        ];
+        miette::ensure!(
+            steps == [true, false, false, true],
+            "Error: TOTP step replayed {steps:?}"
+        );
+
+        let codes = ["a".to_string(), "b".to_string()];
+        two_factor
+            .replace_recovery_codes(user.id, &codes)
+            .await?;
+        let uses = [
+            two_factor
+                .use_recovery_code(user.id, "a", now())
+                .await?,
+            two_factor
+                .use_recovery_code(user.id, "a", now())
+                .await?,
+            two_factor
+                .use_recovery_code(Uuid::new_v4(), "b", now())
+                .await?,
+        ];
+        miette::ensure!(
+            uses == [true, false, false],
+            "Error: recovery code reused {uses:?}"
+        );
+
+        two_factor.delete_all(user.id).await?;
+        let unused_after_delete = two_factor
+            .use_recovery_code(user.id, "b", now())
+            .await?;
+        miette::ensure!(
+            two_factor
+                .find_totp(user.id)
+                .await?
+                .is_none()
+                && !unused_after_delete,
+            "Error: second factor survived deletion"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_single_use() -> miette::Result<()> {
+        assert_single_use(
+            &InMemoryUserRepository::default(),
+            &InMemoryTwoFactorRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_single_use() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_single_use(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlTwoFactorRepository::new(pool),
+        )
+        .await
+    }
+}
@@ -0,0 +1,264 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+// local modules
+use super::{parse_uuid, timestamp, try_get_optional};
+use api_shared::prelude::LibError;
+
+/// What a mailed token grants once it's presented back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::VerifyEmail => "verify_email",
+            Self::ResetPassword => "reset_password",
+        }
+    }
+}
+
+/// A single-use token mailed to a user, identified by its digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserToken {
+    pub token_hash: String,
+    pub user_id: Uuid,
+    pub purpose: TokenPurpose,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Storage for email verification and password reset tokens.
+#[async_trait]
+pub trait UserTokenRepository: Send + Sync {
+    async fn insert(&self, token: &UserToken) -> Result<(), LibError>;
+    /// Atomically marks an unused, unexpired token of `purpose` as used and
+    /// returns it. `None` when the token is unknown, expired, already used
+    /// or issued for something else.
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<UserToken>, LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Process-local store, used by tests and throwaway instances.
+#[derive(Debug, Default)]
+pub struct InMemoryUserTokenRepository {
+    tokens: Mutex<HashMap<String, UserToken>>,
+}
+
+#[async_trait]
+impl UserTokenRepository for InMemoryUserTokenRepository {
+    async fn insert(&self, token: &UserToken) -> Result<(), LibError> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(token.token_hash.clone(), token.clone());
+
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<UserToken>, LibError> {
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.get_mut(token_hash) {
+            Some(token)
+                if token.purpose == purpose
+                    && token.used_at.is_none()
+                    && token.expires_at > now =>
+            {
+                token.used_at = Some(now);
+                Ok(Some(token.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Store backed by SQLite or PostgreSQL through `sqlx`'s `Any` driver.
+#[derive(Debug, Clone)]
+pub struct SqlUserTokenRepository {
+    pool: AnyPool,
+}
+
+impl SqlUserTokenRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserTokenRepository for SqlUserTokenRepository {
+    async fn insert(&self, token: &UserToken) -> Result<(), LibError> {
+        sqlx::query(
+            "INSERT INTO user_tokens \
+             (token_hash, user_id, purpose, created_at, expires_at, used_at) \
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&token.token_hash)
+        .bind(token.user_id.to_string())
+        .bind(token.purpose.as_str())
+        .bind(token.created_at.timestamp())
+        .bind(token.expires_at.timestamp())
+        .bind(token.used_at.map(|at| at.timestamp()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> Result<Option<UserToken>, LibError> {
+        // the conditional update is what makes the token single-use under
+        // concurrent requests
+        let result = sqlx::query(
+            "UPDATE user_tokens SET used_at = $1 \
+             WHERE token_hash = $2 AND purpose = $3 \
+             AND used_at IS NULL AND expires_at > $1",
+        )
+        .bind(now.timestamp())
+        .bind(token_hash)
+        .bind(purpose.as_str())
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() != 1 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query(
+            "SELECT token_hash, user_id, created_at, expires_at, used_at \
+             FROM user_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        user_token_from_row(&row, purpose).map(Some)
+    }
+}
+
+fn user_token_from_row(
+    row: &AnyRow,
+    purpose: TokenPurpose,
+) -> Result<UserToken, LibError> {
+    let user_id: String = row.try_get("user_id")?;
+    let created_at: i64 = row.try_get("created_at")?;
+    let expires_at: i64 = row.try_get("expires_at")?;
+    let used_at: Option<i64> = try_get_optional(row, "used_at")?;
+
+    Ok(UserToken {
+        token_hash: row.try_get("token_hash")?,
+        user_id: parse_uuid(&user_id)?,
+        purpose,
+        created_at: timestamp(created_at),
+        expires_at: timestamp(expires_at),
+        used_at: used_at.map(timestamp),
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{
+        connect, now, InMemoryUserRepository, SqlUserRepository, User,
+        UserRepository,
+    };
+    use chrono::Duration;
+
+    async fn assert_single_use(
+        users: &dyn UserRepository,
+        tokens: &dyn UserTokenRepository,
+    ) -> miette::Result<()> {
+        let user = User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: "$argon2id$v=19$m=256,t=1,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        };
+        users.insert(&user).await?;
+        let mock_token = |hash: &str, expires_in: Duration| UserToken {
+            token_hash: hash.to_string(),
+            user_id: user.id,
+            purpose: TokenPurpose::ResetPassword,
+            created_at: now(),
+            expires_at: now() + expires_in,
+            used_at: None,
+        };
+        tokens
+            .insert(&mock_token("fresh", Duration::hours(1)))
+            .await?;
+        tokens
+            .insert(&mock_token("stale", Duration::hours(-1)))
+            .await?;
+
+        let wrong_purpose = tokens
+            .consume("fresh", TokenPurpose::VerifyEmail, now())
+            .await?;
+        miette::ensure!(
+            wrong_purpose.is_none(),
+            "Error: token used for another purpose"
+        );
+        let first = tokens
+            .consume("fresh", TokenPurpose::ResetPassword, now())
+            .await?;
+        miette::ensure!(
+            first.is_some_and(|t| t.user_id == user.id && t.used_at.is_some()),
+            "Error: fresh token refused"
+        );
+        let second = tokens
+            .consume("fresh", TokenPurpose::ResetPassword, now())
+            .await?;
+        miette::ensure!(second.is_none(), "Error: token used twice");
+        let stale = tokens
+            .consume("stale", TokenPurpose::ResetPassword, now())
+            .await?;
+        miette::ensure!(stale.is_none(), "Error: expired token accepted");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_single_use() -> miette::Result<()> {
+        assert_single_use(
+            &InMemoryUserRepository::default(),
+            &InMemoryUserTokenRepository::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sql_single_use() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_single_use(
+            &SqlUserRepository::new(pool.clone()),
+            &SqlUserTokenRepository::new(pool),
+        )
+        .await
+    }
+}
@@ -0,0 +1,384 @@
+// external crates
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{any::AnyRow, AnyPool, Row};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+// local modules
+use super::{parse_uuid, timestamp, try_get_optional, unique_violation};
+use api_shared::prelude::LibError;
+
+/// A registered account as kept by the user store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub username: String,
+    /// Argon2id PHC string, see `services::PasswordHashing`.
+    pub password_hash: String,
+    pub created_at: DateTime<Utc>,
+    /// When the owner opened the verification link, `None` until then.
+    pub email_verified_at: Option<DateTime<Utc>>,
+    /// Language picked for messages, see `api_shared::i18n`.
+    pub locale: Option<String>,
+}
+
+/// Storage for user accounts.
+///
+/// Implementations must reject duplicated emails with
+/// `LibError::EmailTaken` and duplicated usernames with
+/// `LibError::UserTaken`, so uniqueness holds even under concurrent sign ups.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn insert(&self, user: &User) -> Result<(), LibError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, LibError>;
+    async fn find_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<User>, LibError>;
+    async fn find_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, LibError>;
+    async fn update_password_hash(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+    ) -> Result<(), LibError>;
+    /// Records the email as verified. Verifying twice keeps the first time.
+    async fn mark_email_verified(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError>;
+    async fn update_locale(
+        &self,
+        id: Uuid,
+        locale: Option<&str>,
+    ) -> Result<(), LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Process-local store, used by tests and throwaway instances.
+#[derive(Debug, Default)]
+pub struct InMemoryUserRepository {
+    users: Mutex<HashMap<Uuid, User>>,
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), LibError> {
+        let mut users = self.users.lock().unwrap();
+        if users
+            .values()
+            .any(|u| u.email == user.email)
+        {
+            return Err(LibError::EmailTaken);
+        }
+        if users
+            .values()
+            .any(|u| u.username == user.username)
+        {
+            return Err(LibError::UserTaken);
+        }
+        users.insert(user.id, user.clone());
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, LibError> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned())
+    }
+
+    async fn find_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<User>, LibError> {
+        let users = self.users.lock().unwrap();
+        Ok(users
+            .values()
+            .find(|u| u.email == email)
+            .cloned())
+    }
+
+    async fn find_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, LibError> {
+        let users = self.users.lock().unwrap();
+        Ok(users
+            .values()
+            .find(|u| u.username == username)
+            .cloned())
+    }
+
+    async fn update_password_hash(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+    ) -> Result<(), LibError> {
+        if let Some(user) = self.users.lock().unwrap().get_mut(&id) {
+            user.password_hash = password_hash.to_string();
+        }
+
+        Ok(())
+    }
+
+    async fn mark_email_verified(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        if let Some(user) = self.users.lock().unwrap().get_mut(&id) {
+            user.email_verified_at.get_or_insert(at);
+        }
+
+        Ok(())
+    }
+
+    async fn update_locale(
+        &self,
+        id: Uuid,
+        locale: Option<&str>,
+    ) -> Result<(), LibError> {
+        if let Some(user) = self.users.lock().unwrap().get_mut(&id) {
+            user.locale = locale.map(String::from);
+        }
+
+        Ok(())
+    }
+}
+
+// SECTION: SQL.................................................................
+
+/// Store backed by SQLite or PostgreSQL through `sqlx`'s `Any` driver.
+#[derive(Debug, Clone)]
+pub struct SqlUserRepository {
+    pool: AnyPool,
+}
+
+impl SqlUserRepository {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+
+    async fn find_by(
+        &self,
+        column: &str,
+        value: String,
+    ) -> Result<Option<User>, LibError> {
+        let query = format!(
+            "SELECT id, email, username, password_hash, created_at, \
+             email_verified_at, locale FROM users WHERE {column} = $1"
+        );
+        let row = sqlx::query(&query)
+            .bind(value)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| user_from_row(&row))
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqlUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), LibError> {
+        let result = sqlx::query(
+            "INSERT INTO users (id, email, username, password_hash, \
+             created_at, email_verified_at, locale) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(user.id.to_string())
+        .bind(&user.email)
+        .bind(&user.username)
+        .bind(&user.password_hash)
+        .bind(user.created_at.timestamp())
+        .bind(
+            user.email_verified_at
+                .map(|at| at.timestamp()),
+        )
+        .bind(user.locale.clone())
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match unique_violation(&err) {
+                Some(c) if c.contains("email") => Err(LibError::EmailTaken),
+                Some(c) if c.contains("username") => Err(LibError::UserTaken),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, LibError> {
+        self.find_by("id", id.to_string()).await
+    }
+
+    async fn find_by_email(
+        &self,
+        email: &str,
+    ) -> Result<Option<User>, LibError> {
+        self.find_by("email", email.to_string())
+            .await
+    }
+
+    async fn find_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, LibError> {
+        self.find_by("username", username.to_string())
+            .await
+    }
+
+    async fn update_password_hash(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+    ) -> Result<(), LibError> {
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(password_hash)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn mark_email_verified(
+        &self,
+        id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        sqlx::query(
+            "UPDATE users SET email_verified_at = $1 \
+             WHERE id = $2 AND email_verified_at IS NULL",
+        )
+        .bind(at.timestamp())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_locale(
+        &self,
+        id: Uuid,
+        locale: Option<&str>,
+    ) -> Result<(), LibError> {
+        sqlx::query("UPDATE users SET locale = $1 WHERE id = $2")
+            .bind(locale)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn user_from_row(row: &AnyRow) -> Result<User, LibError> {
+    let id: String = row.try_get("id")?;
+    let created_at: i64 = row.try_get("created_at")?;
+    let email_verified_at: Option<i64> =
+        try_get_optional(row, "email_verified_at")?;
+
+    Ok(User {
+        id: parse_uuid(&id)?,
+        email: row.try_get("email")?,
+        username: row.try_get("username")?,
+        password_hash: row.try_get("password_hash")?,
+        created_at: timestamp(created_at),
+        email_verified_at: email_verified_at.map(timestamp),
+        locale: try_get_optional(row, "locale")?,
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{connect, now};
+
+    fn mock_user(email: &str, username: &str) -> User {
+        User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            username: username.to_string(),
+            password_hash: "$argon2id$v=19$m=256,t=1,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        }
+    }
+
+    async fn assert_uniqueness(
+        repo: &dyn UserRepository,
+    ) -> miette::Result<()> {
+        let user = mock_user("user@email.com", "username");
+        repo.insert(&user).await?;
+
+        let same_email = mock_user("user@email.com", "other");
+        let same_username = mock_user("other@email.com", "username");
+        miette::ensure!(
+            matches!(repo.insert(&same_email).await, Err(LibError::EmailTaken)),
+            "Error: duplicated email was stored"
+        );
+        miette::ensure!(
+            matches!(
+                repo.insert(&same_username).await,
+                Err(LibError::UserTaken)
+            ),
+            "Error: duplicated username was stored"
+        );
+
+        let found = repo
+            .find_by_email("user@email.com")
+            .await?;
+        miette::ensure!(found == Some(user.clone()), "Error: user not found");
+        let found = repo
+            .find_by_username("username")
+            .await?;
+        miette::ensure!(found == Some(user.clone()), "Error: user not found");
+        let found = repo.find_by_id(user.id).await?;
+        miette::ensure!(found == Some(user.clone()), "Error: user not found");
+
+        let verified_at = now();
+        repo.mark_email_verified(user.id, verified_at)
+            .await?;
+        let found = repo.find_by_id(user.id).await?;
+        miette::ensure!(
+            found.is_some_and(|u| u.email_verified_at == Some(verified_at)),
+            "Error: email not marked as verified"
+        );
+
+        repo.update_locale(user.id, Some("en"))
+            .await?;
+        let found = repo.find_by_id(user.id).await?;
+        miette::ensure!(
+            found.is_some_and(|u| u.locale.as_deref() == Some("en")),
+            "Error: locale not saved"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_uniqueness() -> miette::Result<()> {
+        assert_uniqueness(&InMemoryUserRepository::default()).await
+    }
+
+    #[tokio::test]
+    async fn test_sql_uniqueness() -> miette::Result<()> {
+        let pool = connect("sqlite::memory:").await?;
+        assert_uniqueness(&SqlUserRepository::new(pool)).await
+    }
+}
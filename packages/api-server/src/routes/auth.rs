@@ -0,0 +1,206 @@
+// external crates
+use api_shared::prelude::LibError;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+use uuid::Uuid;
+// local modules
+use crate::{
+    routes::sessions::{read_cookie, ACCESS_COOKIE},
+    services::TokenKeys,
+};
+
+/// The caller of a protected route.
+///
+/// Read from an `Authorization: Bearer` access token or, for browsers, from
+/// the access cookie. Only the token signature and expiry are checked, so
+/// no database round trip is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthUser {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+    TokenKeys: FromRef<S>,
+{
+    type Rejection = LibError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        // already validated by the route layer of a protected group
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(*user);
+        }
+
+        let token = bearer_token(&parts.headers)
+            .or_else(|| read_cookie(&parts.headers, ACCESS_COOKIE))
+            .ok_or(LibError::MissingCredentials)?;
+        let claims = TokenKeys::from_ref(state).decode_access(&token)?;
+        let user = AuthUser {
+            user_id: claims.sub,
+            session_id: claims.sid,
+        };
+        parts.extensions.insert(user);
+        tracing::Span::current()
+            .record("user_id", tracing::field::display(user.user_id));
+
+        Ok(user)
+    }
+}
+
+pub(super) fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::now;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use chrono::Duration;
+    use tower::ServiceExt;
+
+    async fn whoami(user: AuthUser) -> String {
+        user.user_id.to_string()
+    }
+
+    fn mock_app(keys: TokenKeys) -> Router {
+        Router::new()
+            .route("/whoami", get(whoami))
+            .route_layer(middleware::from_extractor_with_state::<AuthUser, _>(
+                keys.clone(),
+            ))
+            .with_state(keys)
+    }
+
+    async fn call(
+        app: Router,
+        header: Option<(&str, String)>,
+    ) -> (StatusCode, String) {
+        let mut request = Request::get("/whoami");
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token() -> miette::Result<()> {
+        let keys = TokenKeys::random();
+        let user_id = Uuid::new_v4();
+        let token = keys.mint_access(user_id, Uuid::new_v4(), now())?;
+        let (status, body) = call(
+            mock_app(keys),
+            Some(("authorization", format!("Bearer {token}"))),
+        )
+        .await;
+
+        miette::ensure!(
+            status == StatusCode::OK && body == user_id.to_string(),
+            "Error: valid token refused"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_access_cookie() -> miette::Result<()> {
+        let keys = TokenKeys::random();
+        let token = keys.mint_access(Uuid::new_v4(), Uuid::new_v4(), now())?;
+        let (status, _) = call(
+            mock_app(keys),
+            Some(("cookie", format!("theme=dark; {ACCESS_COOKIE}={token}"))),
+        )
+        .await;
+
+        miette::ensure!(status == StatusCode::OK, "Error: cookie refused");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_credentials() -> miette::Result<()> {
+        let (status, _) = call(mock_app(TokenKeys::random()), None).await;
+
+        miette::ensure!(
+            status == StatusCode::UNAUTHORIZED,
+            "Error: anonymous request accepted"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_token() -> miette::Result<()> {
+        let keys = TokenKeys::random();
+        let issued_at = now() - keys.access_ttl - Duration::seconds(1);
+        let token =
+            keys.mint_access(Uuid::new_v4(), Uuid::new_v4(), issued_at)?;
+
+        miette::ensure!(
+            matches!(
+                keys.decode_access(&token),
+                Err(LibError::ExpiredCredentials)
+            ),
+            "Error: expired token accepted"
+        );
+        let (status, _) = call(
+            mock_app(keys),
+            Some(("authorization", format!("Bearer {token}"))),
+        )
+        .await;
+        miette::ensure!(
+            status == StatusCode::UNAUTHORIZED,
+            "Error: expired token accepted"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_from_other_keys() -> miette::Result<()> {
+        let token = TokenKeys::random().mint_access(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            now(),
+        )?;
+
+        miette::ensure!(
+            matches!(
+                TokenKeys::random().decode_access(&token),
+                Err(LibError::InvalidToken)
+            ),
+            "Error: forged token accepted"
+        );
+        Ok(())
+    }
+}
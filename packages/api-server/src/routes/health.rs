@@ -0,0 +1,58 @@
+// external crates
+use api_shared::dto::{HealthReport, HealthStatus};
+use axum::{extract::State, http::StatusCode, Json};
+// local modules
+use crate::{routes::AppState, services::readiness_service};
+
+/// Answers as long as the process runs, for supervisors restarting hung
+/// servers. Nothing else is checked.
+pub async fn get_healthz_route() -> Json<HealthReport> {
+    Json(HealthReport {
+        status: HealthStatus::Ok,
+        checks: Default::default(),
+    })
+}
+
+/// Whether the server can take requests, for load balancers: 503 while a
+/// dependency is unreachable or the server is stopping.
+pub async fn get_readyz_route(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<HealthReport>) {
+    let report = readiness_service(&state.health_checks).await;
+    let status = match report.status {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (status, Json(report))
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::tests::{mock_state, spawn_server};
+    use api_shared::dto::HealthStatus;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_readyz_while_stopping() -> miette::Result<()> {
+        let state = mock_state();
+        let jobs = state.jobs.clone();
+        let client = spawn_server(state);
+
+        let alive = client.health().await?;
+        let ready = client.readiness().await?;
+        jobs.stop(Duration::from_secs(1)).await;
+        let stopping = client.readiness().await?;
+
+        miette::ensure!(
+            alive.status == HealthStatus::Ok
+                && ready.status == HealthStatus::Ok
+                && stopping.status == HealthStatus::Unavailable
+                && stopping.checks["jobs"] == HealthStatus::Unavailable,
+            "Error: unexpected reports {ready:?} {stopping:?}"
+        );
+        Ok(())
+    }
+}
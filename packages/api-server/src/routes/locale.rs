@@ -0,0 +1,170 @@
+// external crates
+use api_shared::{
+    i18n,
+    response::{localize_response, LocalizableProblem},
+};
+use axum::{
+    extract::State,
+    http::{header, Request},
+    middleware::Next,
+    response::Response,
+};
+// local modules
+use crate::routes::{
+    auth::bearer_token,
+    sessions::{read_cookie, ACCESS_COOKIE},
+    AppState,
+};
+
+/// Renders error bodies in the caller's language: the one saved in their
+/// profile when signed in, else the best match of `Accept-Language`.
+///
+/// The profile is only loaded once a request has failed, successful
+/// responses cost nothing more than reading two headers.
+pub async fn localize_errors<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let headers = request.headers();
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let token =
+        bearer_token(headers).or_else(|| read_cookie(headers, ACCESS_COOKIE));
+
+    let response = next.run(request).await;
+    if response
+        .extensions()
+        .get::<LocalizableProblem>()
+        .is_none()
+    {
+        return response;
+    }
+
+    let saved = match token {
+        Some(token) => saved_locale(&state, &token).await,
+        None => None,
+    };
+    let catalog = saved
+        .as_deref()
+        .and_then(i18n::find)
+        .unwrap_or_else(|| i18n::negotiate(&accept_language));
+
+    localize_response(response, catalog)
+}
+
+/// Locale of the token's owner. Invalid tokens are simply ignored, the
+/// request failed for its own reasons already.
+async fn saved_locale(state: &AppState, token: &str) -> Option<String> {
+    let claims = state.tokens.decode_access(token).ok()?;
+
+    state
+        .users
+        .find_by_id(claims.sub)
+        .await
+        .ok()??
+        .locale
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        repository::{now, User},
+        routes::{router, tests::mock_state, AppState},
+    };
+    use api_shared::prelude::ProblemDetails;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    async fn call(
+        state: AppState,
+        request: Request<Body>,
+    ) -> (StatusCode, Option<String>, ProblemDetails) {
+        let response = router(state)
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        let language = response
+            .headers()
+            .get(header::CONTENT_LANGUAGE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        (status, language, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_accept_language() -> miette::Result<()> {
+        let request = |language: &str| {
+            Request::post("/users")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::ACCEPT_LANGUAGE, language)
+                .body(Body::from(
+                    r#"{"email":"x","password":"x","username":"x"}"#,
+                ))
+                .unwrap()
+        };
+
+        let (status, language, problem) =
+            call(mock_state(), request("en-US,en;q=0.9")).await;
+        miette::ensure!(
+            status == StatusCode::UNPROCESSABLE_ENTITY
+                && language.as_deref() == Some("en")
+                && problem.title == "Invalid fields"
+                && problem.fields[0].message == "Invalid email",
+            "Error: English not chosen {problem:?}"
+        );
+
+        let (_, language, problem) = call(mock_state(), request("fr")).await;
+        miette::ensure!(
+            language.as_deref() == Some("pt-BR")
+                && problem.title == "Campos inválidos",
+            "Error: default locale not used {problem:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_saved_locale_wins() -> miette::Result<()> {
+        let state = mock_state();
+        let user = User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: String::new(),
+            created_at: now(),
+            email_verified_at: Some(now()),
+            locale: Some("en".to_string()),
+        };
+        state.users.insert(&user).await?;
+        let token = state
+            .tokens
+            .mint_access(user.id, Uuid::new_v4(), now())?;
+        let request = Request::delete(format!("/sessions/{}", Uuid::new_v4()))
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .header(header::ACCEPT_LANGUAGE, "pt-BR")
+            .body(Body::empty())
+            .unwrap();
+
+        let (status, language, problem) = call(state, request).await;
+        miette::ensure!(
+            status == StatusCode::NOT_FOUND
+                && language.as_deref() == Some("en")
+                && problem.title == "Resource not found",
+            "Error: saved locale ignored {problem:?}"
+        );
+        Ok(())
+    }
+}
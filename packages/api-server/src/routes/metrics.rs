@@ -0,0 +1,117 @@
+// external crates
+use api_shared::response::LocalizableProblem;
+use axum::{
+    extract::{MatchedPath, State},
+    http::{header, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::Instant;
+// local modules
+use crate::routes::AppState;
+
+/// Media type of what `Metrics::render` writes, the OpenMetrics flavor of
+/// the Prometheus text format.
+const OPENMETRICS_TEXT: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Every metric of the server, for Prometheus to scrape.
+pub async fn get_metrics_route(State(state): State<AppState>) -> Response {
+    (
+        [(header::CONTENT_TYPE, OPENMETRICS_TEXT)],
+        state.metrics.render(&state.jobs),
+    )
+        .into_response()
+}
+
+/// Counts each request by route and status, along with the diagnostic
+/// code of `LibError` answers. Requests matching no route share the
+/// `unmatched` one.
+pub async fn record_metrics<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched".to_string(), |path| path.as_str().to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    let error = response
+        .extensions()
+        .get::<LocalizableProblem>()
+        .map(LocalizableProblem::code);
+    state.metrics.record_request(
+        &method,
+        &route,
+        response.status(),
+        started.elapsed(),
+        error,
+    );
+
+    response
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        routes::{router, tests::mock_state, AppState},
+    };
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn metrics_state(enabled: bool) -> AppState {
+        let mut state = mock_state();
+        let mut config = Config::default();
+        config.metrics.enabled = enabled;
+        state.config = Arc::new(config);
+        state
+    }
+
+    #[tokio::test]
+    async fn test_metrics_route() -> miette::Result<()> {
+        let get = |uri: &str| {
+            Request::get(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let app = router(metrics_state(true));
+
+        app.clone()
+            .oneshot(get("/users/me"))
+            .await
+            .unwrap();
+        let response = app
+            .oneshot(get("/metrics"))
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8_lossy(&body);
+        let disabled = router(metrics_state(false))
+            .oneshot(get("/metrics"))
+            .await
+            .unwrap();
+
+        miette::ensure!(
+            body.contains(
+                r#"http_requests_total{method="GET",route="/users/me",status="401"} 1"#
+            ) && body.contains(
+                r#"api_errors_total{code="LibError::MissingCredentials"} 1"#
+            ) && disabled.status() == StatusCode::NOT_FOUND,
+            "Error: unexpected metrics {body}"
+        );
+        Ok(())
+    }
+}
@@ -1,38 +1,625 @@
+use std::{
+    error::Error, future::Future, net::SocketAddr, sync::Arc, time::Duration,
+};
+
 use api_shared::prelude::LibError;
 use axum::{
-    http::Method,
-    routing::{get, post},
+    extract::FromRef,
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use tower_http::cors::{Any, CorsLayer};
+use hyper::server::conn::AddrIncoming;
+use tokio::{sync::oneshot, time::Instant};
+use tower_http::{
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
 
-use self::users::post_users_route;
+use self::{
+    auth::AuthUser,
+    health::{get_healthz_route, get_readyz_route},
+    locale::localize_errors,
+    metrics::{get_metrics_route, record_metrics},
+    oidc::{
+        get_oidc_callback_route, get_oidc_login_route, get_oidc_providers_route,
+    },
+    security::{security_headers, verify_origin},
+    sessions::{
+        delete_session_route, delete_sessions_route, get_sessions_route,
+        post_refresh_route, post_sessions_route,
+        post_sessions_two_factor_route,
+    },
+    tasks::{
+        delete_task_route, get_occurrences_route, get_tags_route,
+        get_task_route, get_tasks_route, patch_occurrence_route,
+        patch_tag_route, patch_task_route, post_tasks_route,
+    },
+    two_factor::{
+        delete_two_factor_route, get_two_factor_route,
+        post_two_factor_confirm_route, post_two_factor_route,
+    },
+    users::{
+        get_me_route, get_verify_route, post_password_forgot_route,
+        post_password_reset_route, post_users_route, post_verification_route,
+        put_locale_route,
+    },
+};
+use crate::{
+    config::Config,
+    repository::{
+        self, InMemoryLoginAttemptRepository, InMemoryOidcRepository,
+        InMemorySessionRepository, InMemoryTaskRepository,
+        InMemoryTwoFactorRepository, InMemoryUserRepository,
+        InMemoryUserTokenRepository, OidcRepository, SessionRepository,
+        SqlLoginAttemptRepository, SqlOidcRepository, SqlSessionRepository,
+        SqlTaskRepository, SqlTwoFactorRepository, SqlUserRepository,
+        SqlUserTokenRepository, TaskRepository, TwoFactorRepository,
+        UserRepository, UserTokenRepository,
+    },
+    services::{
+        BackgroundJobs, DatabaseCheck, FileMailer, HashingParams, HealthCheck,
+        InMemoryMailer, InMemoryNotifier, LoginThrottle, MailLinks,
+        MailNotifier, Mailer, Metrics, OidcProviders, PasswordHashing,
+        ReminderScheduler, SmtpMailer, ThrottlePolicy, TokenKeys,
+    },
+    telemetry::RequestSpan,
+};
 
+pub mod auth;
+pub mod health;
+pub mod locale;
+pub mod metrics;
+pub mod oidc;
+pub mod security;
+pub mod sessions;
+pub mod tasks;
+pub mod two_factor;
 pub mod users;
 
-pub async fn run_server() -> Result<(), LibError> {
+/// Shared handles every route can reach through `State<AppState>`.
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub users: Arc<dyn UserRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub user_tokens: Arc<dyn UserTokenRepository>,
+    pub two_factor: Arc<dyn TwoFactorRepository>,
+    pub oidc: Arc<dyn OidcRepository>,
+    pub tasks: Arc<dyn TaskRepository>,
+    /// to wake once a task is saved, run by `run_server`
+    pub reminders: ReminderScheduler,
+    pub oidc_providers: OidcProviders,
+    pub mailer: Arc<dyn Mailer>,
+    pub links: MailLinks,
+    pub throttle: LoginThrottle,
+    pub passwords: PasswordHashing,
+    pub tokens: TokenKeys,
+    pub jobs: BackgroundJobs,
+    pub metrics: Metrics,
+    /// what `GET /readyz` checks
+    pub health_checks: Vec<Arc<dyn HealthCheck>>,
+}
+
+impl AppState {
+    /// State backed by the SQL database of `config`.
+    pub async fn connect(config: Config) -> Result<Self, LibError> {
+        let pool = repository::connect(&config.database.url).await?;
+        // without a configured secret, sessions don't survive a restart
+        let tokens = match &config.tokens.secret {
+            Some(secret) => TokenKeys::new(secret.as_bytes()),
+            None => TokenKeys::random(),
+        };
+        // without an SMTP relay, mail is written to files in an outbox folder
+        let mailer: Arc<dyn Mailer> = match &config.mail.smtp_url {
+            Some(url) => Arc::new(SmtpMailer::new(url, &config.mail.from)?),
+            None => Arc::new(FileMailer::new(&config.mail.outbox)),
+        };
+        let links = MailLinks {
+            api_url: config.server.api_url.clone(),
+            app_url: config.server.app_url.clone(),
+        };
+        let oidc_providers = match config.features.oidc {
+            true => OidcProviders::new(
+                config.oidc.providers.clone(),
+                &links.api_url,
+            ),
+            false => OidcProviders::default(),
+        };
+        let throttle = ThrottlePolicy::from(&config.throttle);
+        let passwords =
+            PasswordHashing::new(HashingParams::from(&config.passwords))?;
+        let jobs = BackgroundJobs::default();
+        let metrics = Metrics::default();
+        metrics.track_pool(pool.clone());
+        let users: Arc<dyn UserRepository> =
+            Arc::new(SqlUserRepository::new(pool.clone()));
+        let tasks: Arc<dyn TaskRepository> =
+            Arc::new(SqlTaskRepository::new(pool.clone()));
+        let reminders = ReminderScheduler::new(
+            tasks.clone(),
+            users.clone(),
+            Arc::new(MailNotifier::new(users.clone(), mailer.clone())),
+            metrics.queue_depth("reminders"),
+        );
+        let health_checks: Vec<Arc<dyn HealthCheck>> = vec![
+            Arc::new(DatabaseCheck::new(pool.clone())),
+            Arc::new(jobs.clone()),
+        ];
+
+        Ok(Self {
+            config: Arc::new(config),
+            users,
+            sessions: Arc::new(SqlSessionRepository::new(pool.clone())),
+            user_tokens: Arc::new(SqlUserTokenRepository::new(pool.clone())),
+            two_factor: Arc::new(SqlTwoFactorRepository::new(pool.clone())),
+            oidc: Arc::new(SqlOidcRepository::new(pool.clone())),
+            tasks,
+            reminders,
+            oidc_providers,
+            mailer,
+            links,
+            throttle: LoginThrottle::new(
+                Arc::new(SqlLoginAttemptRepository::new(pool)),
+                throttle,
+            ),
+            passwords,
+            tokens,
+            jobs,
+            metrics,
+            health_checks,
+        })
+    }
+
+    /// State that lives only in memory, for tests. Mail is kept in an
+    /// `InMemoryMailer`.
+    pub fn in_memory(hashing: HashingParams) -> Result<Self, LibError> {
+        let jobs = BackgroundJobs::default();
+        let metrics = Metrics::default();
+        let users: Arc<dyn UserRepository> =
+            Arc::new(InMemoryUserRepository::default());
+        let tasks: Arc<dyn TaskRepository> =
+            Arc::new(InMemoryTaskRepository::default());
+        let reminders = ReminderScheduler::new(
+            tasks.clone(),
+            users.clone(),
+            Arc::new(InMemoryNotifier::default()),
+            metrics.queue_depth("reminders"),
+        );
+
+        Ok(Self {
+            config: Arc::new(Config::default()),
+            users,
+            sessions: Arc::new(InMemorySessionRepository::default()),
+            user_tokens: Arc::new(InMemoryUserTokenRepository::default()),
+            two_factor: Arc::new(InMemoryTwoFactorRepository::default()),
+            oidc: Arc::new(InMemoryOidcRepository::default()),
+            tasks,
+            reminders,
+            oidc_providers: OidcProviders::default(),
+            mailer: Arc::new(InMemoryMailer::default()),
+            links: MailLinks::default(),
+            throttle: LoginThrottle::new(
+                Arc::new(InMemoryLoginAttemptRepository::default()),
+                ThrottlePolicy::default(),
+            ),
+            passwords: PasswordHashing::new(hashing)?,
+            tokens: TokenKeys::random(),
+            health_checks: vec![Arc::new(jobs.clone())],
+            jobs,
+            metrics,
+        })
+    }
+}
+
+impl FromRef<AppState> for TokenKeys {
+    fn from_ref(state: &AppState) -> Self {
+        state.tokens.clone()
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    // the app sends its session cookies along, which browsers only allow
+    // with an explicit origin; the values were checked by `Config::load`
+    let cors_config = &state.config.cors;
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST])
-        .allow_origin(Any);
+        .allow_origin(AllowOrigin::list(
+            cors_config
+                .allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        ))
+        .allow_methods(AllowMethods::list(
+            cors_config
+                .allowed_methods
+                .iter()
+                .filter_map(|method| {
+                    Method::from_bytes(method.as_bytes()).ok()
+                }),
+        ))
+        .allow_headers(AllowHeaders::list(
+            cors_config
+                .allowed_headers
+                .iter()
+                .filter_map(|name| {
+                    HeaderName::from_bytes(name.as_bytes()).ok()
+                }),
+        ))
+        .expose_headers([header::CONTENT_LANGUAGE, header::RETRY_AFTER])
+        .allow_credentials(true);
+
+    // every route in this group requires a valid access token
+    let protected = Router::new()
+        .route("/users/me", get(get_me_route))
+        .route("/users/me/locale", put(put_locale_route))
+        .route(
+            "/users/me/two_factor",
+            get(get_two_factor_route)
+                .post(post_two_factor_route)
+                .delete(delete_two_factor_route),
+        )
+        .route(
+            "/users/me/two_factor/confirm",
+            post(post_two_factor_confirm_route),
+        )
+        .route("/sessions", get(get_sessions_route))
+        .route("/sessions/:id", delete(delete_session_route))
+        .route("/tasks", get(get_tasks_route).post(post_tasks_route))
+        .route(
+            "/tasks/:id",
+            get(get_task_route)
+                .patch(patch_task_route)
+                .delete(delete_task_route),
+        )
+        .route(
+            "/tasks/:id/occurrences/:date",
+            patch(patch_occurrence_route),
+        )
+        .route("/occurrences", get(get_occurrences_route))
+        .route("/tags", get(get_tags_route))
+        .route("/tags/:name", patch(patch_tag_route))
+        .route_layer(middleware::from_extractor_with_state::<AuthUser, _>(
+            state.clone(),
+        ));
 
-    let app = Router::new()
+    let features = &state.config.features;
+    let mut public = Router::new()
         .route("/", get(default_path))
-        .route("/signin", post(post_users_route))
-        .layer(cors);
+        .route("/healthz", get(get_healthz_route))
+        .route("/readyz", get(get_readyz_route))
+        .route("/users/verify", get(get_verify_route))
+        .route("/users/verification", post(post_verification_route))
+        .route("/users/password/forgot", post(post_password_forgot_route))
+        .route("/users/password/reset", post(post_password_reset_route))
+        .route(
+            "/sessions",
+            post(post_sessions_route).delete(delete_sessions_route),
+        )
+        .route("/sessions/refresh", post(post_refresh_route))
+        .route("/sessions/two_factor", post(post_sessions_two_factor_route));
+    if features.registration {
+        public = public.route("/users", post(post_users_route));
+    }
+    if features.oidc {
+        public = public
+            .route("/auth/oidc", get(get_oidc_providers_route))
+            .route("/auth/oidc/:provider", get(get_oidc_login_route))
+            .route(
+                "/auth/oidc/:provider/callback",
+                get(get_oidc_callback_route),
+            );
+    }
+    // else served on their own, see `metrics_router`
+    let metrics = &state.config.metrics;
+    if metrics.enabled && metrics.listen.is_none() {
+        public = public.route("/metrics", get(get_metrics_route));
+    }
 
-    const HOST: &str = "127.0.0.1";
-    const PORT: &str = "3030";
-    let listen_host_port = format!("{HOST}:{PORT}");
+    // listed from the innermost: the origin check fails with a localized
+    // error, counted before localizing drops its code, and every response,
+    // preflights included, gets the headers
+    public
+        .merge(protected)
+        .layer(middleware::from_fn_with_state(state.clone(), verify_origin))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            record_metrics,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            localize_errors,
+        ))
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security_headers,
+        ))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(RequestSpan)
+                .on_response(RequestSpan)
+                // `LibError::Internal` logs its own failures
+                .on_failure(()),
+        )
+        // a caller's own `X-Request-Id` is kept, to follow a request across
+        // services
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state)
+}
+
+/// `GET /metrics` alone, for `MetricsConfig::listen`.
+pub fn metrics_router(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics_route))
+        .with_state(state)
+}
 
-    println!("\n\n[::] Server running on http://{HOST}:{PORT}");
-    axum::Server::bind(&listen_host_port.parse().unwrap())
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+/// Serves the API until SIGTERM or Ctrl-C, then lets running requests and
+/// jobs finish, see `ServerConfig::shutdown_timeout`.
+pub async fn run_server(config: Config) -> Result<(), LibError> {
+    let address = config.server.listen;
+    let grace = Duration::from_secs(config.server.shutdown_timeout);
+    let state = AppState::connect(config).await?;
+    let jobs = state.jobs.clone();
 
+    let server = axum::Server::try_bind(&address)
+        .map_err(|error| bind_error(address, error))?;
+    tracing::info!(%address, "listening");
+    if let Some(address) = state.config.metrics.listen {
+        spawn_metrics_server(&state, address)?;
+    }
+    if state.config.features.reminders {
+        let reminders = state.reminders.clone();
+        jobs.spawn("reminders", move |signal| reminders.run(signal));
+    }
+
+    serve(server, router(state), jobs, grace, shutdown_signal()).await
+}
+
+/// Serves `metrics_router` on `address` as a background job, so it stops
+/// along with the jobs it reports on.
+fn spawn_metrics_server(
+    state: &AppState,
+    address: SocketAddr,
+) -> Result<(), LibError> {
+    let server = axum::Server::try_bind(&address)
+        .map_err(|error| bind_error(address, error))?
+        .serve(metrics_router(state.clone()).into_make_service());
+    tracing::info!(%address, "serving metrics");
+
+    state
+        .jobs
+        .spawn("metrics server", |mut signal| async move {
+            let server = server.with_graceful_shutdown(async move {
+                signal.stopped().await;
+            });
+            if let Err(error) = server.await {
+                tracing::error!(%error, "metrics server failed");
+            }
+        });
     Ok(())
 }
 
+fn bind_error(address: SocketAddr, error: hyper::Error) -> LibError {
+    LibError::BindError {
+        address: address.to_string(),
+        // hyper's own message only says it couldn't listen
+        reason: error
+            .source()
+            .map_or_else(|| error.to_string(), ToString::to_string),
+    }
+}
+
+/// Runs `server` until `signal`, then stops taking connections and gives
+/// the running requests and jobs until `grace` is over.
+async fn serve(
+    server: hyper::server::Builder<AddrIncoming>,
+    app: Router,
+    jobs: BackgroundJobs,
+    grace: Duration,
+    signal: impl Future<Output = ()>,
+) -> Result<(), LibError> {
+    let (stop, stopped) = oneshot::channel();
+    let server = server
+        // peer addresses feed the sign-in throttling
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async {
+            stopped.await.ok();
+        });
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return result.map_err(LibError::internal),
+        () = signal => {}
+    }
+    tracing::info!("stopping, finishing running requests and jobs");
+    stop.send(()).ok();
+    let deadline = Instant::now() + grace;
+    let (requests, _) = tokio::join!(
+        tokio::time::timeout_at(deadline, server),
+        jobs.stop(grace),
+    );
+
+    match requests {
+        Ok(result) => result.map_err(LibError::internal)?,
+        Err(_) => tracing::warn!("requests still running, dropped"),
+    }
+    tracing::info!("stopped");
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or on the SIGTERM of supervisors and container
+/// runtimes.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => {}
+        () = terminate => {}
+    }
+}
+
 pub async fn default_path() -> &'static str {
     "Home"
 }
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        repository::now, services::password::tests::mock_hashing_params,
+    };
+    use api_shared::{
+        client::ApiClient,
+        dto::{SignInForm, SignInOutcome, UserForm},
+    };
+    use std::net::TcpListener;
+    use uuid::Uuid;
+
+    /// State kept in memory, with cheap password hashing.
+    pub(crate) fn mock_state() -> AppState {
+        AppState::in_memory(mock_hashing_params()).unwrap()
+    }
+
+    /// Serves the routes on a free local port, for `ApiClient` to call.
+    pub(super) fn spawn_server(state: AppState) -> ApiClient {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router(state).into_make_service());
+        tokio::spawn(server);
+
+        ApiClient::new(format!("http://{address}"))
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown() -> miette::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = Router::new().route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                "done"
+            }),
+        );
+        let jobs = BackgroundJobs::default();
+        let (job_stopped, job_result) = oneshot::channel();
+        jobs.spawn("job", |mut signal| async move {
+            signal.stopped().await;
+            job_stopped.send(()).ok();
+        });
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            axum::Server::from_tcp(listener).unwrap(),
+            app,
+            jobs,
+            Duration::from_secs(5),
+            async {
+                stopped.await.ok();
+            },
+        ));
+
+        let request =
+            tokio::spawn(reqwest::get(format!("http://{address}/slow")));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stop.send(()).ok();
+        let body = request
+            .await
+            .unwrap()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let result = server.await.unwrap();
+
+        miette::ensure!(
+            body == "done" && result.is_ok() && job_result.await.is_ok(),
+            "Error: running work dropped on shutdown {result:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_client() -> miette::Result<()> {
+        let state = mock_state();
+        let client = spawn_server(state.clone());
+        let form = UserForm {
+            email: "user@email.com".to_string(),
+            password: "correct horse battery staple".to_string(),
+            username: "username".to_string(),
+        };
+        let credentials = SignInForm {
+            email: form.email.clone(),
+            password: form.password.clone(),
+        };
+
+        let invalid = client
+            .sign_up(&UserForm {
+                email: "x".to_string(),
+                ..form.clone()
+            })
+            .await;
+        client.sign_up(&form).await?;
+        let taken = client.sign_up(&form).await;
+        let unverified = client.sign_in(&credentials).await;
+        miette::ensure!(
+            matches!(&invalid, Err(LibError::Validation { fields }) if fields[0].field == "email")
+                && matches!(taken, Err(LibError::EmailTaken))
+                && matches!(unverified, Err(LibError::EmailNotVerified)),
+            "Error: server errors not decoded {invalid:?}"
+        );
+
+        let user = state
+            .users
+            .find_by_email(&form.email)
+            .await?
+            .unwrap();
+        state
+            .users
+            .mark_email_verified(user.id, now())
+            .await?;
+        let SignInOutcome::SignedIn(tokens) =
+            client.sign_in(&credentials).await?
+        else {
+            miette::bail!("Error: 2FA asked without being enabled");
+        };
+        let client = client.with_token(tokens.access_token);
+        let profile = client.profile().await?;
+        let sessions = client.list_sessions().await?;
+        let unknown = client
+            .revoke_session(Uuid::new_v4())
+            .await;
+
+        miette::ensure!(
+            profile.id == user.id
+                && sessions.len() == 1
+                && sessions[0].id == tokens.session_id
+                && matches!(unknown, Err(LibError::NotFound)),
+            "Error: unexpected answers {profile:?} {sessions:?}"
+        );
+        Ok(())
+    }
+}
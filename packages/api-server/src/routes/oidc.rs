@@ -0,0 +1,128 @@
+// external crates
+use api_shared::{
+    dto::{OidcCallback, OidcProviderList, SignInOutcome},
+    prelude::LibError,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName},
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+    Json,
+};
+use cookie::{time::Duration, Cookie, SameSite};
+// local modules
+use crate::{
+    routes::{
+        sessions::{read_cookie, session_cookies, user_agent},
+        AppState,
+    },
+    services::{
+        authenticate_oidc_service, complete_sign_in, link_oidc_account_service,
+        start_oidc_login_service, OIDC_LOGIN_TTL_MINUTES,
+    },
+};
+
+/// HttpOnly cookie keeping the `state` of the login the browser started,
+/// which the callback must come back with.
+pub const OIDC_STATE_COOKIE: &str = "sight_oidc_state";
+
+type StateCookie = AppendHeaders<[(HeaderName, String); 1]>;
+
+/// Lists the providers the app can offer to sign in with.
+pub async fn get_oidc_providers_route(
+    State(state): State<AppState>,
+) -> Json<OidcProviderList> {
+    Json(OidcProviderList {
+        providers: state.oidc_providers.names(),
+    })
+}
+
+/// Sends the browser to the provider's sign-in page.
+pub async fn get_oidc_login_route(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+) -> Result<(StateCookie, Redirect), LibError> {
+    let start = start_oidc_login_service(
+        state.oidc.as_ref(),
+        &state.oidc_providers,
+        &provider,
+    )
+    .await?;
+
+    Ok((
+        state_cookie(&start.state, OIDC_LOGIN_TTL_MINUTES * 60),
+        Redirect::to(start.url.as_str()),
+    ))
+}
+
+/// Where the provider sends the browser back to. Only the browser holding
+/// the login's state cookie gets through. Signs in with session cookies
+/// and returns to the app, or hands the app a 2FA challenge when the
+/// account has 2FA enabled.
+pub async fn get_oidc_callback_route(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallback>,
+) -> Result<Response, LibError> {
+    let claims = authenticate_oidc_service(
+        query,
+        read_cookie(&headers, OIDC_STATE_COOKIE).as_deref(),
+        state.oidc.as_ref(),
+        &state.oidc_providers,
+        &provider,
+    )
+    .await?;
+    let user = link_oidc_account_service(
+        state.users.as_ref(),
+        state.oidc.as_ref(),
+        &state.passwords,
+        &provider,
+        &claims,
+    )
+    .await?;
+    let outcome = complete_sign_in(
+        state.sessions.as_ref(),
+        state.two_factor.as_ref(),
+        &state.tokens,
+        &user,
+        user_agent(&headers),
+    )
+    .await?;
+
+    let app_url = &state.links.app_url;
+    // the state was spent
+    let clear_state = state_cookie("", 0);
+    Ok(match outcome {
+        SignInOutcome::SignedIn(tokens) => {
+            (clear_state, session_cookies(&tokens), Redirect::to(app_url))
+                .into_response()
+        }
+        // in the fragment, which browsers keep to themselves, so the token
+        // stays out of server logs and Referer headers
+        SignInOutcome::TwoFactorRequired(challenge) => (
+            clear_state,
+            Redirect::to(&format!(
+                "{app_url}/signin#challenge_token={}",
+                challenge.challenge_token
+            )),
+        )
+            .into_response(),
+    })
+}
+
+/// Only sent to the OIDC routes. `SameSite=Lax`, not `Strict`, as the
+/// browser comes back to the callback from the provider's site.
+fn state_cookie(value: &str, max_age: i64) -> StateCookie {
+    AppendHeaders([(
+        header::SET_COOKIE,
+        Cookie::build(OIDC_STATE_COOKIE, value)
+            .path("/auth/oidc")
+            .http_only(true)
+            .secure(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::seconds(max_age))
+            .finish()
+            .to_string(),
+    )])
+}
@@ -0,0 +1,245 @@
+// external crates
+use api_shared::prelude::LibError;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use url::Url;
+// local modules
+use crate::routes::{
+    auth::bearer_token,
+    sessions::{read_cookie, ACCESS_COOKIE, REFRESH_COOKIE},
+    AppState,
+};
+
+/// Adds the headers restricting what browsers do with a response, see
+/// `SecurityConfig`. Handlers setting one of them keep their own.
+pub async fn security_headers<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let security = &state.config.security;
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    let mut set = |name, value: HeaderValue| {
+        headers.entry(name).or_insert(value);
+    };
+    set(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    // checked when loading the configuration
+    if let Ok(policy) = HeaderValue::from_str(&security.content_security_policy)
+    {
+        set(header::CONTENT_SECURITY_POLICY, policy);
+    }
+    // `frame-ancestors` for browsers predating it
+    set(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    set(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    if security.hsts_max_age > 0 {
+        let hsts =
+            format!("max-age={}; includeSubDomains", security.hsts_max_age);
+        set(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_str(&hsts).expect("digits are a valid header"),
+        );
+    }
+
+    response
+}
+
+/// Rejects changes signed in by cookie that come from a page of another
+/// site, which browsers would otherwise send along with the user's
+/// cookies.
+///
+/// The page's origin is read from `Origin`, or `Referer` for the requests
+/// that lack it, and must be one of the CORS allowlist or the server's own.
+/// Bearer tokens are never sent implicitly, so their requests pass.
+pub async fn verify_origin<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let headers = request.headers();
+    let is_change = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    // the same choice `AuthUser` makes, so a stray `Authorization` header
+    // doesn't exempt a request that is then signed in by cookie
+    let by_cookie = bearer_token(headers).is_none()
+        && (read_cookie(headers, ACCESS_COOKIE).is_some()
+            || read_cookie(headers, REFRESH_COOKIE).is_some());
+
+    if is_change && by_cookie && !is_trusted(&state, headers) {
+        return LibError::InvalidOrigin.into_response();
+    }
+
+    next.run(request).await
+}
+
+fn is_trusted(state: &AppState, headers: &HeaderMap) -> bool {
+    let origin = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Url::parse(value).ok())
+        .map(|url| url.origin().ascii_serialization());
+    let Some(origin) = origin else {
+        return false;
+    };
+    let own = Url::parse(&state.config.server.api_url)
+        .map(|url| url.origin().ascii_serialization());
+
+    state
+        .config
+        .cors
+        .allowed_origins
+        .contains(&origin)
+        || own.as_ref() == Ok(&origin)
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        routes::{router, tests::mock_state},
+    };
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::Response,
+    };
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn call(config: Config, request: Request<Body>) -> Response {
+        let mut state = mock_state();
+        state.config = Arc::new(config);
+        router(state)
+            .oneshot(request)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_security_headers() -> miette::Result<()> {
+        let request = || {
+            Request::get("/")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let plain = call(Config::default(), request()).await;
+        let mut config = Config::default();
+        config.security.hsts_max_age = 3600;
+        let hsts = call(config, request()).await;
+
+        let headers = plain.headers();
+        miette::ensure!(
+            headers[header::X_CONTENT_TYPE_OPTIONS] == "nosniff"
+                && headers[header::CONTENT_SECURITY_POLICY]
+                    .to_str()
+                    .unwrap()
+                    .contains("frame-ancestors 'none'")
+                && !headers.contains_key(header::STRICT_TRANSPORT_SECURITY)
+                && hsts.headers()[header::STRICT_TRANSPORT_SECURITY]
+                    == "max-age=3600; includeSubDomains",
+            "Error: unexpected headers {headers:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowlist() -> miette::Result<()> {
+        let preflight = |origin: &str| {
+            Request::options("/users/me/locale")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let allowed =
+            call(Config::default(), preflight("http://127.0.0.1:8080")).await;
+        let other =
+            call(Config::default(), preflight("https://evil.example")).await;
+
+        let allowed = allowed.headers();
+        miette::ensure!(
+            allowed[header::ACCESS_CONTROL_ALLOW_ORIGIN]
+                == "http://127.0.0.1:8080"
+                && allowed[header::ACCESS_CONTROL_ALLOW_CREDENTIALS] == "true"
+                && allowed[header::ACCESS_CONTROL_ALLOW_METHODS]
+                    .to_str()
+                    .unwrap()
+                    .contains("PUT")
+                && !other
+                    .headers()
+                    .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            "Error: unexpected CORS headers {allowed:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cross_site_changes_rejected() -> miette::Result<()> {
+        let sign_out = |origin: Option<&str>, credentials: &[(&str, &str)]| {
+            let mut request = Request::delete("/sessions");
+            for (name, value) in credentials {
+                request = request.header(*name, *value);
+            }
+            if let Some(origin) = origin {
+                request = request.header(header::ORIGIN, origin);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let cookie = (header::COOKIE.as_str(), "sight_refresh=token");
+        let bearer = (header::AUTHORIZATION.as_str(), "Bearer token");
+        let basic = (header::AUTHORIZATION.as_str(), "Basic dXNlcjpwYXNz");
+
+        let cases = [
+            (
+                Some("https://evil.example"),
+                vec![cookie],
+                StatusCode::FORBIDDEN,
+            ),
+            (None, vec![cookie], StatusCode::FORBIDDEN),
+            (
+                Some("http://127.0.0.1:8080"),
+                vec![cookie],
+                StatusCode::NO_CONTENT,
+            ),
+            (
+                Some("https://evil.example"),
+                vec![bearer],
+                StatusCode::NO_CONTENT,
+            ),
+            // not a bearer token, so the cookie is what signs it in
+            (
+                Some("https://evil.example"),
+                vec![cookie, basic],
+                StatusCode::FORBIDDEN,
+            ),
+        ];
+        for (origin, credentials, status) in cases {
+            let response =
+                call(Config::default(), sign_out(origin, &credentials)).await;
+
+            miette::ensure!(
+                response.status() == status,
+                "Error: {origin:?} with {credentials:?} answered {}",
+                response.status()
+            );
+        }
+        Ok(())
+    }
+}
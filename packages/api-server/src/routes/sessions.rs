@@ -0,0 +1,230 @@
+// external crates
+use api_shared::{
+    dto::{
+        RefreshTokenForm, SessionInfo, SessionTokens, SignInForm,
+        SignInOutcome, TwoFactorForm,
+    },
+    prelude::LibError,
+};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse, Response},
+    Json,
+};
+use cookie::{time::Duration, Cookie, SameSite};
+use std::net::SocketAddr;
+use uuid::Uuid;
+// local modules
+use crate::{
+    routes::{auth::AuthUser, AppState},
+    services::{
+        list_sessions_service, refresh_session_service, revoke_session_service,
+        sign_in_service, sign_out_service, verify_second_factor_service,
+        ClientInfo,
+    },
+};
+
+/// HttpOnly cookie carrying the access token for browser clients.
+pub const ACCESS_COOKIE: &str = "sight_access";
+/// HttpOnly cookie carrying the refresh token, only sent to `/sessions`.
+pub const REFRESH_COOKIE: &str = "sight_refresh";
+
+pub(super) type SetCookies = AppendHeaders<[(header::HeaderName, String); 2]>;
+
+/// Signs in. Tokens are returned in the body for API clients and as
+/// HttpOnly cookies for the browser.
+///
+/// Accounts with 2FA enabled answer `202 Accepted` with a challenge
+/// instead, to complete at `POST /sessions/two_factor`.
+///
+/// The peer address is used to throttle failures per client. It is absent
+/// when the server isn't started with connect info, as in tests.
+pub async fn post_sessions_route(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(body): Json<SignInForm>,
+) -> Result<Response, LibError> {
+    let outcome = sign_in_service(
+        body,
+        state.users.as_ref(),
+        state.sessions.as_ref(),
+        state.two_factor.as_ref(),
+        &state.passwords,
+        &state.tokens,
+        &state.throttle,
+        client_info(connect_info, &headers),
+    )
+    .await?;
+
+    Ok(match outcome {
+        SignInOutcome::SignedIn(tokens) => {
+            (session_cookies(&tokens), Json(tokens)).into_response()
+        }
+        SignInOutcome::TwoFactorRequired(challenge) => {
+            (StatusCode::ACCEPTED, Json(challenge)).into_response()
+        }
+    })
+}
+
+/// Completes a sign-in with a TOTP or recovery code.
+pub async fn post_sessions_two_factor_route(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(body): Json<TwoFactorForm>,
+) -> Result<(SetCookies, Json<SessionTokens>), LibError> {
+    let tokens = verify_second_factor_service(
+        body,
+        state.users.as_ref(),
+        state.two_factor.as_ref(),
+        state.sessions.as_ref(),
+        &state.tokens,
+        &state.throttle,
+        client_info(connect_info, &headers),
+    )
+    .await?;
+
+    Ok((session_cookies(&tokens), Json(tokens)))
+}
+
+/// Rotates the refresh token and mints a new access token.
+pub async fn post_refresh_route(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<RefreshTokenForm>>,
+) -> Result<(SetCookies, Json<SessionTokens>), LibError> {
+    let refresh_token =
+        refresh_token(&headers, body).ok_or(LibError::MissingCredentials)?;
+    let tokens = refresh_session_service(
+        state.sessions.as_ref(),
+        &state.tokens,
+        &refresh_token,
+        user_agent(&headers),
+    )
+    .await?;
+
+    Ok((session_cookies(&tokens), Json(tokens)))
+}
+
+/// Lists the devices where the caller is signed in.
+pub async fn get_sessions_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<SessionInfo>>, LibError> {
+    let sessions = list_sessions_service(
+        state.sessions.as_ref(),
+        user.user_id,
+        user.session_id,
+    )
+    .await?;
+
+    Ok(Json(sessions))
+}
+
+/// Signs out one of the caller's devices.
+pub async fn delete_session_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, LibError> {
+    revoke_session_service(state.sessions.as_ref(), user.user_id, session_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Signs out the session owning the refresh token, taken from the body or
+/// from the refresh cookie.
+pub async fn delete_sessions_route(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<RefreshTokenForm>>,
+) -> Result<(StatusCode, SetCookies), LibError> {
+    if let Some(refresh_token) = refresh_token(&headers, body) {
+        sign_out_service(state.sessions.as_ref(), &refresh_token).await?;
+    }
+
+    Ok((StatusCode::NO_CONTENT, clear_session_cookies()))
+}
+
+pub fn read_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == name)
+        .map(|cookie| cookie.value().to_string())
+}
+
+fn refresh_token(
+    headers: &HeaderMap,
+    body: Option<Json<RefreshTokenForm>>,
+) -> Option<String> {
+    body.and_then(|Json(form)| form.refresh_token)
+        .or_else(|| read_cookie(headers, REFRESH_COOKIE))
+}
+
+fn client_info(
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: &HeaderMap,
+) -> ClientInfo {
+    ClientInfo {
+        ip: connect_info.map(|ConnectInfo(addr)| addr.ip()),
+        user_agent: user_agent(headers),
+    }
+}
+
+pub(super) fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(256).collect())
+}
+
+pub(super) fn session_cookies(tokens: &SessionTokens) -> SetCookies {
+    AppendHeaders([
+        (
+            header::SET_COOKIE,
+            build_cookie(
+                ACCESS_COOKIE,
+                &tokens.access_token,
+                "/",
+                tokens.expires_in,
+            ),
+        ),
+        (
+            header::SET_COOKIE,
+            build_cookie(
+                REFRESH_COOKIE,
+                &tokens.refresh_token,
+                "/sessions",
+                tokens.refresh_expires_in,
+            ),
+        ),
+    ])
+}
+
+fn clear_session_cookies() -> SetCookies {
+    AppendHeaders([
+        (header::SET_COOKIE, build_cookie(ACCESS_COOKIE, "", "/", 0)),
+        (
+            header::SET_COOKIE,
+            build_cookie(REFRESH_COOKIE, "", "/sessions", 0),
+        ),
+    ])
+}
+
+fn build_cookie(name: &str, value: &str, path: &str, max_age: i64) -> String {
+    Cookie::build(name, value)
+        .path(path)
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .max_age(Duration::seconds(max_age))
+        .finish()
+        .to_string()
+}
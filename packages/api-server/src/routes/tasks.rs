@@ -0,0 +1,251 @@
+// external crates
+use api_shared::{
+    dto::{
+        Occurrence, OccurrencePatch, OccurrenceQuery, Tag, TagPatch, Task,
+        TaskForm, TaskPatch, TaskQuery,
+    },
+    prelude::LibError,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDate;
+use uuid::Uuid;
+// local modules
+use crate::{
+    routes::{auth::AuthUser, AppState},
+    services::{
+        create_task_service, delete_task_service, get_task_service,
+        list_occurrences_service, list_tags_service, list_tasks_service,
+        update_occurrence_service, update_tag_service, update_task_service,
+    },
+};
+
+pub async fn get_tasks_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<TaskQuery>,
+) -> Result<Json<Vec<Task>>, LibError> {
+    let tasks =
+        list_tasks_service(query, state.tasks.as_ref(), user.user_id).await?;
+
+    Ok(Json(tasks))
+}
+
+pub async fn post_tasks_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(body): Json<TaskForm>,
+) -> Result<(StatusCode, Json<Task>), LibError> {
+    let task =
+        create_task_service(body, state.tasks.as_ref(), user.user_id).await?;
+    // its reminders may come before the ones the scheduler sleeps until
+    state.reminders.wake();
+
+    Ok((StatusCode::CREATED, Json(task)))
+}
+
+pub async fn get_task_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<Task>, LibError> {
+    let task =
+        get_task_service(state.tasks.as_ref(), user.user_id, task_id).await?;
+
+    Ok(Json(task))
+}
+
+pub async fn patch_task_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(task_id): Path<Uuid>,
+    Json(body): Json<TaskPatch>,
+) -> Result<Json<Task>, LibError> {
+    let task =
+        update_task_service(body, state.tasks.as_ref(), user.user_id, task_id)
+            .await?;
+    state.reminders.wake();
+
+    Ok(Json(task))
+}
+
+pub async fn delete_task_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(task_id): Path<Uuid>,
+) -> Result<StatusCode, LibError> {
+    delete_task_service(state.tasks.as_ref(), user.user_id, task_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_occurrences_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<OccurrenceQuery>,
+) -> Result<Json<Vec<Occurrence>>, LibError> {
+    let occurrences =
+        list_occurrences_service(query, state.tasks.as_ref(), user.user_id)
+            .await?;
+
+    Ok(Json(occurrences))
+}
+
+pub async fn patch_occurrence_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((task_id, date)): Path<(Uuid, NaiveDate)>,
+    Json(body): Json<OccurrencePatch>,
+) -> Result<Json<Occurrence>, LibError> {
+    let occurrence = update_occurrence_service(
+        body,
+        state.tasks.as_ref(),
+        user.user_id,
+        task_id,
+        date,
+    )
+    .await?;
+
+    Ok(Json(occurrence))
+}
+
+pub async fn get_tags_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<Vec<Tag>>, LibError> {
+    let tags = list_tags_service(state.tasks.as_ref(), user.user_id).await?;
+
+    Ok(Json(tags))
+}
+
+pub async fn patch_tag_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(name): Path<String>,
+    Json(body): Json<TagPatch>,
+) -> Result<Json<Tag>, LibError> {
+    let tag =
+        update_tag_service(body, state.tasks.as_ref(), user.user_id, &name)
+            .await?;
+
+    Ok(Json(tag))
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        repository::now,
+        routes::tests::{mock_state, spawn_server},
+    };
+    use api_shared::{
+        dto::{
+            OccurrencePatch, OccurrenceQuery, Recurrence, TagPatch, TaskForm,
+            TaskPatch, TaskQuery, TaskStatus,
+        },
+        prelude::LibError,
+    };
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_tasks_of_signed_in_user() -> miette::Result<()> {
+        let state = mock_state();
+        let token = |user_id| {
+            state
+                .tokens
+                .mint_access(user_id, Uuid::new_v4(), now())
+        };
+        let anonymous = spawn_server(state.clone());
+        let owner = anonymous
+            .clone()
+            .with_token(token(Uuid::new_v4())?);
+        let stranger = anonymous
+            .clone()
+            .with_token(token(Uuid::new_v4())?);
+        let form = TaskForm {
+            title: "Water the plants".to_string(),
+            tags: vec!["Casa & jardim".to_string()],
+            ..TaskForm::default()
+        };
+        let today = Utc::now().date_naive();
+
+        let signed_out = anonymous.create_task(&form).await;
+        let invalid = owner
+            .create_task(&TaskForm::default())
+            .await;
+        let task = owner.create_task(&form).await?;
+        let done = owner
+            .update_task(
+                task.id,
+                &TaskPatch {
+                    status: Some(TaskStatus::Done),
+                    ..TaskPatch::default()
+                },
+            )
+            .await?;
+        let listed = owner
+            .list_tasks(&TaskQuery {
+                status: Some(TaskStatus::Done),
+                tag: Some("casa & jardim".to_string()),
+            })
+            .await?;
+        let tag = owner
+            .update_tag(
+                "CASA & JARDIM",
+                &TagPatch {
+                    color: Some("#123456".to_string()),
+                },
+            )
+            .await?;
+        let tags = owner.list_tags().await?;
+        let habit = owner
+            .create_task(&TaskForm {
+                title: "Rinse and repeat".to_string(),
+                recurrence: Some(Recurrence {
+                    rule: "FREQ=DAILY".to_string(),
+                    starts_on: today,
+                    exceptions: Vec::new(),
+                }),
+                ..TaskForm::default()
+            })
+            .await?;
+        let occurrence = owner
+            .update_occurrence(habit.id, today, &OccurrencePatch { done: true })
+            .await?;
+        let occurrences = owner
+            .list_occurrences(&OccurrenceQuery {
+                from: today,
+                to: today + Duration::days(1),
+            })
+            .await?;
+        let hidden_occurrence = stranger
+            .update_occurrence(habit.id, today, &OccurrencePatch { done: true })
+            .await;
+        let hidden = stranger.task(task.id).await;
+        let not_deleted = stranger.delete_task(task.id).await;
+        owner.delete_task(task.id).await?;
+        let deleted = owner.task(task.id).await;
+
+        miette::ensure!(
+            matches!(signed_out, Err(LibError::MissingCredentials))
+                && matches!(&invalid, Err(LibError::Validation { fields }) if fields[0].code == "title.required")
+                && listed == [done]
+                && tag.color == "#123456"
+                && tags == [tag]
+                && occurrences.len() == 2
+                && occurrences[0] == occurrence
+                && occurrence.completed_at.is_some()
+                && matches!(hidden_occurrence, Err(LibError::NotFound))
+                && matches!(hidden, Err(LibError::NotFound))
+                && matches!(not_deleted, Err(LibError::NotFound))
+                && matches!(deleted, Err(LibError::NotFound)),
+            "Error: unexpected answers {invalid:?} {listed:?}"
+        );
+        Ok(())
+    }
+}
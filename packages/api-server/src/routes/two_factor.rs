@@ -0,0 +1,76 @@
+// external crates
+use api_shared::{
+    dto::{
+        CodeForm, PasswordForm, RecoveryCodes, TotpEnrollment, TwoFactorStatus,
+    },
+    prelude::LibError,
+};
+use axum::{extract::State, http::StatusCode, Json};
+// local modules
+use crate::{
+    routes::{auth::AuthUser, AppState},
+    services::{
+        confirm_totp_enrollment_service, disable_two_factor_service,
+        start_totp_enrollment_service, two_factor_status_service,
+    },
+};
+
+pub async fn get_two_factor_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<TwoFactorStatus>, LibError> {
+    let status =
+        two_factor_status_service(state.two_factor.as_ref(), user.user_id)
+            .await?;
+
+    Ok(Json(status))
+}
+
+/// Generates the secret to scan into an authenticator app.
+pub async fn post_two_factor_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<TotpEnrollment>, LibError> {
+    let enrollment = start_totp_enrollment_service(
+        state.users.as_ref(),
+        state.two_factor.as_ref(),
+        user.user_id,
+    )
+    .await?;
+
+    Ok(Json(enrollment))
+}
+
+/// Enables 2FA with a first code from the app. The recovery codes are
+/// only ever sent in this response.
+pub async fn post_two_factor_confirm_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(body): Json<CodeForm>,
+) -> Result<Json<RecoveryCodes>, LibError> {
+    let codes = confirm_totp_enrollment_service(
+        state.two_factor.as_ref(),
+        user.user_id,
+        &body.code,
+    )
+    .await?;
+
+    Ok(Json(codes))
+}
+
+pub async fn delete_two_factor_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(body): Json<PasswordForm>,
+) -> Result<StatusCode, LibError> {
+    disable_two_factor_service(
+        body,
+        state.users.as_ref(),
+        state.two_factor.as_ref(),
+        &state.passwords,
+        user.user_id,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,11 +1,129 @@
 // external crates
-use api_shared::prelude::LibError;
-use axum::{Json};
+use api_shared::{
+    dto::{
+        EmailForm, LocaleForm, PasswordResetForm, TokenQuery, UserForm,
+        UserProfile,
+    },
+    prelude::LibError,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
 // local modules
-use crate::services::UserForm;
+use crate::{
+    routes::{auth::AuthUser, AppState},
+    services::{
+        create_user_service, get_user_service, request_password_reset_service,
+        resend_verification_service, reset_password_service,
+        send_verification_service, update_locale_service, verify_email_service,
+    },
+};
 
-pub async fn post_users_route(Json(body): Json<UserForm>) -> Result<&'static str, LibError> {
-    body.create_user_service()?;
+/// Registers an account and mails it a verification link.
+pub async fn post_users_route(
+    State(state): State<AppState>,
+    Json(body): Json<UserForm>,
+) -> Result<&'static str, LibError> {
+    let user =
+        create_user_service(body, state.users.as_ref(), &state.passwords)
+            .await?;
+    send_verification_service(
+        state.user_tokens.as_ref(),
+        state.mailer.as_ref(),
+        &state.links,
+        &user,
+    )
+    .await?;
 
     Ok("Success: account created")
 }
+
+pub async fn get_me_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<Json<UserProfile>, LibError> {
+    let profile = get_user_service(state.users.as_ref(), user.user_id).await?;
+
+    Ok(Json(profile))
+}
+
+/// Saves the language the user's error messages are shown in.
+pub async fn put_locale_route(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(body): Json<LocaleForm>,
+) -> Result<Json<UserProfile>, LibError> {
+    let profile =
+        update_locale_service(body, state.users.as_ref(), user.user_id).await?;
+
+    Ok(Json(profile))
+}
+
+/// Target of the mailed verification link.
+pub async fn get_verify_route(
+    State(state): State<AppState>,
+    Query(query): Query<TokenQuery>,
+) -> Result<&'static str, LibError> {
+    verify_email_service(
+        state.users.as_ref(),
+        state.user_tokens.as_ref(),
+        &query.token,
+    )
+    .await?;
+
+    Ok("Success: email verified")
+}
+
+/// Mails a new verification link. Always accepted, whether or not the
+/// email is registered.
+pub async fn post_verification_route(
+    State(state): State<AppState>,
+    Json(body): Json<EmailForm>,
+) -> Result<StatusCode, LibError> {
+    resend_verification_service(
+        state.users.as_ref(),
+        state.user_tokens.as_ref(),
+        state.mailer.as_ref(),
+        &state.links,
+        &body.email,
+    )
+    .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Mails a password reset link. Always accepted, whether or not the email
+/// is registered.
+pub async fn post_password_forgot_route(
+    State(state): State<AppState>,
+    Json(body): Json<EmailForm>,
+) -> Result<StatusCode, LibError> {
+    request_password_reset_service(
+        state.users.as_ref(),
+        state.user_tokens.as_ref(),
+        state.mailer.as_ref(),
+        &state.links,
+        &body.email,
+    )
+    .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn post_password_reset_route(
+    State(state): State<AppState>,
+    Json(body): Json<PasswordResetForm>,
+) -> Result<&'static str, LibError> {
+    reset_password_service(
+        body,
+        state.users.as_ref(),
+        state.user_tokens.as_ref(),
+        state.sessions.as_ref(),
+        &state.passwords,
+    )
+    .await?;
+
+    Ok("Success: password changed")
+}
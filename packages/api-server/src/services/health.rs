@@ -0,0 +1,113 @@
+// external crates
+use api_shared::{
+    dto::{HealthReport, HealthStatus},
+    prelude::LibError,
+};
+use async_trait::async_trait;
+use sqlx::AnyPool;
+use std::{io, sync::Arc, time::Duration};
+// local modules
+use crate::repository;
+
+/// How long a check may take before it counts as failed, so a hung
+/// dependency doesn't hang the probes too.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Something the server needs to answer requests, see `readiness_service`.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// key of the check in `HealthReport::checks`
+    fn name(&self) -> &'static str;
+    async fn check(&self) -> Result<(), LibError>;
+}
+
+/// Whether the server can take requests: `Ok` when every check passed.
+/// Failures are logged, the report only names the failing checks.
+pub async fn readiness_service(
+    checks: &[Arc<dyn HealthCheck>],
+) -> HealthReport {
+    let mut report = HealthReport {
+        status: HealthStatus::Ok,
+        checks: Default::default(),
+    };
+    for check in checks {
+        let result = tokio::time::timeout(CHECK_TIMEOUT, check.check())
+            .await
+            .unwrap_or_else(|_| {
+                Err(LibError::internal(io::Error::other("timed out")))
+            });
+        let status = match result {
+            Ok(()) => HealthStatus::Ok,
+            Err(error) => {
+                tracing::warn!(check = check.name(), ?error, "not ready");
+                report.status = HealthStatus::Unavailable;
+                HealthStatus::Unavailable
+            }
+        };
+        report
+            .checks
+            .insert(check.name().to_string(), status);
+    }
+
+    report
+}
+
+/// The SQL database answers queries.
+pub struct DatabaseCheck {
+    pool: AnyPool,
+}
+
+impl DatabaseCheck {
+    pub fn new(pool: AnyPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for DatabaseCheck {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn check(&self) -> Result<(), LibError> {
+        repository::ping(&self.pool).await
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Failing;
+
+    #[async_trait]
+    impl HealthCheck for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn check(&self) -> Result<(), LibError> {
+            Err(LibError::internal(io::Error::other("unreachable")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness() -> miette::Result<()> {
+        let pool = repository::connect("sqlite::memory:").await?;
+        let database: Arc<dyn HealthCheck> = Arc::new(DatabaseCheck::new(pool));
+
+        let ready = readiness_service(std::slice::from_ref(&database)).await;
+        let unready = readiness_service(&[database, Arc::new(Failing)]).await;
+
+        miette::ensure!(
+            ready.status == HealthStatus::Ok
+                && unready.status == HealthStatus::Unavailable
+                && unready.checks["database"] == HealthStatus::Ok
+                && unready.checks["failing"] == HealthStatus::Unavailable,
+            "Error: unexpected reports {ready:?} {unready:?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+// external crates
+use async_trait::async_trait;
+use std::{
+    future::Future,
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
+use tracing::Instrument;
+// local modules
+use super::HealthCheck;
+use api_shared::prelude::LibError;
+
+/// Name and task of each job spawned.
+type Jobs = Vec<(&'static str, JoinHandle<()>)>;
+
+/// Jobs running next to the server, stopped along with it, see
+/// `BackgroundJobs::stop`. Clones share the same jobs.
+#[derive(Clone)]
+pub struct BackgroundJobs {
+    stop: Arc<watch::Sender<bool>>,
+    running: Arc<Mutex<Jobs>>,
+}
+
+impl Default for BackgroundJobs {
+    fn default() -> Self {
+        Self {
+            stop: Arc::new(watch::channel(false).0),
+            running: Arc::default(),
+        }
+    }
+}
+
+/// Tells a job the server is stopping: it should finish the work at hand
+/// and return.
+#[derive(Debug, Clone)]
+pub struct StopSignal(watch::Receiver<bool>);
+
+impl StopSignal {
+    pub fn is_stopped(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once the server stops, e.g. to `tokio::select!` against the
+    /// job's timer.
+    pub async fn stopped(&mut self) {
+        while !*self.0.borrow_and_update() {
+            if self.0.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl BackgroundJobs {
+    /// Runs `job` on its own task, in a span named after it.
+    pub fn spawn<F, Fut>(&self, name: &'static str, job: F)
+    where
+        F: FnOnce(StopSignal) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let signal = StopSignal(self.stop.subscribe());
+        let handle = tokio::spawn(
+            job(signal).instrument(tracing::info_span!("job", name)),
+        );
+        self.running
+            .lock()
+            .unwrap()
+            .push((name, handle));
+    }
+
+    /// How many jobs haven't returned yet.
+    pub fn running(&self) -> usize {
+        self.running
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, handle)| !handle.is_finished())
+            .count()
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        *self.stop.borrow()
+    }
+
+    /// Asks every job to stop and waits up to `timeout` for them, aborting
+    /// the ones still running then. Returns whether they all returned.
+    pub async fn stop(&self, timeout: Duration) -> bool {
+        self.stop.send_replace(true);
+        let deadline = Instant::now() + timeout;
+        let running = std::mem::take(&mut *self.running.lock().unwrap());
+
+        let mut all_returned = true;
+        for (name, mut handle) in running {
+            if tokio::time::timeout_at(deadline, &mut handle)
+                .await
+                .is_err()
+            {
+                tracing::warn!(job = name, "still running, aborted");
+                handle.abort();
+                all_returned = false;
+            }
+        }
+        all_returned
+    }
+}
+
+/// Fails once the server is stopping, or when a job returned or panicked
+/// while it wasn't.
+#[async_trait]
+impl HealthCheck for BackgroundJobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    async fn check(&self) -> Result<(), LibError> {
+        if self.is_stopping() {
+            return Err(LibError::internal(io::Error::other("stopping")));
+        }
+        let running = self.running.lock().unwrap();
+        match running
+            .iter()
+            .find(|(_, handle)| handle.is_finished())
+        {
+            Some((name, _)) => Err(LibError::internal(io::Error::other(
+                format!("job {name} is no longer running"),
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_waits_for_jobs() -> miette::Result<()> {
+        let jobs = BackgroundJobs::default();
+        let (done, mut finished) = tokio::sync::mpsc::unbounded_channel();
+        jobs.spawn("cooperative", |mut signal| async move {
+            signal.stopped().await;
+            done.send("cleaned up").unwrap();
+        });
+        let healthy = jobs.check().await.is_ok();
+
+        let returned = jobs.stop(Duration::from_secs(5)).await;
+
+        miette::ensure!(
+            healthy
+                && returned
+                && finished.try_recv() == Ok("cleaned up")
+                && jobs.check().await.is_err(),
+            "Error: job not stopped cleanly"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop_aborts_stuck_jobs() -> miette::Result<()> {
+        let jobs = BackgroundJobs::default();
+        jobs.spawn("stuck", |_| std::future::pending());
+
+        let returned = jobs
+            .stop(Duration::from_millis(10))
+            .await;
+
+        miette::ensure!(!returned, "Error: stuck job reported as returned");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ended_job_unhealthy() -> miette::Result<()> {
+        let jobs = BackgroundJobs::default();
+        jobs.spawn("short", |_| async {});
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let result = jobs.check().await;
+
+        miette::ensure!(result.is_err(), "Error: ended job reported healthy");
+        Ok(())
+    }
+}
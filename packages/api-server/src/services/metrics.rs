@@ -0,0 +1,259 @@
+// external crates
+use axum::http::{Method, StatusCode};
+use prometheus_client::{
+    encoding::{text, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use sqlx::AnyPool;
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+// local modules
+use super::BackgroundJobs;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: String,
+    /// the route's pattern, e.g. `/sessions/:id`, so ids don't each get a
+    /// series
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ErrorLabels {
+    code: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PoolLabels {
+    state: &'static str,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct QueueLabels {
+    queue: &'static str,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct SourceLabels {
+    source: String,
+}
+
+/// Counters of the server, rendered by `GET /metrics`. Clones share the
+/// same counters.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    latency: Family<RequestLabels, Histogram>,
+    errors: Family<ErrorLabels, Counter>,
+    pool_connections: Family<PoolLabels, Gauge>,
+    jobs_running: Gauge,
+    queue_depth: Family<QueueLabels, Gauge>,
+    ingested: Family<SourceLabels, Counter>,
+    pool: OnceLock<AnyPool>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let requests = Family::<RequestLabels, Counter>::default();
+        // from 5ms to 10s
+        let latency =
+            Family::<RequestLabels, Histogram>::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.005, 2.0, 12))
+            });
+        let errors = Family::<ErrorLabels, Counter>::default();
+        let pool_connections = Family::<PoolLabels, Gauge>::default();
+        let jobs_running = Gauge::default();
+        let queue_depth = Family::<QueueLabels, Gauge>::default();
+        let ingested = Family::<SourceLabels, Counter>::default();
+
+        let mut registry = Registry::default();
+        registry.register(
+            "http_requests",
+            "Requests answered, by route and status",
+            requests.clone(),
+        );
+        registry.register(
+            "http_request_duration_seconds",
+            "Time taken to answer requests, by route and status",
+            latency.clone(),
+        );
+        registry.register(
+            "api_errors",
+            "Error responses, by diagnostic code",
+            errors.clone(),
+        );
+        registry.register(
+            "db_pool_connections",
+            "Open database connections, idle or in use",
+            pool_connections.clone(),
+        );
+        registry.register(
+            "jobs_running",
+            "Background jobs still running",
+            jobs_running.clone(),
+        );
+        registry.register(
+            "job_queue_depth",
+            "Work waiting for a background job, by queue",
+            queue_depth.clone(),
+        );
+        registry.register(
+            "ingested_records",
+            "Records taken in, by data source",
+            ingested.clone(),
+        );
+
+        Self {
+            inner: Arc::new(Inner {
+                registry,
+                requests,
+                latency,
+                errors,
+                pool_connections,
+                jobs_running,
+                queue_depth,
+                ingested,
+                pool: OnceLock::new(),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Reports the connections of `pool` along with the other metrics.
+    pub fn track_pool(&self, pool: AnyPool) {
+        self.inner.pool.set(pool).ok();
+    }
+
+    /// Counts an answered request. `error` is the diagnostic code of a
+    /// `LibError` answer.
+    pub fn record_request(
+        &self,
+        method: &Method,
+        route: &str,
+        status: StatusCode,
+        latency: Duration,
+        error: Option<&str>,
+    ) {
+        let labels = RequestLabels {
+            method: method.to_string(),
+            route: route.to_string(),
+            status: status.as_u16(),
+        };
+        self.inner
+            .requests
+            .get_or_create(&labels)
+            .inc();
+        self.inner
+            .latency
+            .get_or_create(&labels)
+            .observe(latency.as_secs_f64());
+        if let Some(code) = error {
+            self.inner
+                .errors
+                .get_or_create(&ErrorLabels {
+                    code: code.to_string(),
+                })
+                .inc();
+        }
+    }
+
+    /// Work waiting in `queue`, set by the job draining it.
+    pub fn queue_depth(&self, queue: &'static str) -> Gauge {
+        self.inner
+            .queue_depth
+            .get_or_create(&QueueLabels { queue })
+            .clone()
+    }
+
+    /// Counts `records` taken in from `source`, e.g. a calendar feed. The
+    /// rate of the counter is the throughput of the source.
+    pub fn record_ingested(&self, source: &str, records: u64) {
+        self.inner
+            .ingested
+            .get_or_create(&SourceLabels {
+                source: source.to_string(),
+            })
+            .inc_by(records);
+    }
+
+    /// Every metric in the Prometheus text format, the gauges read now.
+    pub fn render(&self, jobs: &BackgroundJobs) -> String {
+        let inner = &self.inner;
+        if let Some(pool) = inner.pool.get() {
+            let idle = pool.num_idle() as i64;
+            inner
+                .pool_connections
+                .get_or_create(&PoolLabels { state: "idle" })
+                .set(idle);
+            inner
+                .pool_connections
+                .get_or_create(&PoolLabels { state: "in_use" })
+                .set(i64::from(pool.size()) - idle);
+        }
+        inner
+            .jobs_running
+            .set(jobs.running() as i64);
+
+        let mut body = String::new();
+        // writing to a String can't fail
+        text::encode(&mut body, &inner.registry).ok();
+        body
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository;
+
+    #[tokio::test]
+    async fn test_render() -> miette::Result<()> {
+        let metrics = Metrics::default();
+        metrics.track_pool(repository::connect("sqlite::memory:").await?);
+        metrics.record_request(
+            &Method::POST,
+            "/sessions",
+            StatusCode::UNAUTHORIZED,
+            Duration::from_millis(20),
+            Some("LibError::InvalidCredentials"),
+        );
+        metrics.queue_depth("reminders").set(3);
+        metrics.record_ingested("calendar", 2);
+        metrics.record_ingested("calendar", 3);
+
+        let body = metrics.render(&BackgroundJobs::default());
+
+        miette::ensure!(
+            [
+                r#"http_requests_total{method="POST",route="/sessions",status="401"} 1"#,
+                r#"http_request_duration_seconds_bucket{le="0.04",method="POST",route="/sessions",status="401"} 1"#,
+                r#"api_errors_total{code="LibError::InvalidCredentials"} 1"#,
+                r#"db_pool_connections{state="idle"}"#,
+                r#"job_queue_depth{queue="reminders"} 3"#,
+                r#"ingested_records_total{source="calendar"} 5"#,
+                "jobs_running 0",
+            ]
+            .iter()
+            .all(|line| body.contains(line)),
+            "Error: unexpected metrics {body}"
+        );
+        Ok(())
+    }
+}
@@ -1,53 +1,194 @@
 // external crates
-use serde::Deserialize;
-use std::fmt;
+use tracing::instrument;
+use uuid::Uuid;
 // local modules
-use api_shared::prelude::LibError;
+use crate::repository::{now, User, UserRepository};
+use api_shared::{
+    dto::{LocaleForm, UserForm, UserProfile},
+    i18n::{self, Catalog},
+    prelude::{FieldError, LibError},
+    validation::Validator,
+};
 
-#[derive(Debug, Deserialize)]
-pub struct UserForm {
-    pub email: String,
-    pub password: String,
-    pub username: String,
+mod health;
+pub use health::*;
+
+mod jobs;
+pub use jobs::*;
+
+mod mailer;
+pub use mailer::*;
+
+mod metrics;
+pub use metrics::*;
+
+mod notifier;
+pub use notifier::*;
+
+mod oidc;
+pub use oidc::*;
+
+pub(crate) mod password;
+pub use password::*;
+
+mod password_reset;
+pub use password_reset::*;
+
+mod reminders;
+pub use reminders::*;
+
+mod sessions;
+pub use sessions::*;
+
+mod tasks;
+pub use tasks::*;
+
+mod throttle;
+pub use throttle::*;
+
+mod tokens;
+pub use tokens::*;
+
+mod two_factor;
+pub use two_factor::*;
+
+mod verification;
+pub use verification::*;
+
+/// Registers the account. Every field is validated first and all the
+/// failures are returned together as `LibError::Validation`.
+///
+/// Email and username uniqueness is enforced by the store, emails are
+/// compared case-insensitively. Only the Argon2id hash of the password
+/// is kept. The email starts unverified, see
+/// `send_verification_service`.
+#[instrument(skip_all)]
+pub async fn create_user_service(
+    form: UserForm,
+    users: &dyn UserRepository,
+    passwords: &PasswordHashing,
+) -> Result<User, LibError> {
+    let email = form.email.trim().to_lowercase();
+    let email_local = email
+        .split('@')
+        .next()
+        .unwrap_or_default();
+    Validator::new()
+        .email("email", &email)
+        .username("username", &form.username)
+        .password("password", &form.password, &[&form.username, email_local])
+        .finish()?;
+
+    let user = User {
+        id: Uuid::new_v4(),
+        email,
+        username: form.username,
+        password_hash: passwords.hash(form.password).await?,
+        created_at: now(),
+        email_verified_at: None,
+        locale: None,
+    };
+    users.insert(&user).await?;
+
+    Ok(user)
 }
 
-// TODO: replace mock data with PostgreSQL
-pub fn mock_db_user() -> UserForm {
-    UserForm {
-        email: "user@email.com".to_string(),
-        password: "valid_password".to_string(),
-        username: "username".to_string(),
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            username: user.username,
+            created_at: user.created_at,
+            email_verified: user.email_verified_at.is_some(),
+            locale: user.locale,
+        }
     }
 }
 
-impl UserForm {
-    pub fn create_user_service(self) -> Result<Self, LibError> {
-        let is_email_taken = self.email.eq(&mock_db_user().email);
-        if is_email_taken {
-            return Err(LibError::EmailTaken);
-        }
-        let is_username_taken = self
-            .username
-            .eq(&mock_db_user().username);
-        if is_username_taken {
-            return Err(LibError::UserTaken);
-        }
-        let is_password_invalid = self.password.len() < 8;
-        if is_password_invalid {
-            return Err(LibError::PasswordInvalid);
-        }
+/// Catalog of the language `user` chose, else the default one.
+pub fn user_catalog(user: &User) -> &'static Catalog {
+    user.locale
+        .as_deref()
+        .and_then(i18n::find)
+        .unwrap_or_else(i18n::default_catalog)
+}
 
-        Ok(Self {
-            email: self.email,
-            password: self.password,
-            username: self.username,
-        })
-    }
+/// Loads the profile of a signed-in user. A token pointing to a deleted
+/// account is treated as invalid.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn get_user_service(
+    users: &dyn UserRepository,
+    user_id: Uuid,
+) -> Result<UserProfile, LibError> {
+    users
+        .find_by_id(user_id)
+        .await?
+        .map(UserProfile::from)
+        .ok_or(LibError::InvalidToken)
 }
 
-impl fmt::Display for UserForm {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Display::fmt(&self, f)
+/// Saves the language of the user's messages. Tags are matched like
+/// `i18n::find` does and stored as the tag of the matching catalog.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn update_locale_service(
+    form: LocaleForm,
+    users: &dyn UserRepository,
+    user_id: Uuid,
+) -> Result<UserProfile, LibError> {
+    let locale = match form.locale.as_deref() {
+        Some(tag) => Some(
+            i18n::find(tag)
+                .ok_or_else(|| LibError::Validation {
+                    fields: vec![FieldError::new(
+                        "locale",
+                        "locale.unsupported",
+                        &[],
+                    )],
+                })?
+                .locale(),
+        ),
+        None => None,
+    };
+    users
+        .update_locale(user_id, locale)
+        .await?;
+
+    get_user_service(users, user_id).await
+}
+
+/// Checks an email/password pair, returning `None` when either is wrong.
+///
+/// When the stored hash was made with outdated cost parameters it is
+/// transparently replaced by one using the current parameters.
+#[instrument(skip_all)]
+pub async fn verify_credentials_service(
+    users: &dyn UserRepository,
+    passwords: &PasswordHashing,
+    email: &str,
+    password: String,
+) -> Result<Option<User>, LibError> {
+    let email = email.trim().to_lowercase();
+    let Some(mut user) = users.find_by_email(&email).await? else {
+        passwords.verify_dummy(password).await;
+        return Ok(None);
+    };
+
+    let verification = passwords
+        .verify(password, user.password_hash.clone())
+        .await?;
+    match verification {
+        Verification::Invalid => Ok(None),
+        Verification::Valid { rehash: None } => Ok(Some(user)),
+        Verification::Valid {
+            rehash: Some(new_hash),
+        } => {
+            users
+                .update_password_hash(user.id, &new_hash)
+                .await?;
+            user.password_hash = new_hash;
+            Ok(Some(user))
+        }
     }
 }
 
@@ -55,7 +196,9 @@ impl fmt::Display for UserForm {
 
 #[cfg(test)]
 mod tests {
-    use crate::services::{mock_db_user, UserForm};
+    use crate::repository::InMemoryUserRepository;
+    use crate::services::{create_user_service, password::tests::mock_hashing};
+    use api_shared::dto::UserForm;
 
     fn mock_existing_user() -> UserForm {
         UserForm {
@@ -72,70 +215,287 @@ mod tests {
         }
     }
 
+    async fn mock_db() -> InMemoryUserRepository {
+        let users = InMemoryUserRepository::default();
+        create_user_service(mock_existing_user(), &users, &mock_hashing())
+            .await
+            .unwrap();
+        users
+    }
+
     mod test_create_user_service {
-        use super::{mock_db_user, mock_existing_user};
-        use crate::services::{tests::mock_new_user, UserForm};
+        use super::{mock_db, mock_existing_user, mock_hashing, mock_new_user};
+        use crate::{
+            repository::UserRepository, services::create_user_service,
+        };
+        use api_shared::{dto::UserForm, prelude::LibError};
 
-        #[test]
-        fn test_email_taken() -> miette::Result<()> {
-            let is_email_taken = mock_existing_user()
-                .email
-                .eq(&mock_db_user().email);
+        #[tokio::test]
+        async fn test_email_taken() -> miette::Result<()> {
+            let users = mock_db().await;
+            let same_email = UserForm {
+                username: "other_username".to_string(),
+                ..mock_existing_user()
+            };
+            let result =
+                create_user_service(same_email, &users, &mock_hashing()).await;
 
-            miette::ensure!(is_email_taken, "Error: email already used");
+            miette::ensure!(
+                matches!(result, Err(LibError::EmailTaken)),
+                "Error: email already used"
+            );
             Ok(())
         }
 
-        #[test]
-        fn test_email_available() -> miette::Result<()> {
-            let is_email_available = mock_new_user()
-                .email
-                .ne(&mock_db_user().email);
+        #[tokio::test]
+        async fn test_email_taken_ignores_case() -> miette::Result<()> {
+            let users = mock_db().await;
+            let same_email = UserForm {
+                email: " USER@Email.com".to_string(),
+                username: "other_username".to_string(),
+                ..mock_existing_user()
+            };
+            let result =
+                create_user_service(same_email, &users, &mock_hashing()).await;
 
-            miette::ensure!(is_email_available, "Success!");
+            miette::ensure!(
+                matches!(result, Err(LibError::EmailTaken)),
+                "Error: email already used"
+            );
             Ok(())
         }
 
-        #[test]
-        fn test_username_taken() -> miette::Result<()> {
-            let is_username_taken = mock_existing_user()
-                .username
-                .eq(&mock_db_user().username);
+        #[tokio::test]
+        async fn test_email_available() -> miette::Result<()> {
+            let users = mock_db().await;
+            let new_user = UserForm {
+                password: "valid_password".to_string(),
+                ..mock_new_user()
+            };
+            let user =
+                create_user_service(new_user, &users, &mock_hashing()).await?;
+            let stored = users.find_by_email(&user.email).await?;
 
-            miette::ensure!(is_username_taken, "Error: username already used");
+            miette::ensure!(stored == Some(user), "Success!");
             Ok(())
         }
 
-        #[test]
-        fn test_username_available() -> miette::Result<()> {
-            let is_username_taken = mock_new_user()
-                .username
-                .ne(&mock_db_user().username);
+        #[tokio::test]
+        async fn test_username_taken() -> miette::Result<()> {
+            let users = mock_db().await;
+            let same_username = UserForm {
+                email: "other@email.com".to_string(),
+                ..mock_existing_user()
+            };
+            let result =
+                create_user_service(same_username, &users, &mock_hashing())
+                    .await;
+
+            miette::ensure!(
+                matches!(result, Err(LibError::UserTaken)),
+                "Error: username already used"
+            );
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_username_available() -> miette::Result<()> {
+            let users = mock_db().await;
+            let new_user = UserForm {
+                password: "valid_password".to_string(),
+                ..mock_new_user()
+            };
+            let result =
+                create_user_service(new_user, &users, &mock_hashing()).await;
 
-            miette::ensure!(is_username_taken, "Success!");
+            miette::ensure!(result.is_ok(), "Success!");
             Ok(())
         }
 
-        #[test]
-        fn test_password_invalid() -> miette::Result<()> {
-            let is_password_invalid = mock_new_user().password.len() < 8;
+        #[tokio::test]
+        async fn test_password_invalid() -> miette::Result<()> {
+            let users = mock_db().await;
+            let result =
+                create_user_service(mock_new_user(), &users, &mock_hashing())
+                    .await;
 
             miette::ensure!(
-                is_password_invalid,
+                matches!(
+                    result,
+                    Err(LibError::Validation { fields })
+                        if fields.len() == 1 && fields[0].field == "password"
+                ),
                 "Error: password must be at least 8 characters long"
             );
             Ok(())
         }
 
-        #[test]
-        fn test_password_valid() -> miette::Result<()> {
+        #[tokio::test]
+        async fn test_reports_every_invalid_field() -> miette::Result<()> {
+            let users = mock_db().await;
+            let invalid = UserForm {
+                email: "not an email".to_string(),
+                password: "password".to_string(),
+                username: "admin".to_string(),
+            };
+            let result =
+                create_user_service(invalid, &users, &mock_hashing()).await;
+            let Err(LibError::Validation { fields }) = result else {
+                miette::bail!("Error: invalid form accepted");
+            };
+            let names: Vec<&str> = fields
+                .iter()
+                .map(|f| f.field.as_str())
+                .collect();
+
+            miette::ensure!(
+                names == ["email", "username", "password"],
+                "Error: unexpected fields {names:?}"
+            );
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_password_valid() -> miette::Result<()> {
+            let users = mock_db().await;
             let valid_password = UserForm {
                 password: "valid_password".to_string(),
                 ..mock_new_user()
             };
-            let is_password_valid = valid_password.password.len() >= 8;
+            let result =
+                create_user_service(valid_password, &users, &mock_hashing())
+                    .await;
+
+            miette::ensure!(result.is_ok(), "Success!");
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_password_not_stored_in_plaintext() -> miette::Result<()> {
+            let users = mock_db().await;
+            let user = users
+                .find_by_email("user@email.com")
+                .await?
+                .unwrap();
+
+            miette::ensure!(
+                user.password_hash
+                    .starts_with("$argon2id$")
+                    && !user
+                        .password_hash
+                        .contains("valid_password"),
+                "Error: password stored in plaintext"
+            );
+            Ok(())
+        }
+    }
+
+    mod test_update_locale_service {
+        use super::mock_db;
+        use crate::{
+            repository::UserRepository, services::update_locale_service,
+        };
+        use api_shared::{dto::LocaleForm, prelude::LibError};
 
-            miette::ensure!(is_password_valid, "Success!");
+        #[tokio::test]
+        async fn test_saves_catalog_tag() -> miette::Result<()> {
+            let users = mock_db().await;
+            let user_id = users
+                .find_by_email("user@email.com")
+                .await?
+                .unwrap()
+                .id;
+
+            let form = LocaleForm {
+                locale: Some("en_US".to_string()),
+            };
+            let profile = update_locale_service(form, &users, user_id).await?;
+            miette::ensure!(
+                profile.locale.as_deref() == Some("en"),
+                "Error: unexpected locale {:?}",
+                profile.locale
+            );
+
+            let form = LocaleForm {
+                locale: Some("tlh".to_string()),
+            };
+            let result = update_locale_service(form, &users, user_id).await;
+            miette::ensure!(
+                matches!(result, Err(LibError::Validation { .. })),
+                "Error: unavailable locale saved"
+            );
+            Ok(())
+        }
+    }
+
+    mod test_verify_credentials_service {
+        use super::{mock_db, mock_hashing};
+        use crate::services::{
+            verify_credentials_service, HashingParams, PasswordHashing,
+        };
+
+        #[tokio::test]
+        async fn test_valid_credentials() -> miette::Result<()> {
+            let users = mock_db().await;
+            let user = verify_credentials_service(
+                &users,
+                &mock_hashing(),
+                "User@Email.com",
+                "valid_password".to_string(),
+            )
+            .await?;
+
+            miette::ensure!(user.is_some(), "Error: valid credentials refused");
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_invalid_credentials() -> miette::Result<()> {
+            let users = mock_db().await;
+            let wrong_password = verify_credentials_service(
+                &users,
+                &mock_hashing(),
+                "user@email.com",
+                "wrong_password".to_string(),
+            )
+            .await?;
+            let unknown_email = verify_credentials_service(
+                &users,
+                &mock_hashing(),
+                "unknown@email.com",
+                "valid_password".to_string(),
+            )
+            .await?;
+
+            miette::ensure!(
+                wrong_password.is_none() && unknown_email.is_none(),
+                "Error: invalid credentials accepted"
+            );
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_rehash_on_login() -> miette::Result<()> {
+            let users = mock_db().await;
+            let stronger = PasswordHashing::new(HashingParams {
+                memory_kib: 512,
+                iterations: 2,
+                parallelism: 1,
+            })?;
+            let user = verify_credentials_service(
+                &users,
+                &stronger,
+                "user@email.com",
+                "valid_password".to_string(),
+            )
+            .await?
+            .unwrap();
+
+            miette::ensure!(
+                user.password_hash
+                    .contains("m=512,t=2,p=1"),
+                "Error: stored hash kept the old cost"
+            );
             Ok(())
         }
     }
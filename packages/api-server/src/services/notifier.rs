@@ -0,0 +1,134 @@
+// external crates
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+// local modules
+use super::{Email, Mailer};
+use crate::repository::UserRepository;
+use api_shared::prelude::LibError;
+
+/// A message for a user, however it reaches them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub user_id: Uuid,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers notifications, such as task reminders.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, notification: Notification) -> Result<(), LibError>;
+}
+
+// SECTION: IN-MEMORY...........................................................
+
+/// Keeps notifications in memory, used by tests.
+#[derive(Debug, Default)]
+pub struct InMemoryNotifier {
+    sent: Mutex<Vec<Notification>>,
+}
+
+impl InMemoryNotifier {
+    /// Notifications sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Notification> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for InMemoryNotifier {
+    async fn notify(&self, notification: Notification) -> Result<(), LibError> {
+        self.sent
+            .lock()
+            .unwrap()
+            .push(notification);
+
+        Ok(())
+    }
+}
+
+// SECTION: MAIL................................................................
+
+/// Emails the notification to the user's address.
+#[derive(Clone)]
+pub struct MailNotifier {
+    users: Arc<dyn UserRepository>,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl MailNotifier {
+    pub fn new(
+        users: Arc<dyn UserRepository>,
+        mailer: Arc<dyn Mailer>,
+    ) -> Self {
+        Self { users, mailer }
+    }
+}
+
+#[async_trait]
+impl Notifier for MailNotifier {
+    /// Drops the notification of a deleted account, nobody left to tell.
+    async fn notify(&self, notification: Notification) -> Result<(), LibError> {
+        let Some(user) = self
+            .users
+            .find_by_id(notification.user_id)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        self.mailer
+            .send(Email {
+                to: user.email,
+                subject: notification.subject,
+                body: notification.body,
+            })
+            .await
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{now, InMemoryUserRepository, User},
+        services::InMemoryMailer,
+    };
+
+    #[tokio::test]
+    async fn test_mail_notifier() -> miette::Result<()> {
+        let users = Arc::new(InMemoryUserRepository::default());
+        let mailer = Arc::new(InMemoryMailer::default());
+        let user = User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: String::new(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: None,
+        };
+        users.insert(&user).await?;
+        let notifier = MailNotifier::new(users, mailer.clone());
+
+        for user_id in [user.id, Uuid::new_v4()] {
+            notifier
+                .notify(Notification {
+                    user_id,
+                    subject: "Hello".to_string(),
+                    body: "World".to_string(),
+                })
+                .await?;
+        }
+
+        let sent = mailer.sent();
+        miette::ensure!(
+            sent.len() == 1 && sent[0].to == "user@email.com",
+            "Error: unexpected emails {sent:?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,951 @@
+// external crates
+use chrono::Duration;
+use jsonwebtoken::{
+    decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation,
+};
+use rand::{rngs::OsRng, Rng};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+use tracing::instrument;
+use url::Url;
+use uuid::Uuid;
+// local modules
+use super::{hash_token, random_token, PasswordHashing};
+use crate::{
+    config::OidcProviderConfig,
+    repository::{
+        now, OidcIdentity, OidcLogin, OidcRepository, User, UserRepository,
+    },
+};
+use api_shared::{
+    dto::OidcCallback,
+    prelude::LibError,
+    validation::{Validator, USERNAME_MAX_LEN, USERNAME_MIN_LEN},
+};
+
+/// Time the user has to sign in at the provider and come back.
+pub const OIDC_LOGIN_TTL_MINUTES: i64 = 10;
+/// Asymmetric algorithms accepted on ID tokens. HMAC ones are refused so the
+/// client secret can never double as a signing key.
+const ID_TOKEN_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// The part of a provider's discovery document the sign-in flow uses.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Clone)]
+struct Discovered {
+    metadata: ProviderMetadata,
+    keys: JwkSet,
+}
+
+/// Claims of a verified ID token that account linking relies on.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub nonce: Option<String>,
+    pub preferred_username: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The configured providers. Discovery documents and signing keys are
+/// fetched on first use and cached, so the server starts even while a
+/// provider is down; keys are fetched again when a token names an unknown
+/// one, which is how providers rotate them.
+#[derive(Clone, Default)]
+pub struct OidcProviders {
+    http: reqwest::Client,
+    configs: Arc<HashMap<String, OidcProviderConfig>>,
+    discovered: Arc<Mutex<HashMap<String, Discovered>>>,
+    /// public URL of this server, the base of the redirect URIs
+    api_url: String,
+}
+
+impl OidcProviders {
+    pub fn new(
+        configs: BTreeMap<String, OidcProviderConfig>,
+        api_url: &str,
+    ) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            http,
+            configs: Arc::new(configs.into_iter().collect()),
+            discovered: Arc::default(),
+            api_url: api_url
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    /// Names of the configured providers, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.configs.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Where the provider sends the user back to. It must be registered
+    /// with the provider as is.
+    pub fn redirect_uri(&self, provider: &str) -> String {
+        format!("{}/auth/oidc/{provider}/callback", self.api_url)
+    }
+
+    fn config(&self, provider: &str) -> Result<&OidcProviderConfig, LibError> {
+        self.configs
+            .get(provider)
+            .ok_or(LibError::NotFound)
+    }
+
+    async fn discover(&self, provider: &str) -> Result<Discovered, LibError> {
+        if let Some(discovered) = self
+            .discovered
+            .lock()
+            .unwrap()
+            .get(provider)
+        {
+            return Ok(discovered.clone());
+        }
+
+        let config = self.config(provider)?;
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = self.get_json(&url).await?;
+        // a document claiming another issuer could mint tokens for it
+        if metadata.issuer != config.issuer {
+            tracing::warn!(
+                expected = %config.issuer,
+                found = %metadata.issuer,
+                "discovery document of another issuer"
+            );
+            return Err(LibError::IdentityProviderError);
+        }
+        let keys = self
+            .get_json(&metadata.jwks_uri)
+            .await?;
+        let discovered = Discovered { metadata, keys };
+        self.discovered
+            .lock()
+            .unwrap()
+            .insert(provider.to_string(), discovered.clone());
+
+        Ok(discovered)
+    }
+
+    async fn refresh_keys(&self, provider: &str) -> Result<JwkSet, LibError> {
+        let mut discovered = self.discover(provider).await?;
+        discovered.keys = self
+            .get_json(&discovered.metadata.jwks_uri)
+            .await?;
+        self.discovered
+            .lock()
+            .unwrap()
+            .insert(provider.to_string(), discovered.clone());
+
+        Ok(discovered.keys)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<T, LibError> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)
+    }
+
+    /// Trades the authorization code for the provider's tokens, proving
+    /// with `code_verifier` that this server started the flow.
+    async fn exchange_code(
+        &self,
+        provider: &str,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<String, LibError> {
+        let config = self.config(provider)?;
+        let discovered = self.discover(provider).await?;
+        let redirect_uri = self.redirect_uri(provider);
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &redirect_uri),
+            ("client_id", &config.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &config.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response: TokenResponse = self
+            .http
+            .post(&discovered.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+        Ok(response.id_token)
+    }
+
+    /// Checks the signature, issuer, audience, expiry and nonce of an ID
+    /// token.
+    async fn verify_id_token(
+        &self,
+        provider: &str,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, LibError> {
+        let config = self.config(provider)?;
+        let discovered = self.discover(provider).await?;
+        let header =
+            decode_header(id_token).map_err(|_| LibError::InvalidToken)?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(LibError::InvalidToken);
+        }
+
+        let find_key = |keys: &JwkSet| match &header.kid {
+            Some(kid) => keys.find(kid).cloned(),
+            None => keys.keys.first().cloned(),
+        };
+        let jwk = match find_key(&discovered.keys) {
+            Some(jwk) => jwk,
+            None => find_key(&self.refresh_keys(provider).await?)
+                .ok_or(LibError::InvalidToken)?,
+        };
+        let key =
+            DecodingKey::from_jwk(&jwk).map_err(|_| LibError::InvalidToken)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&discovered.metadata.issuer]);
+        validation.set_audience(&[&config.client_id]);
+        validation.leeway = 60;
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|_| LibError::InvalidToken)?
+            .claims;
+        // binds the token to the login that asked for it, against replays
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(LibError::InvalidToken);
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Logs why a request to a provider failed, as clients only learn that
+/// it did.
+fn provider_error(err: reqwest::Error) -> LibError {
+    tracing::warn!(error = %err, "identity provider request failed");
+    LibError::IdentityProviderError
+}
+
+/// A sign-in started at a provider.
+#[derive(Debug, Clone)]
+pub struct OidcLoginStart {
+    /// where to send the browser to
+    pub url: Url,
+    /// what the provider sends back to the callback, for the browser to
+    /// keep until then
+    pub state: String,
+}
+
+/// Starts a sign-in at `provider`. The flow is the authorization code flow
+/// with PKCE (S256).
+#[instrument(skip_all, fields(provider))]
+pub async fn start_oidc_login_service(
+    oidc: &dyn OidcRepository,
+    providers: &OidcProviders,
+    provider: &str,
+) -> Result<OidcLoginStart, LibError> {
+    let config = providers.config(provider)?;
+    let discovered = providers.discover(provider).await?;
+    let state = random_token();
+    let nonce = random_token();
+    let code_verifier = random_token();
+    let now = now();
+    oidc.insert_login(&OidcLogin {
+        state_hash: hash_token(&state),
+        provider: provider.to_string(),
+        code_verifier: code_verifier.clone(),
+        nonce: nonce.clone(),
+        created_at: now,
+        expires_at: now + Duration::minutes(OIDC_LOGIN_TTL_MINUTES),
+    })
+    .await?;
+
+    let mut url = Url::parse(
+        &discovered
+            .metadata
+            .authorization_endpoint,
+    )
+    .map_err(|err| {
+        tracing::warn!(error = %err, "invalid authorization endpoint");
+        LibError::IdentityProviderError
+    })?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &providers.redirect_uri(provider))
+        .append_pair("scope", &config.scopes.join(" "))
+        .append_pair("state", &state)
+        .append_pair("nonce", &nonce)
+        // S256 is base64url(SHA-256(verifier)), which is what
+        // `hash_token` computes
+        .append_pair("code_challenge", &hash_token(&code_verifier))
+        .append_pair("code_challenge_method", "S256");
+
+    Ok(OidcLoginStart { url, state })
+}
+
+/// Checks the provider's answer against the pending login and returns
+/// the claims of its verified ID token. Each `state` works once.
+///
+/// `started_state` is the `state` kept by the browser that started the
+/// login. Without this check, someone could start a login with their own
+/// provider account and get another person to open its callback, signing
+/// that person in to their account.
+#[instrument(skip_all, fields(provider))]
+pub async fn authenticate_oidc_service(
+    callback: OidcCallback,
+    started_state: Option<&str>,
+    oidc: &dyn OidcRepository,
+    providers: &OidcProviders,
+    provider: &str,
+) -> Result<IdTokenClaims, LibError> {
+    if started_state != Some(callback.state.as_str()) {
+        return Err(LibError::InvalidToken);
+    }
+    let login = oidc
+        .take_login(&hash_token(&callback.state), now())
+        .await?
+        .filter(|login| login.provider == provider)
+        .ok_or(LibError::InvalidToken)?;
+    if let Some(error) = callback.error {
+        tracing::warn!(%error, "sign-in refused by the provider");
+        return Err(LibError::IdentityProviderError);
+    }
+    let code = callback
+        .code
+        .ok_or(LibError::MissingCredentials)?;
+
+    let id_token = providers
+        .exchange_code(provider, &code, &login.code_verifier)
+        .await?;
+    providers
+        .verify_id_token(provider, &id_token, &login.nonce)
+        .await
+}
+
+/// Finds the local account of a provider account, linking or creating it
+/// on first sign-in.
+///
+/// Accounts are matched by email only when the provider vouches for it
+/// (`email_verified`). A matching account whose email was never verified
+/// here may have been registered by someone else to squat it, so its
+/// password is replaced by a random one before linking; the owner can
+/// reset it by mail.
+#[instrument(skip_all, fields(provider))]
+pub async fn link_oidc_account_service(
+    users: &dyn UserRepository,
+    oidc: &dyn OidcRepository,
+    passwords: &PasswordHashing,
+    provider: &str,
+    claims: &IdTokenClaims,
+) -> Result<User, LibError> {
+    if let Some(identity) = oidc
+        .find_identity(provider, &claims.sub)
+        .await?
+    {
+        return users
+            .find_by_id(identity.user_id)
+            .await?
+            .ok_or(LibError::InvalidToken);
+    }
+
+    let email = match &claims.email {
+        Some(email) if claims.email_verified => email.trim().to_lowercase(),
+        _ => return Err(LibError::EmailNotVerified),
+    };
+    let user = match users.find_by_email(&email).await? {
+        Some(user) => {
+            if user.email_verified_at.is_none() {
+                let unusable = passwords.hash(random_token()).await?;
+                users
+                    .update_password_hash(user.id, &unusable)
+                    .await?;
+                users
+                    .mark_email_verified(user.id, now())
+                    .await?;
+            }
+            user
+        }
+        None => create_oidc_user(users, passwords, &email, claims).await?,
+    };
+    oidc.insert_identity(&OidcIdentity {
+        provider: provider.to_string(),
+        subject: claims.sub.clone(),
+        user_id: user.id,
+        created_at: now(),
+    })
+    .await?;
+
+    Ok(user)
+}
+
+/// Registers the account of a first-time provider sign-in. It gets a
+/// random password, which the owner can replace through a password reset.
+async fn create_oidc_user(
+    users: &dyn UserRepository,
+    passwords: &PasswordHashing,
+    email: &str,
+    claims: &IdTokenClaims,
+) -> Result<User, LibError> {
+    let wanted = claims
+        .preferred_username
+        .as_deref()
+        .unwrap_or_else(|| {
+            email
+                .split('@')
+                .next()
+                .unwrap_or_default()
+        });
+    let base = username_from(wanted);
+    let password_hash = passwords.hash(random_token()).await?;
+
+    for attempt in 0..5 {
+        let username = match attempt {
+            0 => base.clone(),
+            _ => format!("{base}{}", OsRng.gen_range(1000..10000)),
+        };
+        if Validator::new()
+            .username("username", &username)
+            .finish()
+            .is_err()
+        {
+            continue;
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            username,
+            password_hash: password_hash.clone(),
+            created_at: now(),
+            email_verified_at: Some(now()),
+            locale: None,
+        };
+        match users.insert(&user).await {
+            Ok(()) => return Ok(user),
+            Err(LibError::UserTaken) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(LibError::UserTaken)
+}
+
+/// Keeps the characters usernames allow and fits the length bounds.
+fn username_from(wanted: &str) -> String {
+    let username: String = wanted
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .skip_while(|c| !c.is_ascii_alphanumeric())
+        .take(USERNAME_MAX_LEN - 4)
+        .collect();
+
+    if username.len() < USERNAME_MIN_LEN {
+        format!("user{username}")
+    } else {
+        username
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{InMemoryOidcRepository, InMemoryUserRepository},
+        services::{create_user_service, password::tests::mock_hashing},
+    };
+    use api_shared::dto::UserForm;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// Signing key of the mock provider. Test only, never used elsewhere.
+    const IDP_KEY: &str = include_str!("test_idp_key.pem");
+    const IDP_KEY_N: &str = "74fvc6VeIupF3X-QtNcWJED-GO1J8YeriWqVdnQsa2BxO_Uo4DLR0obWRiNVwK5-oHJ0kPzUGCLiWkEFfuLDOOfTqEbU1YILBBiKnTwE9JVNEOwXT9zwzWNqnXtspsRQePcxAH3nznfhTlyt40TEGOQ67p1fKxm-XllldPrw3c8uFV_mn01lvjaR1AIBM7Dzg_AAdBII2MndRu9PY1EzUfdKxkCPRX2QfBDsrZdc_WxE2ymPB8Bh3PjcL6yZY82CCZdFwJz8VRy_pWxjPqBGs7qkneEdvbAv4Yd0Krn73DuCIeno46Rww_Ub0KIjOtpgbopiOrqfzk2UKbGcG_wDNQ";
+    const CLIENT_ID: &str = "sight-agent";
+
+    /// A local provider serving discovery and keys, plus the state of the
+    /// server side of the flow.
+    struct MockIdp {
+        server: MockServer,
+        providers: OidcProviders,
+        oidc: InMemoryOidcRepository,
+        users: InMemoryUserRepository,
+    }
+
+    async fn mock_idp() -> MockIdp {
+        let server = MockServer::start().await;
+        let issuer = server.uri();
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{issuer}/authorize"),
+                "token_endpoint": format!("{issuer}/token"),
+                "jwks_uri": format!("{issuer}/jwks"),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "keys": [{
+                    "kty": "RSA",
+                    "kid": "test-key",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "n": IDP_KEY_N,
+                    "e": "AQAB",
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let providers = OidcProviders::new(
+            BTreeMap::from([(
+                "company".to_string(),
+                OidcProviderConfig {
+                    issuer: issuer.clone(),
+                    client_id: CLIENT_ID.to_string(),
+                    client_secret: Some("client-secret".to_string()),
+                    scopes: vec!["openid".to_string(), "email".to_string()],
+                },
+            )]),
+            "http://127.0.0.1:3030",
+        );
+
+        MockIdp {
+            server,
+            providers,
+            oidc: InMemoryOidcRepository::default(),
+            users: InMemoryUserRepository::default(),
+        }
+    }
+
+    impl MockIdp {
+        /// Runs the whole flow: the provider signs the user in with
+        /// `claims`, plus `iss`, `aud`, `exp` and the nonce it was sent.
+        async fn sign_in(
+            &self,
+            claims: serde_json::Value,
+        ) -> Result<IdTokenClaims, LibError> {
+            let start = start_oidc_login_service(
+                &self.oidc,
+                &self.providers,
+                "company",
+            )
+            .await?;
+            let param = |name: &str| {
+                start
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap()
+            };
+
+            let mut claims = claims;
+            let defaults = json!({
+                "iss": self.server.uri(),
+                "aud": CLIENT_ID,
+                "exp": now().timestamp() + 300,
+                "iat": now().timestamp(),
+                "nonce": param("nonce"),
+            });
+            for (key, value) in defaults.as_object().unwrap() {
+                claims
+                    .as_object_mut()
+                    .unwrap()
+                    .entry(key)
+                    .or_insert(value.clone());
+            }
+            let mut header = Header::new(Algorithm::RS256);
+            header.kid = Some("test-key".to_string());
+            let id_token = encode(
+                &header,
+                &claims,
+                &EncodingKey::from_rsa_pem(IDP_KEY.as_bytes()).unwrap(),
+            )
+            .unwrap();
+
+            Mock::given(method("POST"))
+                .and(path("/token"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({ "id_token": id_token })),
+                )
+                .up_to_n_times(1)
+                .mount(&self.server)
+                .await;
+
+            let callback = OidcCallback {
+                state: param("state"),
+                code: Some("authorization-code".to_string()),
+                error: None,
+            };
+            let result = authenticate_oidc_service(
+                callback,
+                Some(&start.state),
+                &self.oidc,
+                &self.providers,
+                "company",
+            )
+            .await;
+
+            // the code was redeemed with the verifier of the challenge
+            let requests = self
+                .server
+                .received_requests()
+                .await
+                .unwrap();
+            let token_request = requests
+                .iter()
+                .rev()
+                .find(|request| request.url.path() == "/token")
+                .unwrap();
+            let form: HashMap<String, String> =
+                url::form_urlencoded::parse(&token_request.body)
+                    .into_owned()
+                    .collect();
+            assert_eq!(
+                hash_token(&form["code_verifier"]),
+                param("code_challenge")
+            );
+            assert_eq!(form["redirect_uri"], param("redirect_uri"));
+
+            result
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authorization_url() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        let start =
+            start_oidc_login_service(&idp.oidc, &idp.providers, "company")
+                .await?;
+        let url = start.url;
+        let params: HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        miette::ensure!(
+            url.as_str()
+                .starts_with(&format!("{}/authorize?", idp.server.uri()))
+                && params["response_type"] == "code"
+                && params["client_id"] == CLIENT_ID
+                && params["code_challenge_method"] == "S256"
+                && params["scope"] == "openid email"
+                && params["state"] == start.state
+                && params["redirect_uri"]
+                    == "http://127.0.0.1:3030/auth/oidc/company/callback",
+            "Error: unexpected authorization URL {url}"
+        );
+        let unknown =
+            start_oidc_login_service(&idp.oidc, &idp.providers, "other").await;
+        miette::ensure!(
+            matches!(unknown, Err(LibError::NotFound)),
+            "Error: unknown provider accepted"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_creates_and_links_account() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        let claims = idp
+            .sign_in(json!({
+                "sub": "248289761001",
+                "email": "Jane@Company.com",
+                "email_verified": true,
+                "preferred_username": "jane.doe",
+            }))
+            .await?;
+        let created = link_oidc_account_service(
+            &idp.users,
+            &idp.oidc,
+            &mock_hashing(),
+            "company",
+            &claims,
+        )
+        .await?;
+        miette::ensure!(
+            created.email == "jane@company.com"
+                && created.username == "jane.doe"
+                && created.email_verified_at.is_some(),
+            "Error: unexpected account {created:?}"
+        );
+
+        // the next sign-in finds the link, even if the email changed
+        let claims = idp
+            .sign_in(json!({
+                "sub": "248289761001",
+                "email": "jane.doe@company.com",
+                "email_verified": true,
+            }))
+            .await?;
+        let linked = link_oidc_account_service(
+            &idp.users,
+            &idp.oidc,
+            &mock_hashing(),
+            "company",
+            &claims,
+        )
+        .await?;
+        miette::ensure!(linked.id == created.id, "Error: account duplicated");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_links_existing_account_by_email() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        let existing = create_user_service(
+            UserForm {
+                email: "user@email.com".to_string(),
+                password: "valid_password".to_string(),
+                username: "username".to_string(),
+            },
+            &idp.users,
+            &mock_hashing(),
+        )
+        .await?;
+
+        let unverified = idp
+            .sign_in(json!({
+                "sub": "1",
+                "email": "user@email.com",
+                "email_verified": false,
+            }))
+            .await?;
+        let refused = link_oidc_account_service(
+            &idp.users,
+            &idp.oidc,
+            &mock_hashing(),
+            "company",
+            &unverified,
+        )
+        .await;
+        miette::ensure!(
+            matches!(refused, Err(LibError::EmailNotVerified)),
+            "Error: linked by an unverified email"
+        );
+
+        let verified = idp
+            .sign_in(json!({
+                "sub": "1",
+                "email": "user@email.com",
+                "email_verified": true,
+            }))
+            .await?;
+        let linked = link_oidc_account_service(
+            &idp.users,
+            &idp.oidc,
+            &mock_hashing(),
+            "company",
+            &verified,
+        )
+        .await?;
+        let stored = idp
+            .users
+            .find_by_id(existing.id)
+            .await?
+            .unwrap();
+        // the account was never verified here: its password may belong to
+        // a squatter, so it no longer works
+        miette::ensure!(
+            linked.id == existing.id
+                && stored.email_verified_at.is_some()
+                && stored.password_hash != existing.password_hash,
+            "Error: squatted account kept its password"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_forged_tokens() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        for claims in [
+            json!({ "sub": "1", "aud": "another-client" }),
+            json!({ "sub": "1", "iss": "https://evil.example" }),
+            json!({ "sub": "1", "nonce": "replayed" }),
+            json!({ "sub": "1", "exp": now().timestamp() - 3600 }),
+        ] {
+            let result = idp.sign_in(claims.clone()).await;
+            miette::ensure!(
+                matches!(result, Err(LibError::InvalidToken)),
+                "Error: forged token accepted: {claims}"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_is_single_use() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        let state =
+            start_oidc_login_service(&idp.oidc, &idp.providers, "company")
+                .await?
+                .state;
+        let callback = |state: &str| OidcCallback {
+            state: state.to_string(),
+            code: None,
+            error: Some("access_denied".to_string()),
+        };
+
+        let denied = authenticate_oidc_service(
+            callback(&state),
+            Some(&state),
+            &idp.oidc,
+            &idp.providers,
+            "company",
+        )
+        .await;
+        miette::ensure!(
+            matches!(denied, Err(LibError::IdentityProviderError)),
+            "Error: provider error ignored"
+        );
+        let replayed = authenticate_oidc_service(
+            callback(&state),
+            Some(&state),
+            &idp.oidc,
+            &idp.providers,
+            "company",
+        )
+        .await;
+        miette::ensure!(
+            matches!(replayed, Err(LibError::InvalidToken)),
+            "Error: state accepted twice"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_provider_failures() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        let provider = |issuer: String| {
+            OidcProviders::new(
+                BTreeMap::from([(
+                    "company".to_string(),
+                    OidcProviderConfig {
+                        issuer,
+                        client_id: CLIENT_ID.to_string(),
+                        client_secret: None,
+                        scopes: vec!["openid".to_string()],
+                    },
+                )]),
+                "http://127.0.0.1:3030",
+            )
+        };
+
+        // the document names the issuer without the trailing slash
+        let other_issuer = provider(format!("{}/", idp.server.uri()));
+        let unreachable = provider("http://127.0.0.1:9".to_string());
+        for providers in [other_issuer, unreachable] {
+            let result =
+                start_oidc_login_service(&idp.oidc, &providers, "company")
+                    .await;
+            miette::ensure!(
+                matches!(result, Err(LibError::IdentityProviderError)),
+                "Error: provider failure not reported {result:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_bound_to_browser() -> miette::Result<()> {
+        let idp = mock_idp().await;
+        let state =
+            start_oidc_login_service(&idp.oidc, &idp.providers, "company")
+                .await?
+                .state;
+        let callback = || OidcCallback {
+            state: state.clone(),
+            code: None,
+            error: Some("access_denied".to_string()),
+        };
+
+        for started_state in [None, Some("state-of-another-login")] {
+            let result = authenticate_oidc_service(
+                callback(),
+                started_state,
+                &idp.oidc,
+                &idp.providers,
+                "company",
+            )
+            .await;
+            miette::ensure!(
+                matches!(result, Err(LibError::InvalidToken)),
+                "Error: callback accepted from another browser"
+            );
+        }
+        // still pending for the browser that started it
+        let own = authenticate_oidc_service(
+            callback(),
+            Some(&state),
+            &idp.oidc,
+            &idp.providers,
+            "company",
+        )
+        .await;
+        miette::ensure!(
+            matches!(own, Err(LibError::IdentityProviderError)),
+            "Error: login spent by another browser"
+        );
+        Ok(())
+    }
+}
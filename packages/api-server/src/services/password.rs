@@ -0,0 +1,230 @@
+// external crates
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier,
+    Version,
+};
+// local modules
+use crate::config::PasswordsConfig;
+use api_shared::prelude::LibError;
+
+/// Argon2id cost parameters. The defaults follow the OWASP recommendation
+/// (19 MiB of memory, 2 iterations, 1 lane).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashingParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for HashingParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl From<&PasswordsConfig> for HashingParams {
+    fn from(config: &PasswordsConfig) -> Self {
+        Self {
+            memory_kib: config.memory_kib,
+            iterations: config.iterations,
+            parallelism: config.parallelism,
+        }
+    }
+}
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    Invalid,
+    /// The password matches. `rehash` holds a new hash when the stored one
+    /// was made with different cost parameters.
+    Valid {
+        rehash: Option<String>,
+    },
+}
+
+/// Hashes and verifies passwords with Argon2id and a random per-user salt.
+///
+/// Hashing is CPU bound, so the work runs on tokio's blocking pool.
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordHashing {
+    params: HashingParams,
+}
+
+impl PasswordHashing {
+    pub fn new(params: HashingParams) -> Result<Self, LibError> {
+        // reject unusable costs at startup instead of at the first sign up
+        argon2_with(params)?;
+
+        Ok(Self { params })
+    }
+
+    pub async fn hash(&self, password: String) -> Result<String, LibError> {
+        let params = self.params;
+        tokio::task::spawn_blocking(move || hash_with(params, &password))
+            .await
+            .map_err(LibError::internal)?
+    }
+
+    pub async fn verify(
+        &self,
+        password: String,
+        stored_hash: String,
+    ) -> Result<Verification, LibError> {
+        let params = self.params;
+        tokio::task::spawn_blocking(move || {
+            let parsed =
+                PasswordHash::new(&stored_hash).map_err(LibError::internal)?;
+            let is_valid = Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok();
+            if !is_valid {
+                return Ok(Verification::Invalid);
+            }
+
+            let rehash = if is_outdated(params, &parsed) {
+                Some(hash_with(params, &password)?)
+            } else {
+                None
+            };
+            Ok(Verification::Valid { rehash })
+        })
+        .await
+        .map_err(LibError::internal)?
+    }
+
+    /// Burns the same time as a real verification. Used when the account
+    /// does not exist so response times don't reveal registered emails.
+    pub async fn verify_dummy(&self, password: String) {
+        let params = self.params;
+        let _ =
+            tokio::task::spawn_blocking(move || hash_with(params, &password))
+                .await;
+    }
+}
+
+fn argon2_with(params: HashingParams) -> Result<Argon2<'static>, LibError> {
+    let params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        None,
+    )
+    .map_err(LibError::internal)?;
+
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+fn hash_with(
+    params: HashingParams,
+    password: &str,
+) -> Result<String, LibError> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2_with(params)?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(LibError::internal)?;
+
+    Ok(hash.to_string())
+}
+
+fn is_outdated(params: HashingParams, hash: &PasswordHash) -> bool {
+    let Ok(stored) = Params::try_from(hash) else {
+        return true;
+    };
+
+    hash.algorithm != Algorithm::Argon2id.ident()
+        || hash.version != Some(Version::V0x13.into())
+        || stored.m_cost() != params.memory_kib
+        || stored.t_cost() != params.iterations
+        || stored.p_cost() != params.parallelism
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests don't spend seconds hashing.
+    pub(crate) fn mock_hashing_params() -> HashingParams {
+        HashingParams {
+            memory_kib: 256,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    pub(crate) fn mock_hashing() -> PasswordHashing {
+        PasswordHashing::new(mock_hashing_params()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hash_and_verify() -> miette::Result<()> {
+        let hashing = mock_hashing();
+        let hash = hashing
+            .hash("valid_password".to_string())
+            .await?;
+
+        miette::ensure!(
+            !hash.contains("valid_password"),
+            "Error: plaintext inside the hash"
+        );
+        let verification = hashing
+            .verify("valid_password".to_string(), hash.clone())
+            .await?;
+        miette::ensure!(
+            verification == Verification::Valid { rehash: None },
+            "Error: valid password rejected"
+        );
+        let verification = hashing
+            .verify("wrong_password".to_string(), hash)
+            .await?;
+        miette::ensure!(
+            verification == Verification::Invalid,
+            "Error: wrong password accepted"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_salt_per_hash() -> miette::Result<()> {
+        let hashing = mock_hashing();
+        let first = hashing.hash("same".to_string()).await?;
+        let second = hashing.hash("same".to_string()).await?;
+
+        miette::ensure!(first != second, "Error: salt reused");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rehash_on_new_cost() -> miette::Result<()> {
+        let old_hash = mock_hashing()
+            .hash("valid_password".to_string())
+            .await?;
+        let stronger = PasswordHashing::new(HashingParams {
+            memory_kib: 512,
+            iterations: 2,
+            parallelism: 1,
+        })?;
+        let verification = stronger
+            .verify("valid_password".to_string(), old_hash)
+            .await?;
+
+        let Verification::Valid {
+            rehash: Some(new_hash),
+        } = verification
+        else {
+            miette::bail!("Error: outdated hash not rehashed");
+        };
+        miette::ensure!(
+            new_hash.contains("m=512,t=2,p=1"),
+            "Error: rehash ignored the new cost"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+// external crates
+use chrono::Duration;
+use std::collections::BTreeMap;
+use tracing::instrument;
+// local modules
+use super::{
+    hash_token, issue_user_token, user_catalog, Email, MailLinks, Mailer,
+    PasswordHashing,
+};
+use crate::repository::{
+    now, SessionRepository, TokenPurpose, UserRepository, UserTokenRepository,
+};
+use api_shared::{
+    dto::PasswordResetForm, prelude::LibError, validation::Validator,
+};
+
+/// How long a password reset link stays valid.
+pub const RESET_PASSWORD_TTL_MINUTES: i64 = 60;
+
+/// Mails a password reset link to `email`. Unknown emails are silently
+/// ignored, so callers can't probe which emails are registered.
+#[instrument(skip_all)]
+pub async fn request_password_reset_service(
+    users: &dyn UserRepository,
+    tokens: &dyn UserTokenRepository,
+    mailer: &dyn Mailer,
+    links: &MailLinks,
+    email: &str,
+) -> Result<(), LibError> {
+    let email = email.trim().to_lowercase();
+    let Some(user) = users.find_by_email(&email).await? else {
+        return Ok(());
+    };
+    let token = issue_user_token(
+        tokens,
+        user.id,
+        TokenPurpose::ResetPassword,
+        Duration::minutes(RESET_PASSWORD_TTL_MINUTES),
+    )
+    .await?;
+
+    let catalog = user_catalog(&user);
+    let args = BTreeMap::from([
+        ("username".to_string(), user.username.clone()),
+        ("link".to_string(), links.reset_password(&token)),
+        (
+            "minutes".to_string(),
+            RESET_PASSWORD_TTL_MINUTES.to_string(),
+        ),
+    ]);
+
+    mailer
+        .send(Email {
+            to: user.email,
+            subject: catalog.message("password_reset.subject", &args),
+            body: catalog.message("password_reset.body", &args),
+        })
+        .await
+}
+
+/// Replaces the password of the token's owner. The token works once.
+///
+/// Every session of the account is revoked, so whoever knew the old
+/// password is signed out. Opening the link also proves ownership of
+/// the email, which is marked as verified.
+#[instrument(skip_all)]
+pub async fn reset_password_service(
+    form: PasswordResetForm,
+    users: &dyn UserRepository,
+    tokens: &dyn UserTokenRepository,
+    sessions: &dyn SessionRepository,
+    passwords: &PasswordHashing,
+) -> Result<(), LibError> {
+    // checked first so a rejected password doesn't burn the token
+    Validator::new()
+        .password("password", &form.password, &[])
+        .finish()?;
+
+    let now = now();
+    let token = tokens
+        .consume(&hash_token(&form.token), TokenPurpose::ResetPassword, now)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+    let password_hash = passwords.hash(form.password).await?;
+    users
+        .update_password_hash(token.user_id, &password_hash)
+        .await?;
+    users
+        .mark_email_verified(token.user_id, now)
+        .await?;
+
+    for session in sessions
+        .list_active(token.user_id, now)
+        .await?
+    {
+        sessions.revoke(session.id, now).await?;
+    }
+
+    Ok(())
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{
+            InMemorySessionRepository, InMemoryUserRepository,
+            InMemoryUserTokenRepository, Session,
+        },
+        services::{
+            create_user_service, password::tests::mock_hashing,
+            verification::tests::mailed_token, verify_credentials_service,
+            InMemoryMailer,
+        },
+    };
+    use api_shared::dto::UserForm;
+    use uuid::Uuid;
+
+    struct MockDb {
+        users: InMemoryUserRepository,
+        tokens: InMemoryUserTokenRepository,
+        sessions: InMemorySessionRepository,
+        mailer: InMemoryMailer,
+    }
+
+    async fn mock_db() -> MockDb {
+        let db = MockDb {
+            users: InMemoryUserRepository::default(),
+            tokens: InMemoryUserTokenRepository::default(),
+            sessions: InMemorySessionRepository::default(),
+            mailer: InMemoryMailer::default(),
+        };
+        create_user_service(
+            UserForm {
+                email: "user@email.com".to_string(),
+                password: "valid_password".to_string(),
+                username: "username".to_string(),
+            },
+            &db.users,
+            &mock_hashing(),
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn mock_reset(db: &MockDb, token: &str) -> Result<(), LibError> {
+        reset_password_service(
+            PasswordResetForm {
+                token: token.to_string(),
+                password: "new_password".to_string(),
+            },
+            &db.users,
+            &db.tokens,
+            &db.sessions,
+            &mock_hashing(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_reset_password() -> miette::Result<()> {
+        let db = mock_db().await;
+        let user = db
+            .users
+            .find_by_email("user@email.com")
+            .await?
+            .unwrap();
+        let session = Session {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            created_at: now(),
+            expires_at: now() + Duration::days(1),
+            last_seen_at: now(),
+            user_agent: None,
+            revoked_at: None,
+        };
+        db.sessions.insert(&session).await?;
+        request_password_reset_service(
+            &db.users,
+            &db.tokens,
+            &db.mailer,
+            &MailLinks::default(),
+            "user@email.com",
+        )
+        .await?;
+        let token = mailed_token(&db.mailer);
+
+        mock_reset(&db, &token).await?;
+        let signed_in = verify_credentials_service(
+            &db.users,
+            &mock_hashing(),
+            "user@email.com",
+            "new_password".to_string(),
+        )
+        .await?;
+        miette::ensure!(
+            signed_in.is_some_and(|u| u.email_verified_at.is_some()),
+            "Error: new password refused"
+        );
+        let session = db
+            .sessions
+            .find_by_id(session.id)
+            .await?;
+        miette::ensure!(
+            session.is_some_and(|s| s.revoked_at.is_some()),
+            "Error: old session survived the reset"
+        );
+        miette::ensure!(
+            matches!(
+                mock_reset(&db, &token).await,
+                Err(LibError::InvalidToken)
+            ),
+            "Error: reset token used twice"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reset_unknown_email() -> miette::Result<()> {
+        let db = mock_db().await;
+        request_password_reset_service(
+            &db.users,
+            &db.tokens,
+            &db.mailer,
+            &MailLinks::default(),
+            "unknown@email.com",
+        )
+        .await?;
+
+        miette::ensure!(
+            db.mailer.sent().is_empty(),
+            "Error: mailed an unknown address"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reset_weak_password_keeps_token() -> miette::Result<()> {
+        let db = mock_db().await;
+        request_password_reset_service(
+            &db.users,
+            &db.tokens,
+            &db.mailer,
+            &MailLinks::default(),
+            "user@email.com",
+        )
+        .await?;
+        let token = mailed_token(&db.mailer);
+        let weak = reset_password_service(
+            PasswordResetForm {
+                token: token.clone(),
+                password: "short".to_string(),
+            },
+            &db.users,
+            &db.tokens,
+            &db.sessions,
+            &mock_hashing(),
+        )
+        .await;
+
+        miette::ensure!(
+            matches!(weak, Err(LibError::Validation { .. })),
+            "Error: weak password accepted"
+        );
+        mock_reset(&db, &token).await?;
+        Ok(())
+    }
+}
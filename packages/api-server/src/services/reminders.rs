@@ -0,0 +1,482 @@
+// external crates
+use chrono::{DateTime, Utc};
+use prometheus_client::metrics::gauge::Gauge;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::sync::Notify;
+use uuid::Uuid;
+// local modules
+use super::{user_catalog, Notification, Notifier, StopSignal};
+use crate::repository::{now, DueReminder, TaskRepository, UserRepository};
+use api_shared::{
+    i18n::{self, Catalog},
+    prelude::LibError,
+};
+
+/// How many due reminders are loaded at once.
+const BATCH_SIZE: usize = 100;
+/// Longest sleep between two looks at the queue, in case a wake-up was
+/// missed, e.g. a task saved by another instance.
+const MAX_SLEEP: Duration = Duration::from_secs(60 * 60);
+/// Sleep after the store failed, and first wait before sending a
+/// reminder again, doubled after each failure.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Failed sends after which a reminder is given up, about an hour
+/// after its time.
+const MAX_ATTEMPTS: u32 = 8;
+
+/// Sends the task reminders when their time comes, see `run`.
+///
+/// The queue lives in the store, so reminders survive restarts: those
+/// whose time passed while no scheduler ran are sent on start. A reminder
+/// is marked sent after its notification went out, so a crash in
+/// between sends it twice rather than never. Clones share the wake-ups.
+#[derive(Clone)]
+pub struct ReminderScheduler {
+    tasks: Arc<dyn TaskRepository>,
+    /// for the language of each reminder's owner
+    users: Arc<dyn UserRepository>,
+    notifier: Arc<dyn Notifier>,
+    /// reminders due but not sent yet
+    depth: Gauge,
+    wake: Arc<Notify>,
+}
+
+impl ReminderScheduler {
+    pub fn new(
+        tasks: Arc<dyn TaskRepository>,
+        users: Arc<dyn UserRepository>,
+        notifier: Arc<dyn Notifier>,
+        depth: Gauge,
+    ) -> Self {
+        Self {
+            tasks,
+            users,
+            notifier,
+            depth,
+            wake: Arc::default(),
+        }
+    }
+
+    /// Makes `run` look at the queue again, once a task was saved.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Sends every reminder due by `now`. Returns how many were sent.
+    ///
+    /// A reminder that fails to go out is postponed, later after each
+    /// failure, so it doesn't hold back the others. It's given up after
+    /// `MAX_ATTEMPTS`.
+    pub async fn send_due(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<usize, LibError> {
+        let mut sent = 0;
+        loop {
+            let due = self
+                .tasks
+                .due_reminders(now, BATCH_SIZE)
+                .await?;
+            self.depth.set(due.len() as i64);
+
+            for reminder in &due {
+                let catalog = self
+                    .catalog_of(reminder.user_id)
+                    .await?;
+                match self
+                    .notifier
+                    .notify(notification(reminder, catalog, now))
+                    .await
+                {
+                    Ok(()) => {
+                        self.tasks
+                            .mark_reminder_sent(reminder, now)
+                            .await?;
+                        sent += 1;
+                    }
+                    Err(error) => {
+                        self.postpone(reminder, now, error)
+                            .await?
+                    }
+                }
+                self.depth.dec();
+            }
+
+            if due.len() < BATCH_SIZE {
+                return Ok(sent);
+            }
+        }
+    }
+
+    /// Sends the reminders as they come due until `signal` stops it,
+    /// sleeping in between until the next one or a `wake`.
+    pub async fn run(self, mut signal: StopSignal) {
+        while !signal.is_stopped() {
+            let delay = match self.send_due(now()).await {
+                Ok(sent) => {
+                    if sent > 0 {
+                        tracing::info!(sent, "reminders sent");
+                    }
+                    self.until_next().await
+                }
+                Err(error) => {
+                    tracing::error!(%error, "reminders not sent");
+                    RETRY_DELAY
+                }
+            };
+
+            tokio::select! {
+                _ = signal.stopped() => {}
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+    }
+
+    /// Catalog of the language `user_id` chose, else the default one.
+    async fn catalog_of(
+        &self,
+        user_id: Uuid,
+    ) -> Result<&'static Catalog, LibError> {
+        let user = self.users.find_by_id(user_id).await?;
+
+        Ok(user
+            .as_ref()
+            .map_or_else(i18n::default_catalog, user_catalog))
+    }
+
+    /// Tries `reminder` again later, or gives it up, after its
+    /// notification failed with `error`.
+    async fn postpone(
+        &self,
+        reminder: &DueReminder,
+        now: DateTime<Utc>,
+        error: LibError,
+    ) -> Result<(), LibError> {
+        let attempts = reminder.attempts + 1;
+        if attempts >= MAX_ATTEMPTS {
+            tracing::error!(%error, task_id = %reminder.task_id, attempts, "reminder given up");
+            // recorded as sent so it leaves the queue
+            self.tasks
+                .mark_reminder_sent(reminder, now)
+                .await?;
+            return Ok(());
+        }
+
+        let delay = RETRY_DELAY
+            .saturating_mul(2u32.saturating_pow(reminder.attempts))
+            .min(MAX_SLEEP);
+        let retry_at = now
+            + chrono::Duration::from_std(delay).map_err(LibError::internal)?;
+        tracing::warn!(%error, task_id = %reminder.task_id, attempts, %retry_at, "reminder not sent");
+        self.tasks
+            .postpone_reminder(reminder, retry_at)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Time left until the next reminder, at most `MAX_SLEEP`.
+    async fn until_next(&self) -> Duration {
+        match self.tasks.next_reminder_at().await {
+            Ok(Some(remind_at)) => (remind_at - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO)
+                .min(MAX_SLEEP),
+            Ok(None) => MAX_SLEEP,
+            Err(error) => {
+                tracing::error!(%error, "next reminder not found");
+                RETRY_DELAY
+            }
+        }
+    }
+}
+
+/// Tells the owner of the task when it's due, or was, when the reminder
+/// is sent late, in the language of `catalog`. Users have no time zone
+/// yet, so the due time is given in UTC along with how long until or
+/// since then.
+fn notification(
+    reminder: &DueReminder,
+    catalog: &Catalog,
+    now: DateTime<Utc>,
+) -> Notification {
+    let date_format = catalog.message("reminder.date_format", &BTreeMap::new());
+    let (key, time) = match reminder.due_at > now {
+        true => ("reminder.upcoming", reminder.due_at - now),
+        false => ("reminder.overdue", now - reminder.due_at),
+    };
+    let args = BTreeMap::from([
+        ("title".to_string(), reminder.title.clone()),
+        ("time".to_string(), span(time)),
+        (
+            "due_at".to_string(),
+            reminder
+                .due_at
+                .format(&date_format)
+                .to_string(),
+        ),
+    ]);
+
+    Notification {
+        user_id: reminder.user_id,
+        subject: catalog.message("reminder.subject", &args),
+        body: catalog.message(key, &args),
+    }
+}
+
+/// `2 d 3 h`, `1 h 30 min` or `5 min`, to the nearest minute.
+fn span(duration: chrono::Duration) -> String {
+    let minutes = (duration.num_seconds() + 30) / 60;
+    let (days, hours, minutes) =
+        (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+
+    match (days, hours, minutes) {
+        (0, 0, _) => format!("{minutes} min"),
+        (0, _, 0) => format!("{hours} h"),
+        (0, _, _) => format!("{hours} h {minutes} min"),
+        (_, 0, _) => format!("{days} d"),
+        _ => format!("{days} d {hours} h"),
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{InMemoryTaskRepository, InMemoryUserRepository, User},
+        services::{BackgroundJobs, InMemoryNotifier},
+    };
+    use api_shared::dto::{Task, TaskStatus};
+    use async_trait::async_trait;
+
+    /// Fails the notifications of `user_id`, sends the others.
+    struct BrokenNotifier {
+        user_id: Uuid,
+        working: InMemoryNotifier,
+    }
+
+    #[async_trait]
+    impl Notifier for BrokenNotifier {
+        async fn notify(
+            &self,
+            notification: Notification,
+        ) -> Result<(), LibError> {
+            if notification.user_id == self.user_id {
+                return Err(LibError::internal(std::io::Error::other(
+                    "unreachable",
+                )));
+            }
+            self.working.notify(notification).await
+        }
+    }
+
+    /// Saves an open task of `user_id`, due two hours after `saved_at` and
+    /// reminded an hour before.
+    async fn mock_task(
+        tasks: &InMemoryTaskRepository,
+        user_id: Uuid,
+        saved_at: DateTime<Utc>,
+    ) {
+        let task = Task {
+            id: Uuid::new_v4(),
+            title: "Pay the rent".to_string(),
+            summary: String::new(),
+            content: String::new(),
+            tags: Vec::new(),
+            status: TaskStatus::Open,
+            created_at: saved_at,
+            updated_at: saved_at,
+            completed_at: None,
+            recurrence: None,
+            starts_at: None,
+            due_at: Some(saved_at + chrono::Duration::hours(2)),
+            reminders: vec![60],
+        };
+        tasks
+            .insert(user_id, &task)
+            .await
+            .unwrap();
+    }
+
+    /// A task of a new user, see `mock_task`.
+    async fn mock_tasks() -> (Arc<InMemoryTaskRepository>, DateTime<Utc>) {
+        let tasks = Arc::new(InMemoryTaskRepository::default());
+        let saved_at = now();
+        mock_task(&tasks, Uuid::new_v4(), saved_at).await;
+        (tasks, saved_at)
+    }
+
+    /// Scheduler of `tasks` whose owners chose no language.
+    fn mock_scheduler(
+        tasks: Arc<InMemoryTaskRepository>,
+        notifier: Arc<dyn Notifier>,
+    ) -> ReminderScheduler {
+        ReminderScheduler::new(
+            tasks,
+            Arc::new(InMemoryUserRepository::default()),
+            notifier,
+            Gauge::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sends_due_reminders_once() -> miette::Result<()> {
+        let (tasks, saved_at) = mock_tasks().await;
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let scheduler = mock_scheduler(tasks, notifier.clone());
+
+        let early = scheduler.send_due(saved_at).await?;
+        // e.g. a restart long after the reminder time, it's caught up
+        let late = saved_at + chrono::Duration::hours(3);
+        let sent = scheduler.send_due(late).await?;
+        let again = scheduler.send_due(late).await?;
+
+        let notifications = notifier.sent();
+        miette::ensure!(
+            early == 0
+                && sent == 1
+                && again == 0
+                && notifications.len() == 1
+                && notifications[0].subject == "Lembrete: Pay the rent"
+                && notifications[0]
+                    .body
+                    .contains("venceu há 1 h, em"),
+            "Error: unexpected notifications {notifications:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_owner_language() -> miette::Result<()> {
+        let tasks = Arc::new(InMemoryTaskRepository::default());
+        let users = Arc::new(InMemoryUserRepository::default());
+        let user = User {
+            id: Uuid::new_v4(),
+            email: "user@email.com".to_string(),
+            username: "username".to_string(),
+            password_hash: String::new(),
+            created_at: now(),
+            email_verified_at: None,
+            locale: Some("en".to_string()),
+        };
+        users.insert(&user).await?;
+        let saved_at = now();
+        mock_task(&tasks, user.id, saved_at).await;
+        let notifier = Arc::new(InMemoryNotifier::default());
+        let scheduler = ReminderScheduler::new(
+            tasks,
+            users,
+            notifier.clone(),
+            Gauge::default(),
+        );
+
+        scheduler
+            .send_due(saved_at + chrono::Duration::hours(1))
+            .await?;
+
+        let notifications = notifier.sent();
+        let due_at = (saved_at + chrono::Duration::hours(2))
+            .format("%Y-%m-%d at %H:%M UTC")
+            .to_string();
+        miette::ensure!(
+            notifications.len() == 1
+                && notifications[0].subject == "Reminder: Pay the rent"
+                && notifications[0].body
+                    == format!(
+                        "The task \"Pay the rent\" is due in 1 h, on {due_at}."
+                    ),
+            "Error: unexpected notifications {notifications:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failure_postpones_reminder() -> miette::Result<()> {
+        let tasks = Arc::new(InMemoryTaskRepository::default());
+        let saved_at = now();
+        let broken_user = Uuid::new_v4();
+        // a whole batch failing ahead of the reminder of another user
+        for _ in 0..BATCH_SIZE {
+            mock_task(&tasks, broken_user, saved_at).await;
+        }
+        let user_id = Uuid::new_v4();
+        mock_task(&tasks, user_id, saved_at + chrono::Duration::minutes(1))
+            .await;
+        let notifier = Arc::new(BrokenNotifier {
+            user_id: broken_user,
+            working: InMemoryNotifier::default(),
+        });
+        let scheduler = mock_scheduler(tasks.clone(), notifier.clone());
+
+        let at = saved_at + chrono::Duration::minutes(90);
+        let sent = scheduler.send_due(at).await?;
+        let depth = scheduler.depth.get();
+        let next = tasks.next_reminder_at().await?;
+        miette::ensure!(
+            sent == 1
+                && notifier.working.sent()[0].user_id == user_id
+                && depth == 0
+                && next == Some(at + chrono::Duration::seconds(30)),
+            "Error: failed reminders not postponed, next at {next:?}"
+        );
+
+        // retried later each time, then given up
+        let mut retries = Vec::new();
+        while let Some(retry_at) = tasks.next_reminder_at().await? {
+            scheduler.send_due(retry_at).await?;
+            retries.push(retry_at);
+        }
+        let waits: Vec<i64> = retries
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).num_seconds())
+            .collect();
+        miette::ensure!(
+            retries.len() == MAX_ATTEMPTS as usize - 1
+                && waits == [60, 120, 240, 480, 960, 1920],
+            "Error: unexpected retries {waits:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_span() -> miette::Result<()> {
+        let cases = [
+            (20, "0 min"),
+            (5 * 60 + 40, "6 min"),
+            (60 * 60, "1 h"),
+            (90 * 60, "1 h 30 min"),
+            (24 * 60 * 60, "1 d"),
+            (27 * 60 * 60 + 5 * 60, "1 d 3 h"),
+        ];
+        for (seconds, text) in cases {
+            let spanned = span(chrono::Duration::seconds(seconds));
+            miette::ensure!(
+                spanned == text,
+                "Error: {seconds}s spanned as {spanned:?} instead of {text:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_stops() -> miette::Result<()> {
+        let (tasks, _) = mock_tasks().await;
+        let scheduler =
+            mock_scheduler(tasks, Arc::new(InMemoryNotifier::default()));
+        let jobs = BackgroundJobs::default();
+        jobs.spawn("reminders", move |signal| scheduler.run(signal));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let running = jobs.running();
+
+        let returned = jobs.stop(Duration::from_secs(5)).await;
+
+        miette::ensure!(
+            running == 1 && returned,
+            "Error: scheduler not stopped"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,585 @@
+// external crates
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use tracing::{
+    field::{display, Empty},
+    instrument, Span,
+};
+use uuid::Uuid;
+// local modules
+use super::{
+    hash_token, random_token, verify_credentials_service, LoginThrottle,
+    PasswordHashing, TokenKeys,
+};
+use crate::repository::{
+    now, RefreshToken, Session, SessionRepository, TwoFactorRepository, User,
+    UserRepository,
+};
+use api_shared::{
+    dto::{
+        SessionInfo, SessionTokens, SignInForm, SignInOutcome,
+        TwoFactorChallenge,
+    },
+    prelude::LibError,
+};
+
+/// What is known about the device signing in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+/// Checks the credentials and opens a new session for the device.
+///
+/// A wrong password and an unknown email both fail with
+/// `LibError::InvalidCredentials`, so callers can't probe which emails
+/// are registered. Accounts whose email isn't verified yet fail with
+/// `LibError::EmailNotVerified`, only once the password matched.
+///
+/// Failures are throttled by `throttle`: once it trips, attempts fail
+/// with `LibError::TooManyAttempts` before the password is even checked.
+///
+/// Accounts with 2FA enabled get no session yet, only a short-lived
+/// challenge to complete with a code.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(user_id = Empty))]
+pub async fn sign_in_service(
+    form: SignInForm,
+    users: &dyn UserRepository,
+    sessions: &dyn SessionRepository,
+    two_factor: &dyn TwoFactorRepository,
+    passwords: &PasswordHashing,
+    keys: &TokenKeys,
+    throttle: &LoginThrottle,
+    client: ClientInfo,
+) -> Result<SignInOutcome, LibError> {
+    throttle
+        .check(&form.email, client.ip, now())
+        .await?;
+    let verified = verify_credentials_service(
+        users,
+        passwords,
+        &form.email,
+        form.password,
+    )
+    .await?;
+    let Some(user) = verified else {
+        throttle
+            .record_failure(&form.email, client.ip, now())
+            .await?;
+        return Err(LibError::InvalidCredentials);
+    };
+    Span::current().record("user_id", display(user.id));
+    if user.email_verified_at.is_none() {
+        throttle
+            .record_success(&form.email)
+            .await?;
+        return Err(LibError::EmailNotVerified);
+    }
+
+    let outcome =
+        complete_sign_in(sessions, two_factor, keys, &user, client.user_agent)
+            .await?;
+    // with 2FA, failures are only cleared once the second factor passes
+    if matches!(outcome, SignInOutcome::SignedIn(_)) {
+        throttle
+            .record_success(&form.email)
+            .await?;
+    }
+
+    Ok(outcome)
+}
+
+/// Opens a session for a user whose first factor passed, be it a password
+/// or an identity provider. Accounts with 2FA enabled only get a challenge.
+pub(crate) async fn complete_sign_in(
+    sessions: &dyn SessionRepository,
+    two_factor: &dyn TwoFactorRepository,
+    keys: &TokenKeys,
+    user: &User,
+    user_agent: Option<String>,
+) -> Result<SignInOutcome, LibError> {
+    let credential = two_factor.find_totp(user.id).await?;
+    if credential.is_some_and(|c| c.is_enabled()) {
+        return Ok(SignInOutcome::TwoFactorRequired(TwoFactorChallenge {
+            challenge_token: keys.mint_challenge(user.id, now())?,
+            expires_in: keys.challenge_ttl.num_seconds(),
+        }));
+    }
+
+    open_session(sessions, keys, user.id, user_agent)
+        .await
+        .map(SignInOutcome::SignedIn)
+}
+
+/// Opens a new session for a user whose credentials were checked.
+pub(crate) async fn open_session(
+    sessions: &dyn SessionRepository,
+    keys: &TokenKeys,
+    user_id: Uuid,
+    user_agent: Option<String>,
+) -> Result<SessionTokens, LibError> {
+    let now = now();
+    let session = Session {
+        id: Uuid::new_v4(),
+        user_id,
+        created_at: now,
+        expires_at: now + keys.refresh_ttl,
+        last_seen_at: now,
+        user_agent,
+        revoked_at: None,
+    };
+    sessions.insert(&session).await?;
+
+    issue_tokens(sessions, keys, &session, now).await
+}
+
+/// Trades a refresh token for a new access token and a new refresh token.
+///
+/// Every refresh token works once. Presenting one that was already used
+/// means it leaked, so the whole session is revoked and every token it
+/// holds stops working.
+#[instrument(skip_all)]
+pub async fn refresh_session_service(
+    sessions: &dyn SessionRepository,
+    keys: &TokenKeys,
+    refresh_token: &str,
+    user_agent: Option<String>,
+) -> Result<SessionTokens, LibError> {
+    let now = now();
+    let token_hash = hash_token(refresh_token);
+    let token = sessions
+        .find_refresh_token(&token_hash)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+    let session = sessions
+        .find_by_id(token.session_id)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+    if session.revoked_at.is_some() {
+        return Err(LibError::InvalidToken);
+    }
+    if !session.is_active(now) {
+        return Err(LibError::ExpiredCredentials);
+    }
+
+    let is_first_use = sessions
+        .use_refresh_token(&token_hash, now)
+        .await?;
+    if !is_first_use {
+        sessions.revoke(session.id, now).await?;
+        return Err(LibError::InvalidToken);
+    }
+
+    sessions
+        .touch(session.id, now, user_agent.as_deref())
+        .await?;
+    issue_tokens(sessions, keys, &session, now).await
+}
+
+/// Revokes the session owning `refresh_token`. Unknown or already revoked
+/// tokens are ignored so signing out is idempotent.
+#[instrument(skip_all)]
+pub async fn sign_out_service(
+    sessions: &dyn SessionRepository,
+    refresh_token: &str,
+) -> Result<(), LibError> {
+    let token = sessions
+        .find_refresh_token(&hash_token(refresh_token))
+        .await?;
+    if let Some(token) = token {
+        sessions
+            .revoke(token.session_id, now())
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Lists the devices where `user_id` is signed in.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn list_sessions_service(
+    sessions: &dyn SessionRepository,
+    user_id: Uuid,
+    current_session_id: Uuid,
+) -> Result<Vec<SessionInfo>, LibError> {
+    let active = sessions
+        .list_active(user_id, now())
+        .await?;
+
+    Ok(active
+        .into_iter()
+        .map(|session| SessionInfo {
+            id: session.id,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            user_agent: session.user_agent,
+            current: session.id == current_session_id,
+        })
+        .collect())
+}
+
+/// Signs out one of the user's devices. Its refresh token stops working
+/// at once, its current access token when it expires.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn revoke_session_service(
+    sessions: &dyn SessionRepository,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<(), LibError> {
+    let session = sessions
+        .find_by_id(session_id)
+        .await?
+        .filter(|session| session.user_id == user_id)
+        .ok_or(LibError::NotFound)?;
+
+    sessions.revoke(session.id, now()).await
+}
+
+async fn issue_tokens(
+    sessions: &dyn SessionRepository,
+    keys: &TokenKeys,
+    session: &Session,
+    now: DateTime<Utc>,
+) -> Result<SessionTokens, LibError> {
+    let refresh_token = random_token();
+    sessions
+        .insert_refresh_token(&RefreshToken {
+            token_hash: hash_token(&refresh_token),
+            session_id: session.id,
+            issued_at: now,
+            used_at: None,
+        })
+        .await?;
+
+    Ok(SessionTokens {
+        session_id: session.id,
+        access_token: keys.mint_access(session.user_id, session.id, now)?,
+        refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in: keys.access_ttl.num_seconds(),
+        refresh_expires_in: (session.expires_at - now).num_seconds(),
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{
+            InMemoryLoginAttemptRepository, InMemorySessionRepository,
+            InMemoryTwoFactorRepository, InMemoryUserRepository,
+        },
+        services::{
+            create_user_service, password::tests::mock_hashing,
+            throttle::tests::mock_throttle, ThrottlePolicy,
+        },
+    };
+    use api_shared::dto::UserForm;
+    use std::sync::Arc;
+
+    async fn mock_db() -> (InMemoryUserRepository, InMemorySessionRepository) {
+        let users = InMemoryUserRepository::default();
+        let user = create_user_service(
+            UserForm {
+                email: "user@email.com".to_string(),
+                password: "valid_password".to_string(),
+                username: "username".to_string(),
+            },
+            &users,
+            &mock_hashing(),
+        )
+        .await
+        .unwrap();
+        users
+            .mark_email_verified(user.id, now())
+            .await
+            .unwrap();
+
+        (users, InMemorySessionRepository::default())
+    }
+
+    fn mock_sign_in(email: &str, password: &str) -> SignInForm {
+        SignInForm {
+            email: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    async fn mock_session(
+        users: &InMemoryUserRepository,
+        sessions: &InMemorySessionRepository,
+        keys: &TokenKeys,
+    ) -> SessionTokens {
+        let outcome = sign_in_service(
+            mock_sign_in("user@email.com", "valid_password"),
+            users,
+            sessions,
+            &InMemoryTwoFactorRepository::default(),
+            &mock_hashing(),
+            keys,
+            &mock_throttle(),
+            ClientInfo {
+                ip: None,
+                user_agent: Some("Firefox".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+        match outcome {
+            SignInOutcome::SignedIn(tokens) => tokens,
+            SignInOutcome::TwoFactorRequired(_) => panic!("2FA not enabled"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_opens_session() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        let tokens =
+            mock_session(&users, &sessions, &TokenKeys::random()).await;
+        let session = sessions
+            .find_by_id(tokens.session_id)
+            .await?;
+
+        miette::ensure!(
+            session.is_some_and(|s| s.is_active(now())),
+            "Error: no active session stored"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_invalid_credentials() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        for form in [
+            mock_sign_in("user@email.com", "wrong_password"),
+            mock_sign_in("unknown@email.com", "valid_password"),
+        ] {
+            let result = sign_in_service(
+                form,
+                &users,
+                &sessions,
+                &InMemoryTwoFactorRepository::default(),
+                &mock_hashing(),
+                &TokenKeys::random(),
+                &mock_throttle(),
+                ClientInfo::default(),
+            )
+            .await;
+
+            miette::ensure!(
+                matches!(result, Err(LibError::InvalidCredentials)),
+                "Error: invalid credentials accepted"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_unverified_email() -> miette::Result<()> {
+        let users = InMemoryUserRepository::default();
+        create_user_service(
+            UserForm {
+                email: "user@email.com".to_string(),
+                password: "valid_password".to_string(),
+                username: "username".to_string(),
+            },
+            &users,
+            &mock_hashing(),
+        )
+        .await?;
+        let result = sign_in_service(
+            mock_sign_in("user@email.com", "valid_password"),
+            &users,
+            &InMemorySessionRepository::default(),
+            &InMemoryTwoFactorRepository::default(),
+            &mock_hashing(),
+            &TokenKeys::random(),
+            &mock_throttle(),
+            ClientInfo::default(),
+        )
+        .await;
+
+        miette::ensure!(
+            matches!(result, Err(LibError::EmailNotVerified)),
+            "Error: unverified account signed in"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_throttled() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        let throttle = LoginThrottle::new(
+            Arc::new(InMemoryLoginAttemptRepository::default()),
+            ThrottlePolicy {
+                free_failures: 1,
+                lockout_failures: 2,
+                ..ThrottlePolicy::default()
+            },
+        );
+        let mut results = Vec::new();
+        for password in ["wrong_password", "wrong_password", "valid_password"] {
+            let result = sign_in_service(
+                mock_sign_in("user@email.com", password),
+                &users,
+                &sessions,
+                &InMemoryTwoFactorRepository::default(),
+                &mock_hashing(),
+                &TokenKeys::random(),
+                &throttle,
+                ClientInfo::default(),
+            )
+            .await;
+            results.push(result);
+        }
+
+        miette::ensure!(
+            results[..2]
+                .iter()
+                .all(|r| matches!(r, Err(LibError::InvalidCredentials))),
+            "Error: failures throttled too early"
+        );
+        // even the right password waits until the lockout is over
+        miette::ensure!(
+            matches!(
+                results[2],
+                Err(LibError::TooManyAttempts { retry_after: 900 })
+            ),
+            "Error: sign in not throttled"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_out_revokes_session() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        let tokens =
+            mock_session(&users, &sessions, &TokenKeys::random()).await;
+        sign_out_service(&sessions, &tokens.refresh_token).await?;
+        sign_out_service(&sessions, &tokens.refresh_token).await?;
+        let session = sessions
+            .find_by_id(tokens.session_id)
+            .await?;
+
+        miette::ensure!(
+            session.is_some_and(|s| !s.is_active(now())),
+            "Error: session still active after sign out"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotates_token() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        let keys = TokenKeys::random();
+        let first = mock_session(&users, &sessions, &keys).await;
+        let second = refresh_session_service(
+            &sessions,
+            &keys,
+            &first.refresh_token,
+            None,
+        )
+        .await?;
+        let third = refresh_session_service(
+            &sessions,
+            &keys,
+            &second.refresh_token,
+            None,
+        )
+        .await?;
+
+        miette::ensure!(
+            first.session_id == third.session_id
+                && first.refresh_token != second.refresh_token
+                && second.refresh_token != third.refresh_token,
+            "Error: refresh token not rotated"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_reuse_revokes_family() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        let keys = TokenKeys::random();
+        let first = mock_session(&users, &sessions, &keys).await;
+        let second = refresh_session_service(
+            &sessions,
+            &keys,
+            &first.refresh_token,
+            None,
+        )
+        .await?;
+
+        // an attacker replays the stolen, already rotated token
+        let replay = refresh_session_service(
+            &sessions,
+            &keys,
+            &first.refresh_token,
+            None,
+        )
+        .await;
+        miette::ensure!(
+            matches!(replay, Err(LibError::InvalidToken)),
+            "Error: replayed refresh token accepted"
+        );
+        // the legitimate, newest token is dead too
+        let legit = refresh_session_service(
+            &sessions,
+            &keys,
+            &second.refresh_token,
+            None,
+        )
+        .await;
+        miette::ensure!(
+            matches!(legit, Err(LibError::InvalidToken)),
+            "Error: session survived token reuse"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_and_revoke_sessions() -> miette::Result<()> {
+        let (users, sessions) = mock_db().await;
+        let keys = TokenKeys::random();
+        let laptop = mock_session(&users, &sessions, &keys).await;
+        let phone = mock_session(&users, &sessions, &keys).await;
+        let user_id = sessions
+            .find_by_id(laptop.session_id)
+            .await?
+            .unwrap()
+            .user_id;
+
+        let listed =
+            list_sessions_service(&sessions, user_id, laptop.session_id)
+                .await?;
+        miette::ensure!(
+            listed.len() == 2
+                && listed
+                    .iter()
+                    .all(|s| s.current == (s.id == laptop.session_id)),
+            "Error: unexpected session list {listed:?}"
+        );
+
+        let stranger =
+            revoke_session_service(&sessions, Uuid::new_v4(), phone.session_id)
+                .await;
+        miette::ensure!(
+            matches!(stranger, Err(LibError::NotFound)),
+            "Error: revoked a session of another user"
+        );
+
+        revoke_session_service(&sessions, user_id, phone.session_id).await?;
+        let listed =
+            list_sessions_service(&sessions, user_id, laptop.session_id)
+                .await?;
+        miette::ensure!(
+            listed.len() == 1 && listed[0].id == laptop.session_id,
+            "Error: remote session not revoked"
+        );
+        Ok(())
+    }
+}
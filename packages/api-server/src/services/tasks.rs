@@ -0,0 +1,659 @@
+// external crates
+use chrono::{DateTime, NaiveDate, Utc};
+use tracing::instrument;
+use uuid::Uuid;
+// local modules
+use crate::repository::{now, timestamp, TaskRepository};
+use api_shared::{
+    dto::{
+        Occurrence, OccurrencePatch, OccurrenceQuery, Recurrence, Tag,
+        TagPatch, Task, TaskForm, TaskPatch, TaskQuery, TaskStatus,
+    },
+    prelude::LibError,
+    validation::{Validator, TASK_CONTENT_MAX_LEN, TASK_SUMMARY_MAX_LEN},
+};
+
+/// Saves a new open task of the user. Every field is validated first and
+/// all the failures are returned together as `LibError::Validation`.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn create_task_service(
+    form: TaskForm,
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+) -> Result<Task, LibError> {
+    let created_at = now();
+    let task = Task {
+        id: Uuid::new_v4(),
+        title: form.title.trim().to_string(),
+        summary: form.summary.trim().to_string(),
+        content: form.content,
+        tags: normalize_tags(form.tags),
+        status: TaskStatus::Open,
+        created_at,
+        updated_at: created_at,
+        completed_at: None,
+        recurrence: form
+            .recurrence
+            .map(normalize_recurrence),
+        starts_at: form.starts_at.map(whole_seconds),
+        due_at: form.due_at.map(whole_seconds),
+        reminders: normalize_reminders(form.reminders),
+    };
+    validate(&task)?;
+    tasks.insert(user_id, &task).await?;
+
+    // tags are named as first entered, which may not be this spelling
+    get_task_service(tasks, user_id, task.id).await
+}
+
+/// Tasks of the user, newest first. Archived ones are only listed when
+/// asked for by status.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn list_tasks_service(
+    query: TaskQuery,
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+) -> Result<Vec<Task>, LibError> {
+    let statuses = match query.status {
+        Some(status) => vec![status],
+        None => vec![TaskStatus::Open, TaskStatus::Done],
+    };
+
+    let tag_key = query.tag.as_deref().map(Tag::key);
+
+    tasks
+        .list(user_id, &statuses, tag_key.as_deref())
+        .await
+}
+
+/// Loads a task of the user. Those of other users are `NotFound` too.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn get_task_service(
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+    task_id: Uuid,
+) -> Result<Task, LibError> {
+    tasks
+        .find(user_id, task_id)
+        .await?
+        .ok_or(LibError::NotFound)
+}
+
+/// Changes the fields set in `patch`, validated like a new task. Marking
+/// the task done records when; reopening it clears that. Reminders follow,
+/// see `repository::plan_reminders`.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn update_task_service(
+    patch: TaskPatch,
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+    task_id: Uuid,
+) -> Result<Task, LibError> {
+    let mut task = get_task_service(tasks, user_id, task_id).await?;
+    let updated_at = now();
+
+    if let Some(title) = patch.title {
+        task.title = title.trim().to_string();
+    }
+    if let Some(summary) = patch.summary {
+        task.summary = summary.trim().to_string();
+    }
+    if let Some(content) = patch.content {
+        task.content = content;
+    }
+    if let Some(tags) = patch.tags {
+        task.tags = normalize_tags(tags);
+    }
+    if let Some(recurrence) = patch.recurrence {
+        task.recurrence = recurrence.map(normalize_recurrence);
+    }
+    if let Some(starts_at) = patch.starts_at {
+        task.starts_at = starts_at.map(whole_seconds);
+    }
+    if let Some(due_at) = patch.due_at {
+        task.due_at = due_at.map(whole_seconds);
+    }
+    if let Some(reminders) = patch.reminders {
+        task.reminders = normalize_reminders(reminders);
+    }
+    match patch.status {
+        Some(status) if status != task.status => {
+            task.completed_at = match status {
+                TaskStatus::Open => None,
+                TaskStatus::Done => Some(updated_at),
+                TaskStatus::Archived => task.completed_at,
+            };
+            task.status = status;
+        }
+        _ => {}
+    }
+    validate(&task)?;
+
+    task.updated_at = updated_at;
+    if !tasks.update(user_id, &task).await? {
+        // deleted in the meantime
+        return Err(LibError::NotFound);
+    }
+
+    get_task_service(tasks, user_id, task_id).await
+}
+
+#[instrument(skip_all, fields(%user_id))]
+pub async fn delete_task_service(
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+    task_id: Uuid,
+) -> Result<(), LibError> {
+    if !tasks.delete(user_id, task_id).await? {
+        return Err(LibError::NotFound);
+    }
+
+    Ok(())
+}
+
+/// The days the user's open recurring tasks happen on within the query,
+/// by date. Tasks done or archived as a whole don't happen anymore.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn list_occurrences_service(
+    query: OccurrenceQuery,
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+) -> Result<Vec<Occurrence>, LibError> {
+    Validator::new()
+        .date_range("from", query.from, query.to)
+        .finish()?;
+
+    let recurring = tasks
+        .list(user_id, &[TaskStatus::Open], None)
+        .await?;
+    let mut completions = tasks
+        .list_completions(user_id, query.from, query.to)
+        .await?;
+    let mut occurrences: Vec<Occurrence> = recurring
+        .iter()
+        .filter_map(|task| Some((task, task.recurrence.as_ref()?)))
+        .flat_map(|(task, recurrence)| {
+            recurrence
+                .occurrences(query.from, query.to)
+                .into_iter()
+                .map(|date| Occurrence {
+                    task_id: task.id,
+                    date,
+                    title: task.title.clone(),
+                    tags: task.tags.clone(),
+                    completed_at: completions.remove(&(task.id, date)),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    // stable, so tasks stay newest first within a day
+    occurrences.sort_by_key(|occurrence| occurrence.date);
+
+    Ok(occurrences)
+}
+
+/// Marks the occurrence of a recurring task on `date` done or not. Days
+/// the task doesn't happen on are `NotFound`.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn update_occurrence_service(
+    patch: OccurrencePatch,
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+    task_id: Uuid,
+    date: NaiveDate,
+) -> Result<Occurrence, LibError> {
+    let task = get_task_service(tasks, user_id, task_id).await?;
+    let happens = task
+        .recurrence
+        .as_ref()
+        .is_some_and(|recurrence| {
+            !recurrence
+                .occurrences(date, date)
+                .is_empty()
+        });
+    if !happens {
+        return Err(LibError::NotFound);
+    }
+
+    let completed_at = patch.done.then(now);
+    if !tasks
+        .set_completion(user_id, task_id, date, completed_at)
+        .await?
+    {
+        // deleted in the meantime
+        return Err(LibError::NotFound);
+    }
+    let completed_at = tasks
+        .list_completions(user_id, date, date)
+        .await?
+        .remove(&(task_id, date));
+
+    Ok(Occurrence {
+        task_id,
+        date,
+        title: task.title,
+        tags: task.tags,
+        completed_at,
+    })
+}
+
+/// Tags of the user, most used first, e.g. to suggest them while typing.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn list_tags_service(
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+) -> Result<Vec<Tag>, LibError> {
+    tasks.list_tags(user_id).await
+}
+
+/// Changes the fields of the tag `name` set in `patch`.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn update_tag_service(
+    patch: TagPatch,
+    tasks: &dyn TaskRepository,
+    user_id: Uuid,
+    name: &str,
+) -> Result<Tag, LibError> {
+    let key = Tag::key(name);
+    if let Some(color) = patch.color {
+        let color = color.trim().to_lowercase();
+        Validator::new()
+            .color("color", &color)
+            .finish()?;
+        if !tasks
+            .set_tag_color(user_id, &key, &color)
+            .await?
+        {
+            return Err(LibError::NotFound);
+        }
+    }
+
+    tasks
+        .list_tags(user_id)
+        .await?
+        .into_iter()
+        .find(|tag| Tag::key(&tag.name) == key)
+        .ok_or(LibError::NotFound)
+}
+
+fn validate(task: &Task) -> Result<(), LibError> {
+    let mut validator = Validator::new();
+    validator
+        .title("title", &task.title)
+        .text("summary", &task.summary, TASK_SUMMARY_MAX_LEN)
+        .text("content", &task.content, TASK_CONTENT_MAX_LEN)
+        .tags("tags", &task.tags)
+        .schedule("due_at", task.starts_at, task.due_at)
+        .reminders("reminders", &task.reminders, task.due_at.is_some());
+    if let Some(recurrence) = &task.recurrence {
+        validator.recurrence("recurrence", recurrence, now().date_naive());
+    }
+
+    validator.finish()
+}
+
+/// Upper-cases the rule, as RFC 5545 spells it, and sorts the exceptions
+/// without repeating them.
+fn normalize_recurrence(recurrence: Recurrence) -> Recurrence {
+    let mut exceptions = recurrence.exceptions;
+    exceptions.sort();
+    exceptions.dedup();
+
+    Recurrence {
+        rule: recurrence.rule.trim().to_uppercase(),
+        starts_on: recurrence.starts_on,
+        exceptions,
+    }
+}
+
+/// Trims the tags and drops the empty ones and the repeated ones, compared
+/// case-insensitively; the first spelling is kept.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim();
+        let is_repeated = normalized
+            .iter()
+            .any(|kept| kept.to_lowercase() == tag.to_lowercase());
+        if !tag.is_empty() && !is_repeated {
+            normalized.push(tag.to_string());
+        }
+    }
+
+    normalized
+}
+
+/// Sorts the offsets largest first, so the earliest reminder comes first,
+/// without repeating them.
+fn normalize_reminders(mut reminders: Vec<u32>) -> Vec<u32> {
+    reminders.sort_unstable_by(|a, b| b.cmp(a));
+    reminders.dedup();
+
+    reminders
+}
+
+/// Drops the fraction of a second the store wouldn't keep, so reminder
+/// times read back equal.
+fn whole_seconds(at: DateTime<Utc>) -> DateTime<Utc> {
+    timestamp(at.timestamp())
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::InMemoryTaskRepository;
+
+    fn mock_form() -> TaskForm {
+        TaskForm {
+            title: " Water the plants ".to_string(),
+            summary: String::new(),
+            content: "The ones on the balcony".to_string(),
+            tags: vec![
+                "home".to_string(),
+                " Daily".to_string(),
+                "HOME".to_string(),
+                " ".to_string(),
+            ],
+            recurrence: None,
+            starts_at: None,
+            due_at: None,
+            reminders: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_task() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let user_id = Uuid::new_v4();
+
+        let task = create_task_service(mock_form(), &tasks, user_id).await?;
+        let listed =
+            list_tasks_service(TaskQuery::default(), &tasks, user_id).await?;
+
+        miette::ensure!(
+            task.title == "Water the plants"
+                && task.tags == ["home", "Daily"]
+                && task.status == TaskStatus::Open
+                && listed == [task.clone()],
+            "Error: unexpected task {task:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_task_invalid() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let form = TaskForm {
+            title: "  ".to_string(),
+            summary: "s".repeat(TASK_SUMMARY_MAX_LEN + 1),
+            ..mock_form()
+        };
+
+        let result = create_task_service(form, &tasks, Uuid::new_v4()).await;
+        let Err(LibError::Validation { fields }) = result else {
+            miette::bail!("Error: invalid task saved");
+        };
+        let names: Vec<&str> = fields
+            .iter()
+            .map(|f| f.field.as_str())
+            .collect();
+
+        miette::ensure!(
+            names == ["title", "summary"],
+            "Error: unexpected fields {names:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_task_status() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let user_id = Uuid::new_v4();
+        let task = create_task_service(mock_form(), &tasks, user_id).await?;
+        let status = |status| TaskPatch {
+            status: Some(status),
+            ..TaskPatch::default()
+        };
+
+        let done = update_task_service(
+            status(TaskStatus::Done),
+            &tasks,
+            user_id,
+            task.id,
+        )
+        .await?;
+        let archived = update_task_service(
+            status(TaskStatus::Archived),
+            &tasks,
+            user_id,
+            task.id,
+        )
+        .await?;
+        let listed =
+            list_tasks_service(TaskQuery::default(), &tasks, user_id).await?;
+        let reopened = update_task_service(
+            status(TaskStatus::Open),
+            &tasks,
+            user_id,
+            task.id,
+        )
+        .await?;
+
+        miette::ensure!(
+            done.completed_at.is_some()
+                && archived.completed_at == done.completed_at
+                && listed.is_empty()
+                && reopened.completed_at.is_none(),
+            "Error: unexpected completion {done:?} {archived:?} {reopened:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_due_and_reminders() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let user_id = Uuid::new_v4();
+        let due_at = now() + chrono::Duration::days(2);
+        let form = TaskForm {
+            due_at: Some(due_at),
+            reminders: vec![10, 24 * 60, 10],
+            ..mock_form()
+        };
+        let task = create_task_service(form, &tasks, user_id).await?;
+        let next = tasks.next_reminder_at().await?;
+        miette::ensure!(
+            task.reminders == [24 * 60, 10]
+                && next == Some(due_at - chrono::Duration::days(1)),
+            "Error: unexpected reminders {task:?}, next at {next:?}"
+        );
+
+        let undue = TaskPatch {
+            due_at: Some(None),
+            ..TaskPatch::default()
+        };
+        let result =
+            update_task_service(undue.clone(), &tasks, user_id, task.id).await;
+        miette::ensure!(
+            matches!(
+                &result,
+                Err(LibError::Validation { fields })
+                    if fields[0].code == "reminders.without_due"
+            ),
+            "Error: reminders kept without a due time {result:?}"
+        );
+
+        let patch = TaskPatch {
+            reminders: Some(Vec::new()),
+            ..undue
+        };
+        let task = update_task_service(patch, &tasks, user_id, task.id).await?;
+        let next = tasks.next_reminder_at().await?;
+        miette::ensure!(
+            task.due_at.is_none() && next.is_none(),
+            "Error: reminders kept after clearing the due time {next:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_occurrences() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let user_id = Uuid::new_v4();
+        let date =
+            |value| NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap();
+        let form = TaskForm {
+            recurrence: Some(Recurrence {
+                rule: "freq=weekly;byday=mo,th".to_string(),
+                starts_on: date("2026-10-12"),
+                exceptions: vec![date("2026-10-19"), date("2026-10-19")],
+            }),
+            ..mock_form()
+        };
+        let habit = create_task_service(form, &tasks, user_id).await?;
+        create_task_service(mock_form(), &tasks, user_id).await?;
+        let week = || OccurrenceQuery {
+            from: date("2026-10-12"),
+            to: date("2026-10-25"),
+        };
+        let done = |done| OccurrencePatch { done };
+
+        let thursday = update_occurrence_service(
+            done(true),
+            &tasks,
+            user_id,
+            habit.id,
+            date("2026-10-15"),
+        )
+        .await?;
+        let skipped = update_occurrence_service(
+            done(true),
+            &tasks,
+            user_id,
+            habit.id,
+            date("2026-10-19"),
+        )
+        .await;
+        let listed = list_occurrences_service(week(), &tasks, user_id).await?;
+        let too_long = list_occurrences_service(
+            OccurrenceQuery {
+                from: date("2026-01-01"),
+                to: date("2027-12-31"),
+            },
+            &tasks,
+            user_id,
+        )
+        .await;
+        let dates: Vec<(String, bool)> = listed
+            .iter()
+            .map(|o| (o.date.to_string(), o.completed_at.is_some()))
+            .collect();
+
+        miette::ensure!(
+            habit
+                .recurrence
+                .as_ref()
+                .is_some_and(|r| {
+                    r.rule == "FREQ=WEEKLY;BYDAY=MO,TH"
+                        && r.exceptions.len() == 1
+                })
+                && thursday.completed_at.is_some()
+                && matches!(skipped, Err(LibError::NotFound))
+                && matches!(too_long, Err(LibError::Validation { .. }))
+                && dates
+                    == [
+                        ("2026-10-12".to_string(), false),
+                        ("2026-10-15".to_string(), true),
+                        ("2026-10-22".to_string(), false),
+                    ],
+            "Error: unexpected occurrences {dates:?}"
+        );
+
+        // no longer repeating, or done for good, it has no occurrences
+        update_task_service(
+            TaskPatch {
+                recurrence: Some(None),
+                ..TaskPatch::default()
+            },
+            &tasks,
+            user_id,
+            habit.id,
+        )
+        .await?;
+        let listed = list_occurrences_service(week(), &tasks, user_id).await?;
+        miette::ensure!(listed.is_empty(), "Error: stopped task still happens");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tags() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let user_id = Uuid::new_v4();
+        create_task_service(mock_form(), &tasks, user_id).await?;
+        let form = TaskForm {
+            tags: vec!["DAILY".to_string(), "reading".to_string()],
+            ..mock_form()
+        };
+        let second = create_task_service(form, &tasks, user_id).await?;
+        let color = |color: &str| TagPatch {
+            color: Some(color.to_string()),
+        };
+
+        let daily = list_tasks_service(
+            TaskQuery {
+                tag: Some(" daily".to_string()),
+                ..TaskQuery::default()
+            },
+            &tasks,
+            user_id,
+        )
+        .await?;
+        let recolored =
+            update_tag_service(color("#ABCDEF"), &tasks, user_id, "Reading")
+                .await?;
+        let invalid =
+            update_tag_service(color("blue"), &tasks, user_id, "reading").await;
+        let unknown =
+            update_tag_service(color("#abcdef"), &tasks, user_id, "work").await;
+        let tags = list_tags_service(&tasks, user_id).await?;
+
+        miette::ensure!(
+            second.tags == ["Daily", "reading"]
+                && daily.len() == 2
+                && recolored.color == "#abcdef"
+                && matches!(invalid, Err(LibError::Validation { .. }))
+                && matches!(unknown, Err(LibError::NotFound))
+                && tags[0].name == "Daily"
+                && tags[0].usage_count == 2,
+            "Error: unexpected tags {tags:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_other_users_tasks_not_found() -> miette::Result<()> {
+        let tasks = InMemoryTaskRepository::default();
+        let task =
+            create_task_service(mock_form(), &tasks, Uuid::new_v4()).await?;
+        let stranger = Uuid::new_v4();
+
+        let read = get_task_service(&tasks, stranger, task.id).await;
+        let updated = update_task_service(
+            TaskPatch::default(),
+            &tasks,
+            stranger,
+            task.id,
+        )
+        .await;
+        let deleted = delete_task_service(&tasks, stranger, task.id).await;
+
+        miette::ensure!(
+            matches!(read, Err(LibError::NotFound))
+                && matches!(updated, Err(LibError::NotFound))
+                && matches!(deleted, Err(LibError::NotFound)),
+            "Error: another user reached the task"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+// external crates
+use chrono::{DateTime, Duration, Utc};
+use std::{net::IpAddr, sync::Arc};
+// local modules
+use crate::{
+    config::ThrottleConfig,
+    repository::{AttemptCounter, LoginAttemptRepository},
+};
+use api_shared::prelude::LibError;
+
+/// How hard failed sign-ins are throttled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottlePolicy {
+    /// failures per account answered without delay
+    pub free_failures: u32,
+    /// failures per account that lock it for `lockout`; in between, each
+    /// failure doubles the wait, starting at one second
+    pub lockout_failures: u32,
+    pub lockout: Duration,
+    /// failures allowed from one client address within `window`,
+    /// whatever accounts they target
+    pub ip_failure_budget: u32,
+    /// failures older than this are forgotten
+    pub window: Duration,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> Self {
+        Self::from(&ThrottleConfig::default())
+    }
+}
+
+impl From<&ThrottleConfig> for ThrottlePolicy {
+    fn from(config: &ThrottleConfig) -> Self {
+        Self {
+            free_failures: config.free_failures,
+            lockout_failures: config.lockout_failures,
+            lockout: Duration::seconds(config.lockout.into()),
+            ip_failure_budget: config.ip_failure_budget,
+            window: Duration::seconds(config.window.into()),
+        }
+    }
+}
+
+impl ThrottlePolicy {
+    /// Wait required by an account counter, zero when it may try now.
+    fn account_wait(&self, counter: &AttemptCounter) -> Duration {
+        let failures = counter.failures;
+        if failures >= self.lockout_failures {
+            self.lockout
+        } else if failures > self.free_failures {
+            let doublings = (failures - self.free_failures - 1).min(30);
+            Duration::seconds(1 << doublings).min(self.lockout)
+        } else {
+            Duration::zero()
+        }
+    }
+
+    /// Wait required by a client address counter, zero when it may try now.
+    fn ip_wait(&self, counter: &AttemptCounter) -> Duration {
+        if counter.failures >= self.ip_failure_budget {
+            self.window
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
+/// Slows down credential stuffing: per account, failures past a free
+/// allowance are answered with growing delays and end in a temporary
+/// lockout; per client address, a failure budget caps attacks spread over
+/// many accounts.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    attempts: Arc<dyn LoginAttemptRepository>,
+    policy: ThrottlePolicy,
+}
+
+impl LoginThrottle {
+    pub fn new(
+        attempts: Arc<dyn LoginAttemptRepository>,
+        policy: ThrottlePolicy,
+    ) -> Self {
+        Self { attempts, policy }
+    }
+
+    /// Fails with `LibError::TooManyAttempts` while `email` or `ip` has to
+    /// wait before trying again.
+    pub async fn check(
+        &self,
+        email: &str,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        let mut retry_at = None;
+        if let Some(counter) = self
+            .find(&account_key(email), now)
+            .await?
+        {
+            retry_at = Some(
+                counter.last_failure_at + self.policy.account_wait(&counter),
+            );
+        }
+        if let Some(ip) = ip {
+            if let Some(counter) = self.find(&ip_key(ip), now).await? {
+                let ip_retry_at =
+                    counter.first_failure_at + self.policy.ip_wait(&counter);
+                retry_at = retry_at.max(Some(ip_retry_at));
+            }
+        }
+
+        match retry_at {
+            Some(retry_at) if retry_at > now => {
+                Err(LibError::TooManyAttempts {
+                    // rounded up, so clients never retry a second too early
+                    retry_after: ((retry_at - now).num_milliseconds() as u64)
+                        .div_ceil(1000),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(
+        &self,
+        email: &str,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Result<(), LibError> {
+        let window_start = now - self.policy.window;
+        self.attempts
+            .record_failure(&account_key(email), now, window_start)
+            .await?;
+        if let Some(ip) = ip {
+            self.attempts
+                .record_failure(&ip_key(ip), now, window_start)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the failures of the account. The address budget is kept, so
+    /// an attacker can't reset it by signing into an account of their own.
+    pub async fn record_success(&self, email: &str) -> Result<(), LibError> {
+        self.attempts
+            .clear(&account_key(email))
+            .await
+    }
+
+    async fn find(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AttemptCounter>, LibError> {
+        let counter = self.attempts.find(key).await?;
+
+        Ok(counter.filter(|c| c.first_failure_at >= now - self.policy.window))
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+fn ip_key(ip: IpAddr) -> String {
+    format!("ip:{ip}")
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::repository::{now, InMemoryLoginAttemptRepository};
+
+    pub(crate) fn mock_throttle() -> LoginThrottle {
+        LoginThrottle::new(
+            Arc::new(InMemoryLoginAttemptRepository::default()),
+            ThrottlePolicy::default(),
+        )
+    }
+
+    fn retry_after(result: Result<(), LibError>) -> Option<u64> {
+        match result {
+            Err(LibError::TooManyAttempts { retry_after }) => Some(retry_after),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progressive_delay_and_lockout() -> miette::Result<()> {
+        let throttle = mock_throttle();
+        let now = now();
+        let mut waits = Vec::new();
+        for _ in 0..10 {
+            throttle
+                .record_failure("user@email.com", None, now)
+                .await?;
+            waits.push(retry_after(
+                throttle
+                    .check("User@Email.com", None, now)
+                    .await,
+            ));
+        }
+
+        miette::ensure!(
+            waits
+                == [
+                    None,
+                    None,
+                    None,
+                    Some(1),
+                    Some(2),
+                    Some(4),
+                    Some(8),
+                    Some(16),
+                    Some(32),
+                    Some(15 * 60),
+                ],
+            "Error: unexpected waits {waits:?}"
+        );
+        let later = now + Duration::minutes(15);
+        miette::ensure!(
+            throttle
+                .check("user@email.com", None, later)
+                .await
+                .is_ok(),
+            "Error: lockout never ends"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_success_clears_account() -> miette::Result<()> {
+        let throttle = mock_throttle();
+        let now = now();
+        for _ in 0..5 {
+            throttle
+                .record_failure("user@email.com", None, now)
+                .await?;
+        }
+        throttle
+            .record_success("user@email.com")
+            .await?;
+
+        miette::ensure!(
+            throttle
+                .check("user@email.com", None, now)
+                .await
+                .is_ok(),
+            "Error: account still throttled after success"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ip_budget() -> miette::Result<()> {
+        let throttle = mock_throttle();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let now = now();
+        // one failure per account never trips the account delays
+        for n in 0..50 {
+            let email = format!("user{n}@email.com");
+            throttle
+                .record_failure(&email, Some(ip), now)
+                .await?;
+        }
+
+        let wait = retry_after(
+            throttle
+                .check("fresh@email.com", Some(ip), now)
+                .await,
+        );
+        miette::ensure!(wait == Some(60 * 60), "Error: budget not enforced");
+        let other_ip = "203.0.113.8".parse().ok();
+        miette::ensure!(
+            throttle
+                .check("fresh@email.com", other_ip, now)
+                .await
+                .is_ok(),
+            "Error: other address throttled"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+// external crates
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{
+    errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+// local modules
+use api_shared::prelude::LibError;
+
+/// Claims carried by an access token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessClaims {
+    /// user id
+    pub sub: Uuid,
+    /// session id
+    pub sid: Uuid,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Claims of the token proving the password step of a sign-in that still
+/// awaits its second factor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    /// user id
+    pub sub: Uuid,
+    /// always `CHALLENGE_TYPE`, so access tokens can't stand in for it
+    pub typ: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+const CHALLENGE_TYPE: &str = "2fa";
+
+/// HMAC keys and lifetimes used to sign access and challenge tokens
+/// (HS256 JWTs).
+#[derive(Clone)]
+pub struct TokenKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    pub access_ttl: Duration,
+    pub refresh_ttl: Duration,
+    pub challenge_ttl: Duration,
+}
+
+impl TokenKeys {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            access_ttl: Duration::minutes(15),
+            refresh_ttl: Duration::days(30),
+            challenge_ttl: Duration::minutes(5),
+        }
+    }
+
+    /// Keys from a throwaway secret: tokens die with the process.
+    pub fn random() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        Self::new(&secret)
+    }
+
+    pub fn mint_access(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<String, LibError> {
+        let claims = AccessClaims {
+            sub: user_id,
+            sid: session_id,
+            iat: now.timestamp(),
+            exp: (now + self.access_ttl).timestamp(),
+        };
+
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &self.encoding,
+        )
+        .map_err(LibError::internal)
+    }
+
+    /// Checks the signature and expiry of an access token.
+    pub fn decode_access(&self, token: &str) -> Result<AccessClaims, LibError> {
+        self.decode(token)
+    }
+
+    pub fn mint_challenge(
+        &self,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<String, LibError> {
+        let claims = ChallengeClaims {
+            sub: user_id,
+            typ: CHALLENGE_TYPE.to_string(),
+            iat: now.timestamp(),
+            exp: (now + self.challenge_ttl).timestamp(),
+        };
+
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &self.encoding,
+        )
+        .map_err(LibError::internal)
+    }
+
+    /// Checks a challenge token, returning the id of the user signing in.
+    pub fn decode_challenge(&self, token: &str) -> Result<Uuid, LibError> {
+        let claims: ChallengeClaims = self.decode(token)?;
+        if claims.typ != CHALLENGE_TYPE {
+            return Err(LibError::InvalidToken);
+        }
+
+        Ok(claims.sub)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, LibError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+
+        jsonwebtoken::decode::<T>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ExpiredSignature => LibError::ExpiredCredentials,
+                _ => LibError::InvalidToken,
+            })
+    }
+}
+
+/// A random, URL-safe opaque token (256 bits).
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Digest under which opaque tokens are stored, so a leaked table can't be
+/// replayed. Tokens carry enough entropy that a plain SHA-256 is enough.
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
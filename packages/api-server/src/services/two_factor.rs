@@ -0,0 +1,503 @@
+// external crates
+use rand::{rngs::OsRng, Rng};
+use totp_rs::{Algorithm, Secret, TOTP};
+use tracing::instrument;
+use uuid::Uuid;
+// local modules
+use super::{
+    hash_token, open_session, ClientInfo, LoginThrottle, PasswordHashing,
+    TokenKeys, Verification,
+};
+use crate::repository::{
+    now, SessionRepository, TotpCredential, TwoFactorRepository, UserRepository,
+};
+use api_shared::{
+    dto::{
+        PasswordForm, RecoveryCodes, SessionTokens, TotpEnrollment,
+        TwoFactorForm, TwoFactorStatus,
+    },
+    prelude::LibError,
+};
+
+/// Issuer shown next to the account in authenticator apps.
+pub const TOTP_ISSUER: &str = "Sight Agent";
+/// Seconds each TOTP code is valid for, the RFC 6238 default.
+pub const TOTP_STEP: u64 = 30;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Unambiguous characters: no `0`/`o`, `1`/`l`/`i`.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[instrument(skip_all, fields(%user_id))]
+pub async fn two_factor_status_service(
+    two_factor: &dyn TwoFactorRepository,
+    user_id: Uuid,
+) -> Result<TwoFactorStatus, LibError> {
+    let credential = two_factor.find_totp(user_id).await?;
+
+    Ok(TwoFactorStatus {
+        enabled: credential.is_some_and(|c| c.is_enabled()),
+    })
+}
+
+/// Generates a new TOTP secret for the user. It only protects the account
+/// once confirmed by `confirm_totp_enrollment_service`; starting over
+/// replaces an unconfirmed secret.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn start_totp_enrollment_service(
+    users: &dyn UserRepository,
+    two_factor: &dyn TwoFactorRepository,
+    user_id: Uuid,
+) -> Result<TotpEnrollment, LibError> {
+    let user = users
+        .find_by_id(user_id)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+    let existing = two_factor.find_totp(user_id).await?;
+    if existing.is_some_and(|c| c.is_enabled()) {
+        return Err(LibError::TwoFactorAlreadyEnabled);
+    }
+
+    let secret = match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => return Err(LibError::UnknownError),
+    };
+    two_factor
+        .save_totp(&TotpCredential {
+            user_id,
+            secret: secret.clone(),
+            created_at: now(),
+            enabled_at: None,
+            last_used_step: None,
+        })
+        .await?;
+
+    Ok(TotpEnrollment {
+        otpauth_uri: totp(&secret, &user.email)?.get_url(),
+        secret,
+    })
+}
+
+/// Turns 2FA on once the authenticator proved it holds the secret, and
+/// hands out the recovery codes.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn confirm_totp_enrollment_service(
+    two_factor: &dyn TwoFactorRepository,
+    user_id: Uuid,
+    code: &str,
+) -> Result<RecoveryCodes, LibError> {
+    let credential = two_factor
+        .find_totp(user_id)
+        .await?
+        .ok_or(LibError::NotFound)?;
+    if credential.is_enabled() {
+        return Err(LibError::TwoFactorAlreadyEnabled);
+    }
+    if !check_totp(two_factor, &credential, code).await? {
+        return Err(LibError::InvalidTwoFactorCode);
+    }
+
+    two_factor
+        .enable_totp(user_id, now())
+        .await?;
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| recovery_code())
+        .collect();
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+    two_factor
+        .replace_recovery_codes(user_id, &code_hashes)
+        .await?;
+
+    Ok(RecoveryCodes { recovery_codes })
+}
+
+/// Completes a sign-in with a TOTP code or a recovery code.
+///
+/// Wrong codes count as failed sign-ins of the account, so guessing
+/// them is throttled like guessing passwords.
+#[instrument(skip_all)]
+pub async fn verify_second_factor_service(
+    form: TwoFactorForm,
+    users: &dyn UserRepository,
+    two_factor: &dyn TwoFactorRepository,
+    sessions: &dyn SessionRepository,
+    keys: &TokenKeys,
+    throttle: &LoginThrottle,
+    client: ClientInfo,
+) -> Result<SessionTokens, LibError> {
+    let user_id = keys.decode_challenge(&form.challenge_token)?;
+    let user = users
+        .find_by_id(user_id)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+    let credential = two_factor
+        .find_totp(user_id)
+        .await?
+        .filter(TotpCredential::is_enabled)
+        .ok_or(LibError::InvalidToken)?;
+    throttle
+        .check(&user.email, client.ip, now())
+        .await?;
+
+    let is_totp = form.code.trim().len() == 6
+        && form
+            .code
+            .trim()
+            .chars()
+            .all(|c| c.is_ascii_digit());
+    let is_valid = if is_totp {
+        check_totp(two_factor, &credential, &form.code).await?
+    } else {
+        let code_hash = hash_token(&normalize_recovery_code(&form.code));
+        two_factor
+            .use_recovery_code(user_id, &code_hash, now())
+            .await?
+    };
+    if !is_valid {
+        throttle
+            .record_failure(&user.email, client.ip, now())
+            .await?;
+        return Err(LibError::InvalidTwoFactorCode);
+    }
+
+    throttle
+        .record_success(&user.email)
+        .await?;
+    open_session(sessions, keys, user_id, client.user_agent).await
+}
+
+/// Removes the TOTP secret and the recovery codes of the user.
+#[instrument(skip_all, fields(%user_id))]
+pub async fn disable_two_factor_service(
+    form: PasswordForm,
+    users: &dyn UserRepository,
+    two_factor: &dyn TwoFactorRepository,
+    passwords: &PasswordHashing,
+    user_id: Uuid,
+) -> Result<(), LibError> {
+    let user = users
+        .find_by_id(user_id)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+    let verification = passwords
+        .verify(form.password, user.password_hash)
+        .await?;
+    if matches!(verification, Verification::Invalid) {
+        return Err(LibError::InvalidCredentials);
+    }
+
+    two_factor.delete_all(user_id).await
+}
+
+fn totp(secret: &str, account: &str) -> Result<TOTP, LibError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(LibError::internal)?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        TOTP_STEP,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account.to_string(),
+    )
+    .map_err(LibError::internal)
+}
+
+/// Accepts the code of the current step or of the steps next to it, to
+/// allow for clock drift, but never a step at or before the last accepted
+/// one: a code works once.
+async fn check_totp(
+    two_factor: &dyn TwoFactorRepository,
+    credential: &TotpCredential,
+    code: &str,
+) -> Result<bool, LibError> {
+    let totp = totp(&credential.secret, "")?;
+    let now = now().timestamp() as u64;
+    let code = code.trim();
+    for time in [now - TOTP_STEP, now, now + TOTP_STEP] {
+        if totp.check(code, time) {
+            let step = (time / TOTP_STEP) as i64;
+            return two_factor
+                .use_totp_step(credential.user_id, step)
+                .await;
+        }
+    }
+
+    Ok(false)
+}
+
+/// Ten random characters (about 49 bits), shown as `xxxxx-xxxxx`.
+fn recovery_code() -> String {
+    let chars: String = (0..10)
+        .map(|_| {
+            let index = OsRng.gen_range(0..RECOVERY_CODE_ALPHABET.len());
+            RECOVERY_CODE_ALPHABET[index] as char
+        })
+        .collect();
+
+    format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+/// Users may type recovery codes in any case, with or without the dash.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{
+            InMemorySessionRepository, InMemoryTwoFactorRepository,
+            InMemoryUserRepository,
+        },
+        services::{
+            create_user_service, password::tests::mock_hashing,
+            throttle::tests::mock_throttle,
+        },
+    };
+    use api_shared::dto::UserForm;
+
+    struct MockDb {
+        users: InMemoryUserRepository,
+        two_factor: InMemoryTwoFactorRepository,
+        sessions: InMemorySessionRepository,
+        keys: TokenKeys,
+        user_id: Uuid,
+    }
+
+    async fn mock_db() -> MockDb {
+        let users = InMemoryUserRepository::default();
+        let user = create_user_service(
+            UserForm {
+                email: "user@email.com".to_string(),
+                password: "valid_password".to_string(),
+                username: "username".to_string(),
+            },
+            &users,
+            &mock_hashing(),
+        )
+        .await
+        .unwrap();
+
+        MockDb {
+            users,
+            two_factor: InMemoryTwoFactorRepository::default(),
+            sessions: InMemorySessionRepository::default(),
+            keys: TokenKeys::random(),
+            user_id: user.id,
+        }
+    }
+
+    fn current_code(secret: &str) -> String {
+        totp(secret, "")
+            .unwrap()
+            .generate(now().timestamp() as u64)
+    }
+
+    async fn mock_enrolled(db: &MockDb) -> (String, Vec<String>) {
+        let enrollment = start_totp_enrollment_service(
+            &db.users,
+            &db.two_factor,
+            db.user_id,
+        )
+        .await
+        .unwrap();
+        let codes = confirm_totp_enrollment_service(
+            &db.two_factor,
+            db.user_id,
+            &current_code(&enrollment.secret),
+        )
+        .await
+        .unwrap();
+
+        (enrollment.secret, codes.recovery_codes)
+    }
+
+    async fn mock_second_step(
+        db: &MockDb,
+        code: &str,
+    ) -> Result<SessionTokens, LibError> {
+        verify_second_factor_service(
+            TwoFactorForm {
+                challenge_token: db
+                    .keys
+                    .mint_challenge(db.user_id, now())?,
+                code: code.to_string(),
+            },
+            &db.users,
+            &db.two_factor,
+            &db.sessions,
+            &db.keys,
+            &mock_throttle(),
+            ClientInfo::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_enrollment() -> miette::Result<()> {
+        let db = mock_db().await;
+        let enrollment = start_totp_enrollment_service(
+            &db.users,
+            &db.two_factor,
+            db.user_id,
+        )
+        .await?;
+        miette::ensure!(
+            enrollment
+                .otpauth_uri
+                .starts_with("otpauth://totp/Sight%20Agent:user%40email.com?")
+                && enrollment
+                    .otpauth_uri
+                    .contains(&enrollment.secret),
+            "Error: unexpected URI {}",
+            enrollment.otpauth_uri
+        );
+        let status =
+            two_factor_status_service(&db.two_factor, db.user_id).await?;
+        miette::ensure!(!status.enabled, "Error: enabled before confirmation");
+
+        let wrong = confirm_totp_enrollment_service(
+            &db.two_factor,
+            db.user_id,
+            "000000",
+        )
+        .await;
+        miette::ensure!(
+            matches!(wrong, Err(LibError::InvalidTwoFactorCode)),
+            "Error: wrong code confirmed the enrollment"
+        );
+        let codes = confirm_totp_enrollment_service(
+            &db.two_factor,
+            db.user_id,
+            &current_code(&enrollment.secret),
+        )
+        .await?;
+        let status =
+            two_factor_status_service(&db.two_factor, db.user_id).await?;
+        miette::ensure!(
+            status.enabled && codes.recovery_codes.len() == RECOVERY_CODE_COUNT,
+            "Error: 2FA not enabled"
+        );
+
+        let again = start_totp_enrollment_service(
+            &db.users,
+            &db.two_factor,
+            db.user_id,
+        )
+        .await;
+        miette::ensure!(
+            matches!(again, Err(LibError::TwoFactorAlreadyEnabled)),
+            "Error: enabled secret replaced"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_second_step_with_totp() -> miette::Result<()> {
+        let db = mock_db().await;
+        let (secret, _) = mock_enrolled(&db).await;
+        // the code that confirmed the enrollment can't be replayed
+        let replay = mock_second_step(&db, &current_code(&secret)).await;
+        miette::ensure!(
+            matches!(replay, Err(LibError::InvalidTwoFactorCode)),
+            "Error: TOTP code replayed"
+        );
+
+        let next =
+            totp(&secret, "")?.generate(now().timestamp() as u64 + TOTP_STEP);
+        let tokens = mock_second_step(&db, &next).await?;
+        miette::ensure!(
+            db.keys
+                .decode_access(&tokens.access_token)?
+                .sub
+                == db.user_id,
+            "Error: session opened for another user"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_second_step_with_recovery_code() -> miette::Result<()> {
+        let db = mock_db().await;
+        let (_, codes) = mock_enrolled(&db).await;
+        let typed = format!(" {} ", codes[0].to_uppercase().replace('-', ""));
+
+        mock_second_step(&db, &typed).await?;
+        let reused = mock_second_step(&db, &codes[0]).await;
+        miette::ensure!(
+            matches!(reused, Err(LibError::InvalidTwoFactorCode)),
+            "Error: recovery code reused"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_challenge_is_not_an_access_token() -> miette::Result<()> {
+        let db = mock_db().await;
+        let challenge = db
+            .keys
+            .mint_challenge(db.user_id, now())?;
+        let access = db
+            .keys
+            .mint_access(db.user_id, Uuid::new_v4(), now())?;
+
+        miette::ensure!(
+            matches!(
+                db.keys.decode_access(&challenge),
+                Err(LibError::InvalidToken)
+            ) && matches!(
+                db.keys.decode_challenge(&access),
+                Err(LibError::InvalidToken)
+            ),
+            "Error: token accepted for another purpose"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disable_requires_password() -> miette::Result<()> {
+        let db = mock_db().await;
+        mock_enrolled(&db).await;
+        let wrong = disable_two_factor_service(
+            PasswordForm {
+                password: "wrong_password".to_string(),
+            },
+            &db.users,
+            &db.two_factor,
+            &mock_hashing(),
+            db.user_id,
+        )
+        .await;
+        miette::ensure!(
+            matches!(wrong, Err(LibError::InvalidCredentials)),
+            "Error: 2FA disabled without the password"
+        );
+
+        disable_two_factor_service(
+            PasswordForm {
+                password: "valid_password".to_string(),
+            },
+            &db.users,
+            &db.two_factor,
+            &mock_hashing(),
+            db.user_id,
+        )
+        .await?;
+        let status =
+            two_factor_status_service(&db.two_factor, db.user_id).await?;
+        miette::ensure!(!status.enabled, "Error: 2FA still enabled");
+        Ok(())
+    }
+}
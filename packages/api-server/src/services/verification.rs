@@ -0,0 +1,253 @@
+// external crates
+use chrono::Duration;
+use std::collections::BTreeMap;
+use tracing::instrument;
+use uuid::Uuid;
+// local modules
+use super::{hash_token, random_token, user_catalog, Email, MailLinks, Mailer};
+use crate::repository::{
+    now, TokenPurpose, User, UserRepository, UserToken, UserTokenRepository,
+};
+use api_shared::prelude::LibError;
+
+/// How long a verification link stays valid.
+pub const VERIFY_EMAIL_TTL_HOURS: i64 = 24;
+
+/// Mails `user` a link that verifies their email when opened.
+#[instrument(skip_all)]
+pub async fn send_verification_service(
+    tokens: &dyn UserTokenRepository,
+    mailer: &dyn Mailer,
+    links: &MailLinks,
+    user: &User,
+) -> Result<(), LibError> {
+    let token = issue_user_token(
+        tokens,
+        user.id,
+        TokenPurpose::VerifyEmail,
+        Duration::hours(VERIFY_EMAIL_TTL_HOURS),
+    )
+    .await?;
+
+    let catalog = user_catalog(user);
+    let args = BTreeMap::from([
+        ("username".to_string(), user.username.clone()),
+        ("link".to_string(), links.verify_email(&token)),
+        ("hours".to_string(), VERIFY_EMAIL_TTL_HOURS.to_string()),
+    ]);
+
+    mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: catalog.message("verify_email.subject", &args),
+            body: catalog.message("verify_email.body", &args),
+        })
+        .await
+}
+
+/// Sends a new verification link. Unknown and already verified emails are
+/// silently ignored, so callers can't probe which emails are registered.
+#[instrument(skip_all)]
+pub async fn resend_verification_service(
+    users: &dyn UserRepository,
+    tokens: &dyn UserTokenRepository,
+    mailer: &dyn Mailer,
+    links: &MailLinks,
+    email: &str,
+) -> Result<(), LibError> {
+    let email = email.trim().to_lowercase();
+    match users.find_by_email(&email).await? {
+        Some(user) if user.email_verified_at.is_none() => {
+            send_verification_service(tokens, mailer, links, &user).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Marks the email of the token's owner as verified. The token works once.
+#[instrument(skip_all)]
+pub async fn verify_email_service(
+    users: &dyn UserRepository,
+    tokens: &dyn UserTokenRepository,
+    token: &str,
+) -> Result<(), LibError> {
+    let now = now();
+    let token = tokens
+        .consume(&hash_token(token), TokenPurpose::VerifyEmail, now)
+        .await?
+        .ok_or(LibError::InvalidToken)?;
+
+    users
+        .mark_email_verified(token.user_id, now)
+        .await
+}
+
+/// Stores a new single-use token for `user_id` and returns its plaintext,
+/// which only ever leaves the server inside an email.
+pub(crate) async fn issue_user_token(
+    tokens: &dyn UserTokenRepository,
+    user_id: Uuid,
+    purpose: TokenPurpose,
+    ttl: Duration,
+) -> Result<String, LibError> {
+    let now = now();
+    let token = random_token();
+    tokens
+        .insert(&UserToken {
+            token_hash: hash_token(&token),
+            user_id,
+            purpose,
+            created_at: now,
+            expires_at: now + ttl,
+            used_at: None,
+        })
+        .await?;
+
+    Ok(token)
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        repository::{InMemoryUserRepository, InMemoryUserTokenRepository},
+        services::{
+            create_user_service, password::tests::mock_hashing, InMemoryMailer,
+        },
+    };
+    use api_shared::dto::UserForm;
+
+    async fn mock_user(users: &InMemoryUserRepository) -> User {
+        create_user_service(
+            UserForm {
+                email: "user@email.com".to_string(),
+                password: "valid_password".to_string(),
+                username: "username".to_string(),
+            },
+            users,
+            &mock_hashing(),
+        )
+        .await
+        .unwrap()
+    }
+
+    /// The token is the last query parameter of the mailed link.
+    pub(crate) fn mailed_token(mailer: &InMemoryMailer) -> String {
+        let email = mailer.sent().pop().unwrap();
+        let (_, rest) = email.body.split_once("token=").unwrap();
+        rest.split_whitespace()
+            .next()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_verify_email() -> miette::Result<()> {
+        let users = InMemoryUserRepository::default();
+        let tokens = InMemoryUserTokenRepository::default();
+        let mailer = InMemoryMailer::default();
+        let user = mock_user(&users).await;
+        send_verification_service(
+            &tokens,
+            &mailer,
+            &MailLinks::default(),
+            &user,
+        )
+        .await?;
+        let token = mailed_token(&mailer);
+
+        verify_email_service(&users, &tokens, &token).await?;
+        let stored = users.find_by_id(user.id).await?;
+        miette::ensure!(
+            stored.is_some_and(|u| u.email_verified_at.is_some()),
+            "Error: email not verified"
+        );
+        let replay = verify_email_service(&users, &tokens, &token).await;
+        miette::ensure!(
+            matches!(replay, Err(LibError::InvalidToken)),
+            "Error: verification token used twice"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verification_in_users_language() -> miette::Result<()> {
+        let users = InMemoryUserRepository::default();
+        let tokens = InMemoryUserTokenRepository::default();
+        let mailer = InMemoryMailer::default();
+        let mut user = mock_user(&users).await;
+        let links = MailLinks::default();
+
+        send_verification_service(&tokens, &mailer, &links, &user).await?;
+        user.locale = Some("en".to_string());
+        send_verification_service(&tokens, &mailer, &links, &user).await?;
+
+        let sent = mailer.sent();
+        miette::ensure!(
+            sent[0].subject == "Confirme seu email"
+                && sent[1].subject == "Confirm your email"
+                && sent[1]
+                    .body
+                    .starts_with("Hello, username!")
+                && sent[1]
+                    .body
+                    .contains("expires in 24 hours"),
+            "Error: unexpected emails {sent:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resend_verification() -> miette::Result<()> {
+        let users = InMemoryUserRepository::default();
+        let tokens = InMemoryUserTokenRepository::default();
+        let mailer = InMemoryMailer::default();
+        let user = mock_user(&users).await;
+        let links = MailLinks::default();
+
+        resend_verification_service(
+            &users,
+            &tokens,
+            &mailer,
+            &links,
+            "unknown@email.com",
+        )
+        .await?;
+        miette::ensure!(
+            mailer.sent().is_empty(),
+            "Error: mailed an unknown address"
+        );
+
+        resend_verification_service(
+            &users,
+            &tokens,
+            &mailer,
+            &links,
+            "User@Email.com",
+        )
+        .await?;
+        miette::ensure!(
+            mailer.sent().len() == 1,
+            "Error: verification link not mailed"
+        );
+
+        users
+            .mark_email_verified(user.id, now())
+            .await?;
+        resend_verification_service(
+            &users,
+            &tokens,
+            &mailer,
+            &links,
+            "user@email.com",
+        )
+        .await?;
+        miette::ensure!(
+            mailer.sent().len() == 1,
+            "Error: verified account mailed again"
+        );
+        Ok(())
+    }
+}
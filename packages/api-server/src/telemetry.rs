@@ -0,0 +1,196 @@
+// external crates
+use axum::{
+    extract::MatchedPath,
+    http::{Request, Response},
+};
+use std::time::Duration;
+use tower_http::{
+    request_id::RequestId,
+    trace::{MakeSpan, OnResponse},
+};
+use tracing::{field::Empty, Span, Subscriber};
+use tracing_subscriber::{fmt::MakeWriter, util::SubscriberInitExt, EnvFilter};
+// local modules
+use crate::config::{LogConfig, LogFormat};
+
+/// Sends the events of `config` to stdout, for the rest of the process.
+pub fn init(config: &LogConfig) {
+    subscriber(config, std::io::stdout).init();
+}
+
+/// Formats the events kept by `config.filter` into `writer`.
+pub fn subscriber<W>(
+    config: &LogConfig,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    // checked when loading the configuration
+    let filter = EnvFilter::new(&config.filter);
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+
+    match config.format {
+        LogFormat::Pretty => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+/// The span every request runs in, see `routes::router`.
+///
+/// Besides what the request says, it gets the caller's `user_id` from
+/// `AuthUser`, the diagnostic code of a `LibError` answer in `error`, and
+/// the `status` and `latency_ms` once answered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        // unmatched requests are logged by path, queries may hold tokens
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(request.uri().path(), MatchedPath::as_str);
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok());
+
+        tracing::info_span!(
+            "request",
+            method = %request.method(),
+            route,
+            request_id,
+            user_id = Empty,
+            error = Empty,
+            status = Empty,
+            latency_ms = Empty,
+        )
+    }
+}
+
+impl<B> OnResponse<B> for RequestSpan {
+    fn on_response(
+        self,
+        response: &Response<B>,
+        latency: Duration,
+        span: &Span,
+    ) {
+        span.record("status", response.status().as_u16());
+        span.record("latency_ms", latency.as_millis() as u64);
+        tracing::info!(parent: span, "answered");
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::{router, tests::mock_state};
+    use axum::{body::Body, http::header::HeaderName};
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    /// Collects what the subscriber writes.
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap()
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_id() -> miette::Result<()> {
+        let x_request_id = HeaderName::from_static("x-request-id");
+        let app = router(mock_state());
+
+        let given = app
+            .clone()
+            .oneshot(
+                Request::get("/")
+                    .header(&x_request_id, "from-the-caller")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let generated = app
+            .oneshot(
+                Request::get("/")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let generated = generated.headers()[&x_request_id]
+            .to_str()
+            .unwrap();
+        miette::ensure!(
+            given.headers()[&x_request_id] == "from-the-caller"
+                && Uuid::parse_str(generated).is_ok(),
+            "Error: request ids not propagated, generated {generated:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_span() -> miette::Result<()> {
+        let capture = Capture::default();
+        let config = LogConfig {
+            format: LogFormat::Json,
+            filter: "info".to_string(),
+        };
+        let writer = capture.clone();
+        let _guard =
+            tracing::subscriber::set_default(subscriber(&config, move || {
+                writer.clone()
+            }));
+
+        router(mock_state())
+            .oneshot(
+                Request::get("/users/me")
+                    .header("x-request-id", "abc123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let logs =
+            String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let answered = logs
+            .lines()
+            .find(|line| line.contains("answered"))
+            .unwrap_or_default();
+        miette::ensure!(
+            [
+                r#""route":"/users/me""#,
+                r#""request_id":"abc123""#,
+                r#""status":401"#,
+                r#""error":"LibError::MissingCredentials""#,
+            ]
+            .iter()
+            .all(|field| answered.contains(field)),
+            "Error: unexpected logs {logs}"
+        );
+        Ok(())
+    }
+}
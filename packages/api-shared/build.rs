@@ -0,0 +1,39 @@
+//! Embeds every message catalog found in `locales/`, so a language is
+//! added by dropping a `<tag>.toml` file there.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    let dir =
+        Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("locales");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut catalogs: Vec<(String, String)> = fs::read_dir(&dir)
+        .expect("locales/ folder is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "toml")
+        })
+        .map(|path| {
+            let tag = path
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            (tag, path.display().to_string())
+        })
+        .collect();
+    catalogs.sort();
+
+    let entries: String = catalogs
+        .iter()
+        .map(|(tag, path)| format!("    ({tag:?}, include_str!({path:?})),\n"))
+        .collect();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("catalogs.rs");
+    fs::write(
+        out,
+        format!("static CATALOG_FILES: &[(&str, &str)] = &[\n{entries}];\n"),
+    )
+    .unwrap();
+}
@@ -0,0 +1,544 @@
+// Only built with the `client` feature. reqwest sends the requests through
+// the browser's fetch under wasm32, for app-ui, and through hyper
+// elsewhere, for tools and tests.
+
+use std::fmt;
+
+use chrono::NaiveDate;
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use crate::{
+    dto::{
+        CodeForm, EmailForm, HealthReport, LocaleForm, Occurrence,
+        OccurrencePatch, OccurrenceQuery, OidcProviderList, PasswordForm,
+        PasswordResetForm, RecoveryCodes, RefreshTokenForm, SessionInfo,
+        SessionTokens, SignInForm, SignInOutcome, Tag, TagPatch, Task,
+        TaskForm, TaskPatch, TaskQuery, TokenQuery, TotpEnrollment,
+        TwoFactorForm, TwoFactorStatus, UserForm, UserProfile,
+    },
+    error::LibError,
+    problem::ProblemDetails,
+};
+
+/// Client of api-server, with one method per route.
+///
+/// Requests carry the access token as a bearer token once one is set. In
+/// the browser they also carry the session cookies, so app-ui doesn't
+/// need to handle tokens at all.
+///
+/// Error responses are decoded back into the `LibError` the server failed
+/// with, see `LibError::from_problem`.
+#[derive(Clone)]
+pub struct ApiClient {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+// keeps the access token out of logs and panics
+impl fmt::Debug for ApiClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiClient")
+            .field("base_url", &self.base_url)
+            .field(
+                "token",
+                &self
+                    .token
+                    .as_ref()
+                    .map(|_| "[redacted]"),
+            )
+            .finish()
+    }
+}
+
+impl ApiClient {
+    /// Client of the server at `base_url`, e.g. `http://127.0.0.1:3030`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url
+                .into()
+                .trim_end_matches('/')
+                .to_string(),
+            token: None,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// The same client, sending `token` along with every request.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Replaces the access token, e.g. with the one of a refresh.
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// `POST /users`: registers an account, whose email must then be
+    /// verified before signing in.
+    pub async fn sign_up(&self, form: &UserForm) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::POST, "/users")
+                .json(form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `GET /users/me`
+    pub async fn profile(&self) -> Result<UserProfile, LibError> {
+        self.json(self.request(Method::GET, "/users/me"))
+            .await
+    }
+
+    /// `PUT /users/me/locale`: saves the language of the user's messages.
+    pub async fn update_locale(
+        &self,
+        form: &LocaleForm,
+    ) -> Result<UserProfile, LibError> {
+        self.json(
+            self.request(Method::PUT, "/users/me/locale")
+                .json(form),
+        )
+        .await
+    }
+
+    /// `GET /users/verify`: verifies the email with the token of the
+    /// mailed link.
+    pub async fn verify_email(
+        &self,
+        query: &TokenQuery,
+    ) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::GET, "/users/verify")
+                .query(query),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `POST /users/verification`: mails a new verification link.
+    pub async fn resend_verification(
+        &self,
+        form: &EmailForm,
+    ) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::POST, "/users/verification")
+                .json(form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `POST /users/password/forgot`: mails a password reset link.
+    pub async fn forgot_password(
+        &self,
+        form: &EmailForm,
+    ) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::POST, "/users/password/forgot")
+                .json(form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `POST /users/password/reset`: chooses a new password with the token
+    /// of a mailed reset link.
+    pub async fn reset_password(
+        &self,
+        form: &PasswordResetForm,
+    ) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::POST, "/users/password/reset")
+                .json(form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `POST /sessions`
+    pub async fn sign_in(
+        &self,
+        form: &SignInForm,
+    ) -> Result<SignInOutcome, LibError> {
+        let response = self
+            .send(
+                self.request(Method::POST, "/sessions")
+                    .json(form),
+            )
+            .await?;
+
+        Ok(match response.status() {
+            StatusCode::ACCEPTED => {
+                SignInOutcome::TwoFactorRequired(response.json().await?)
+            }
+            _ => SignInOutcome::SignedIn(response.json().await?),
+        })
+    }
+
+    /// `POST /sessions/two_factor`: completes a sign-in with a TOTP or
+    /// recovery code.
+    pub async fn verify_two_factor(
+        &self,
+        form: &TwoFactorForm,
+    ) -> Result<SessionTokens, LibError> {
+        self.json(
+            self.request(Method::POST, "/sessions/two_factor")
+                .json(form),
+        )
+        .await
+    }
+
+    /// `POST /sessions/refresh`
+    pub async fn refresh(
+        &self,
+        form: &RefreshTokenForm,
+    ) -> Result<SessionTokens, LibError> {
+        self.json(
+            self.request(Method::POST, "/sessions/refresh")
+                .json(form),
+        )
+        .await
+    }
+
+    /// `DELETE /sessions`: signs out the session of the refresh token.
+    pub async fn sign_out(
+        &self,
+        form: &RefreshTokenForm,
+    ) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::DELETE, "/sessions")
+                .json(form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `GET /sessions`: the devices where the user is signed in.
+    pub async fn list_sessions(&self) -> Result<Vec<SessionInfo>, LibError> {
+        self.json(self.request(Method::GET, "/sessions"))
+            .await
+    }
+
+    /// `DELETE /sessions/:id`: signs out one of the user's devices.
+    pub async fn revoke_session(&self, id: Uuid) -> Result<(), LibError> {
+        self.send(self.request(Method::DELETE, &format!("/sessions/{id}")))
+            .await?;
+        Ok(())
+    }
+
+    /// `GET /users/me/two_factor`
+    pub async fn two_factor_status(&self) -> Result<TwoFactorStatus, LibError> {
+        self.json(self.request(Method::GET, "/users/me/two_factor"))
+            .await
+    }
+
+    /// `POST /users/me/two_factor`: generates a TOTP secret, only enabled
+    /// once confirmed with a code.
+    pub async fn start_two_factor(&self) -> Result<TotpEnrollment, LibError> {
+        self.json(self.request(Method::POST, "/users/me/two_factor"))
+            .await
+    }
+
+    /// `POST /users/me/two_factor/confirm`: enables 2FA, returning the
+    /// recovery codes. They can't be fetched again.
+    pub async fn confirm_two_factor(
+        &self,
+        form: &CodeForm,
+    ) -> Result<RecoveryCodes, LibError> {
+        self.json(
+            self.request(Method::POST, "/users/me/two_factor/confirm")
+                .json(form),
+        )
+        .await
+    }
+
+    /// `DELETE /users/me/two_factor`
+    pub async fn disable_two_factor(
+        &self,
+        form: &PasswordForm,
+    ) -> Result<(), LibError> {
+        self.send(
+            self.request(Method::DELETE, "/users/me/two_factor")
+                .json(form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// `GET /tasks`: the user's tasks, newest first.
+    pub async fn list_tasks(
+        &self,
+        query: &TaskQuery,
+    ) -> Result<Vec<Task>, LibError> {
+        self.json(
+            self.request(Method::GET, "/tasks")
+                .query(query),
+        )
+        .await
+    }
+
+    /// `POST /tasks`
+    pub async fn create_task(&self, form: &TaskForm) -> Result<Task, LibError> {
+        self.json(
+            self.request(Method::POST, "/tasks")
+                .json(form),
+        )
+        .await
+    }
+
+    /// `GET /tasks/:id`
+    pub async fn task(&self, id: Uuid) -> Result<Task, LibError> {
+        self.json(self.request(Method::GET, &format!("/tasks/{id}")))
+            .await
+    }
+
+    /// `PATCH /tasks/:id`: changes the fields set in `patch`.
+    pub async fn update_task(
+        &self,
+        id: Uuid,
+        patch: &TaskPatch,
+    ) -> Result<Task, LibError> {
+        self.json(
+            self.request(Method::PATCH, &format!("/tasks/{id}"))
+                .json(patch),
+        )
+        .await
+    }
+
+    /// `DELETE /tasks/:id`
+    pub async fn delete_task(&self, id: Uuid) -> Result<(), LibError> {
+        self.send(self.request(Method::DELETE, &format!("/tasks/{id}")))
+            .await?;
+        Ok(())
+    }
+
+    /// `GET /occurrences`: the days the user's open recurring tasks
+    /// happen on, by date.
+    pub async fn list_occurrences(
+        &self,
+        query: &OccurrenceQuery,
+    ) -> Result<Vec<Occurrence>, LibError> {
+        self.json(
+            self.request(Method::GET, "/occurrences")
+                .query(query),
+        )
+        .await
+    }
+
+    /// `PATCH /tasks/:id/occurrences/:date`: marks one occurrence done or
+    /// not.
+    pub async fn update_occurrence(
+        &self,
+        task_id: Uuid,
+        date: NaiveDate,
+        patch: &OccurrencePatch,
+    ) -> Result<Occurrence, LibError> {
+        self.json(
+            self.request(
+                Method::PATCH,
+                &format!("/tasks/{task_id}/occurrences/{date}"),
+            )
+            .json(patch),
+        )
+        .await
+    }
+
+    /// `GET /tags`: the user's tags, most used first.
+    pub async fn list_tags(&self) -> Result<Vec<Tag>, LibError> {
+        self.json(self.request(Method::GET, "/tags"))
+            .await
+    }
+
+    /// `PATCH /tags/:name`
+    pub async fn update_tag(
+        &self,
+        name: &str,
+        patch: &TagPatch,
+    ) -> Result<Tag, LibError> {
+        self.json(
+            self.request(
+                Method::PATCH,
+                &format!("/tags/{}", path_segment(name)),
+            )
+            .json(patch),
+        )
+        .await
+    }
+
+    /// `GET /auth/oidc`: the identity providers the server accepts
+    /// sign-ins from.
+    pub async fn oidc_providers(&self) -> Result<OidcProviderList, LibError> {
+        self.json(self.request(Method::GET, "/auth/oidc"))
+            .await
+    }
+
+    /// `GET /auth/oidc/:provider` starts a sign-in at a provider. It's a
+    /// page navigation, not a fetch: the provider's sign-in page takes over
+    /// and sends the browser back to the callback route.
+    pub fn oidc_login_url(&self, provider: &str) -> String {
+        format!("{}/auth/oidc/{provider}", self.base_url)
+    }
+
+    /// `GET /healthz`: whether the server process runs.
+    pub async fn health(&self) -> Result<HealthReport, LibError> {
+        self.json(self.request(Method::GET, "/healthz"))
+            .await
+    }
+
+    /// `GET /readyz`: whether the server can take requests. An unready
+    /// server answers 503 with the report, returned as well.
+    pub async fn readiness(&self) -> Result<HealthReport, LibError> {
+        let response = self
+            .request(Method::GET, "/readyz")
+            .send()
+            .await?;
+        if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(response.json().await?);
+        }
+
+        Ok(check_status(response)
+            .await?
+            .json()
+            .await?)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{path}", self.base_url));
+        // browsers only send the session cookies to another origin when
+        // asked to
+        #[cfg(target_arch = "wasm32")]
+        let request = request.fetch_credentials_include();
+
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends the request, failing with the server's error on any status
+    /// but a success.
+    async fn send(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Response, LibError> {
+        check_status(request.send().await?).await
+    }
+
+    async fn json<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, LibError> {
+        Ok(self.send(request).await?.json().await?)
+    }
+}
+
+/// `value` as a single path segment: everything but the unreserved
+/// characters of RFC 3986 is percent-encoded.
+fn path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~' => char::from(byte).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The response itself on a success, else the server's error.
+async fn check_status(response: Response) -> Result<Response, LibError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    let body = response.bytes().await?;
+
+    Err(decode_error(status, retry_after, &body))
+}
+
+/// The `LibError` of an error response. Bodies that aren't problem details,
+/// e.g. from a proxy in front of the server, only keep the status.
+fn decode_error(
+    status: StatusCode,
+    retry_after: Option<u64>,
+    body: &[u8],
+) -> LibError {
+    match serde_json::from_slice::<ProblemDetails>(body) {
+        Ok(problem) => LibError::from_problem(problem, retry_after),
+        Err(_) => LibError::internal(std::io::Error::other(format!(
+            "unexpected response {status}"
+        ))),
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n;
+
+    #[test]
+    fn test_decode_error() -> miette::Result<()> {
+        let body = |error: LibError| {
+            serde_json::to_vec(&error.to_problem(i18n::default_catalog()))
+                .unwrap()
+        };
+
+        let throttled = decode_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(30),
+            &body(LibError::TooManyAttempts { retry_after: 30 }),
+        );
+        let taken = decode_error(
+            StatusCode::CONFLICT,
+            None,
+            &body(LibError::EmailTaken),
+        );
+        let proxied =
+            decode_error(StatusCode::BAD_GATEWAY, None, b"<html>502</html>");
+
+        miette::ensure!(
+            matches!(throttled, LibError::TooManyAttempts { retry_after: 30 })
+                && matches!(taken, LibError::EmailTaken)
+                && matches!(proxied, LibError::Internal(_)),
+            "Error: unexpected errors {throttled:?} {taken:?} {proxied:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_not_logged() -> miette::Result<()> {
+        let client =
+            ApiClient::new("http://localhost:3030/").with_token("secret-token");
+
+        miette::ensure!(
+            client.base_url() == "http://localhost:3030"
+                && !format!("{client:?}").contains("secret-token"),
+            "Error: unexpected client {client:?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+// Bodies, query strings and answers of the api-server routes. app-ui sends
+// and reads the very same types, so both ends agree on every payload.
+
+mod health;
+pub use health::*;
+
+mod occurrences;
+pub use occurrences::*;
+
+mod oidc;
+pub use oidc::*;
+
+mod sessions;
+pub use sessions::*;
+
+mod tags;
+pub use tags::*;
+
+mod tasks;
+pub use tasks::*;
+
+mod two_factor;
+pub use two_factor::*;
+
+mod users;
+pub use users::*;
@@ -0,0 +1,61 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::recurrence::RecurrenceRule;
+
+/// How a task repeats, making it a habit done once per occurrence.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurrence {
+    /// `RRULE` value, see `recurrence::RecurrenceRule::parse`
+    pub rule: String,
+    /// first day the rule may fall on, anchoring its intervals
+    pub starts_on: NaiveDate,
+    /// days skipped although the rule falls on them
+    #[serde(default)]
+    pub exceptions: Vec<NaiveDate>,
+}
+
+impl Recurrence {
+    /// Days from `from` to `to`, both included, the task happens on.
+    /// Empty when the rule doesn't parse.
+    pub fn occurrences(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Vec<NaiveDate> {
+        let Some(rule) = RecurrenceRule::parse(&self.rule) else {
+            return Vec::new();
+        };
+
+        rule.dates_from(self.starts_on, from)
+            .take_while(|date| *date <= to)
+            .filter(|date| !self.exceptions.contains(date))
+            .collect()
+    }
+}
+
+/// A day a recurring task happens on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Occurrence {
+    pub task_id: Uuid,
+    pub date: NaiveDate,
+    pub title: String,
+    /// names of the task's `Tag`s
+    pub tags: Vec<String>,
+    /// when this occurrence was done, the others having their own
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Query string of `GET /occurrences`, both days included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OccurrenceQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+/// Body of `PATCH /tasks/:id/occurrences/:date`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OccurrencePatch {
+    pub done: bool,
+}
@@ -0,0 +1,29 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Names of the providers, for the app to offer a button for each.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OidcProviderList {
+    pub providers: Vec<String>,
+}
+
+/// Query string the provider redirects back with.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OidcCallback {
+    pub state: String,
+    pub code: Option<String>,
+    /// set instead of `code` when the user or the provider refused
+    pub error: Option<String>,
+}
+
+// keeps the authorization code out of logs and panics
+impl fmt::Debug for OidcCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OidcCallback")
+            .field("state", &"[redacted]")
+            .field("code", &self.code.as_ref().map(|_| "[redacted]"))
+            .field("error", &self.error)
+            .finish()
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Body of `POST /sessions`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SignInForm {
+    pub email: String,
+    pub password: String,
+}
+
+// keeps the plaintext password out of logs and panics
+impl fmt::Debug for SignInForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignInForm")
+            .field("email", &self.email)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+/// Body of the routes taking a refresh token. Browsers leave it out and
+/// send the refresh cookie instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RefreshTokenForm {
+    pub refresh_token: Option<String>,
+}
+
+/// Credentials handed to a client after signing in or refreshing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTokens {
+    /// id of the new session, as listed in `SessionInfo`
+    pub session_id: Uuid,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// access token lifetime, in seconds
+    pub expires_in: i64,
+    /// refresh token lifetime, in seconds
+    pub refresh_expires_in: i64,
+}
+
+/// Answer of a sign-in on an account with 2FA, to complete at
+/// `POST /sessions/two_factor`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    /// challenge lifetime, in seconds
+    pub expires_in: i64,
+}
+
+/// Result of `POST /sessions`: `200 OK` with the tokens, or `202 Accepted`
+/// with a challenge when the account has 2FA enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignInOutcome {
+    SignedIn(SessionTokens),
+    /// The sign-in completes by sending a code along with this challenge
+    /// to `POST /sessions/two_factor`.
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+/// A signed-in device as listed to its owner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    /// whether this is the session making the request
+    pub current: bool,
+}
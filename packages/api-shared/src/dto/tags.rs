@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// Colors given to new tags, picked by name so a tag keeps its color
+/// across devices until the user changes it.
+pub const TAG_COLORS: &[&str] = &[
+    "#ef4444", "#f97316", "#eab308", "#22c55e", "#14b8a6", "#3b82f6",
+    "#8b5cf6", "#ec4899",
+];
+
+/// A label of the user's tasks. Names are unique per user, compared
+/// case-insensitively, and keep the spelling they were first entered with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    pub name: String,
+    /// `#rrggbb`
+    pub color: String,
+    /// how many of the user's tasks carry it, archived ones included
+    pub usage_count: u32,
+}
+
+impl Tag {
+    /// What names are compared by.
+    pub fn key(name: &str) -> String {
+        name.trim().to_lowercase()
+    }
+
+    /// Color of a new tag named `name`, one of `TAG_COLORS`.
+    pub fn default_color(name: &str) -> &'static str {
+        let sum = Self::key(name)
+            .bytes()
+            .fold(0usize, |sum, byte| sum.wrapping_add(byte as usize));
+
+        TAG_COLORS[sum % TAG_COLORS.len()]
+    }
+}
+
+/// Body of `PATCH /tags/:name`, changing only the fields it holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+}
+
+/// What an authenticator app needs to be set up. `otpauth_uri` is also the
+/// payload of the QR code shown on the Settings page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Codes shown once, when 2FA is enabled. Each works a single time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Body of the routes taking a TOTP code, or a recovery code where noted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CodeForm {
+    pub code: String,
+}
+
+/// Second step of a sign-in on an account with 2FA.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TwoFactorForm {
+    /// from `TwoFactorChallenge`
+    pub challenge_token: String,
+    /// a TOTP code or an unused recovery code
+    pub code: String,
+}
+
+/// Disabling 2FA asks for the password again, so a stolen session alone
+/// can't remove the second factor.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PasswordForm {
+    pub password: String,
+}
+
+// keep codes and passwords out of logs and panics
+impl fmt::Debug for CodeForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CodeForm")
+            .field("code", &"[redacted]")
+            .finish()
+    }
+}
+
+impl fmt::Debug for TwoFactorForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TwoFactorForm")
+            .field("challenge_token", &"[redacted]")
+            .field("code", &"[redacted]")
+            .finish()
+    }
+}
+
+impl fmt::Debug for PasswordForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordForm")
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Body of `POST /users`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserForm {
+    pub email: String,
+    pub password: String,
+    pub username: String,
+}
+
+// keeps the plaintext password out of logs and panics
+impl fmt::Debug for UserForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserForm")
+            .field("email", &self.email)
+            .field("password", &"[redacted]")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+/// Public view of an account, safe to send to its owner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+    pub email_verified: bool,
+    /// language picked for messages, `None` to follow the browser
+    pub locale: Option<String>,
+}
+
+/// Body of `PUT /users/me/locale`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocaleForm {
+    /// tag of an available catalog, `None` to follow `Accept-Language`
+    pub locale: Option<String>,
+}
+
+/// Body of the routes asking for a new verification or reset link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailForm {
+    pub email: String,
+}
+
+/// Body of `POST /users/password/reset`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PasswordResetForm {
+    pub token: String,
+    pub password: String,
+}
+
+// keeps the plaintext password out of logs and panics
+impl fmt::Debug for PasswordResetForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordResetForm")
+            .field("token", &"[redacted]")
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+/// Query string of the link mailed to verify an email.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenQuery {
+    pub token: String,
+}
@@ -1,80 +1,477 @@
-use std::{error::Error, fmt};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    error::Error,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use http::StatusCode;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
 
-use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
+use crate::{
+    i18n::{self, Catalog, ErrorText},
+    problem::ProblemDetails,
 };
 
-/// # `Error` Library
+/// A failure of the server itself (database, IO, serialization...), kept
+/// whole for the logs but only ever shown to clients by its `id`.
 ///
-/// This library helps to diagnose and implement errors
-/// using `miette` and `thiserror`
+/// Like `anyhow::Error` it doesn't implement `Error` itself, which lets any
+/// error convert into it.
+pub struct BoxedError {
+    /// random id, sent to the client and logged next to the error chain
+    pub id: String,
+    pub error: Box<dyn Error + Send + Sync>,
+}
+
+impl BoxedError {
+    /// The error and each of its sources, outermost first.
+    pub fn chain(&self) -> String {
+        let mut chain = self.error.to_string();
+        let mut source = self.error.source();
+        while let Some(error) = source {
+            chain.push_str(": ");
+            chain.push_str(&error.to_string());
+            source = error.source();
+        }
+        chain
+    }
+}
+
+impl<E: Error + Send + Sync + 'static> From<E> for BoxedError {
+    fn from(error: E) -> Self {
+        Self {
+            id: error_id(),
+            error: Box::new(error),
+        }
+    }
+}
 
-#[derive(Debug, thiserror::Error, miette::Diagnostic)]
-pub struct BoxedError(pub Box<dyn Error + Send + Sync>);
+impl fmt::Debug for BoxedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.id, self.chain())
+    }
+}
 
 impl fmt::Display for BoxedError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Display::fmt(&self.0, f)
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+/// 16 hex digits, unique enough to find one failure in the logs. Hashing
+/// a counter with the std's randomly keyed hasher avoids depending on an
+/// RNG crate.
+fn error_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
+}
+
+/// A form field that failed validation, see `LibError::Validation`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// name of the form field, e.g. `email`
+    pub field: String,
+    /// stable id of the failed rule, e.g. `password.too_short`
+    pub code: String,
+    /// values the message refers to, e.g. `min` for `password.too_short`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    pub message: String,
+}
+
+impl FieldError {
+    /// The failure of rule `code` on `field`. Its message is in the default
+    /// language until the error is localized, see `ProblemDetails::localize`.
+    pub fn new(field: &str, code: &str, params: &[(&str, String)]) -> Self {
+        let params: BTreeMap<String, String> = params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+
+        Self {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: i18n::default_catalog().field(code, &params),
+            params,
+        }
     }
 }
-// Define errors and diagnostics
-#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+
+/// Every failure the API answers with.
+///
+/// Texts live in the catalogs of `locales/`, keyed by the diagnostic code
+/// of each variant: `Display` and `miette` reports use the language of the
+/// process, see `i18n::system_catalog`, and HTTP responses the one of the
+/// caller, see `response::localize_response`.
+#[derive(Debug)]
 pub enum LibError {
-    #[error(transparent)]
-    #[diagnostic(
-        code(LibError::IOError),
-        help("Cheque se os campos digitados são compatíveis")
-    )]
-    IOError(#[from] std::io::Error),
-    #[error("Erro: email já cadastrado")]
-    #[diagnostic(code(LibError::EmailTaken), help("Tente um email diferente"))]
+    Internal(BoxedError),
     EmailTaken,
-    #[error("Erro: nome de usuário já cadastrado")]
-    #[diagnostic(
-        code(LibError::UserTaken),
-        help("Tente um nome de usuário diferente")
-    )]
     UserTaken,
-    #[error("Erro: senha inválida")]
-    #[diagnostic(
-        code(LibError::UserTaken),
-        help("Senhas devem conter no mínimo 8 caracteres")
-    )]
-    PasswordInvalid,
-    #[error("Erro: erro desconhecido")]
-    #[diagnostic(code(LibError::UnknownError), help("Cheque o código fonte"))]
+    Validation {
+        fields: Vec<FieldError>,
+    },
     UnknownError,
+    InvalidCredentials,
+    MissingCredentials,
+    ExpiredCredentials,
+    InvalidToken,
+    NotFound,
+    EmailNotVerified,
+    /// A cookie-authenticated change sent from a page of another site.
+    InvalidOrigin,
+    TooManyAttempts {
+        /// seconds until the next attempt is allowed
+        retry_after: u64,
+    },
+    InvalidTwoFactorCode,
+    TwoFactorAlreadyEnabled,
+    /// An OIDC provider failed, or refused the sign-in. The cause is
+    /// logged by the server, not sent.
+    IdentityProviderError,
+    /// names the missing or invalid setting
+    ConfigError(String),
+    /// The server couldn't listen on `address`, e.g. as another process
+    /// already does.
+    BindError {
+        address: String,
+        reason: String,
+    },
 }
 
-// implementing Axum IntoResponse for custom errors
-impl IntoResponse for LibError {
-    fn into_response(self) -> Response {
-        let body = match self {
-            Self::IOError(err) => format!(
-                "Dados enviados estão incorretos.\n\nErro interno: {err}"
-            ),
-            Self::EmailTaken => "Email já cadastrado".into(),
-            Self::UserTaken => "Usuário já cadastrado".into(),
-            Self::PasswordInvalid => {
-                "Senhas devem conter no mínimo 8 caracteres".into()
+impl LibError {
+    /// Wraps a failure of the server itself, see `BoxedError`.
+    pub fn internal(error: impl Into<BoxedError>) -> Self {
+        Self::Internal(error.into())
+    }
+
+    /// Name of the variant, the diagnostic code without `LibError::`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Internal(_) => "Internal",
+            Self::EmailTaken => "EmailTaken",
+            Self::UserTaken => "UserTaken",
+            Self::Validation { .. } => "Validation",
+            Self::UnknownError => "UnknownError",
+            Self::InvalidCredentials => "InvalidCredentials",
+            Self::MissingCredentials => "MissingCredentials",
+            Self::ExpiredCredentials => "ExpiredCredentials",
+            Self::InvalidToken => "InvalidToken",
+            Self::NotFound => "NotFound",
+            Self::EmailNotVerified => "EmailNotVerified",
+            Self::InvalidOrigin => "InvalidOrigin",
+            Self::TooManyAttempts { .. } => "TooManyAttempts",
+            Self::InvalidTwoFactorCode => "InvalidTwoFactorCode",
+            Self::TwoFactorAlreadyEnabled => "TwoFactorAlreadyEnabled",
+            Self::IdentityProviderError => "IdentityProviderError",
+            Self::ConfigError(_) => "ConfigError",
+            Self::BindError { .. } => "BindError",
+        }
+    }
+
+    /// Values the catalog texts may refer to as `{name}`.
+    pub fn args(&self) -> BTreeMap<String, String> {
+        let arg = |name: &str, value: String| {
+            BTreeMap::from([(name.to_string(), value)])
+        };
+        match self {
+            Self::Internal(internal) => arg("error_id", internal.id.clone()),
+            Self::TooManyAttempts { retry_after } => {
+                arg("retry_after", retry_after.to_string())
             }
-            Self::UnknownError => "Erro desconhecido do servidor".into(),
+            Self::ConfigError(setting) => arg("setting", setting.clone()),
+            Self::BindError { address, reason } => BTreeMap::from([
+                ("address".to_string(), address.clone()),
+                ("reason".to_string(), reason.clone()),
+            ]),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// The error's texts in the language of `catalog`.
+    pub fn text(&self, catalog: &Catalog) -> ErrorText {
+        catalog.error(&format!("LibError::{}", self.kind()), &self.args())
+    }
+
+    /// HTTP status the error is answered with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidCredentials
+            | Self::MissingCredentials
+            | Self::ExpiredCredentials
+            | Self::InvalidToken
+            | Self::InvalidTwoFactorCode => StatusCode::UNAUTHORIZED,
+            Self::EmailNotVerified | Self::InvalidOrigin => {
+                StatusCode::FORBIDDEN
+            }
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::EmailTaken
+            | Self::UserTaken
+            | Self::TwoFactorAlreadyEnabled => StatusCode::CONFLICT,
+            Self::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Internal(_)
+            | Self::UnknownError
+            | Self::ConfigError(_)
+            | Self::BindError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::IdentityProviderError => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// The error as sent to clients, in the language of `catalog`.
+    pub fn to_problem(&self, catalog: &Catalog) -> ProblemDetails {
+        let mut problem = ProblemDetails {
+            problem_type: format!(
+                "urn:sight-agent:error:{}",
+                kebab_case(self.kind())
+            ),
+            title: String::new(),
+            status: self.status().as_u16(),
+            detail: None,
+            code: format!("LibError::{}", self.kind()),
+            help: None,
+            fields: match self {
+                Self::Validation { fields } => fields.clone(),
+                _ => Vec::new(),
+            },
+            error_id: match self {
+                Self::Internal(internal) => Some(internal.id.clone()),
+                _ => None,
+            },
         };
+        problem.localize(catalog, &self.args());
 
-        (StatusCode::BAD_REQUEST, body).into_response()
+        problem
+    }
+
+    /// The error a server answered with, rebuilt from its body. The
+    /// `Retry-After` header of the response gives `retry_after`.
+    ///
+    /// Server failures keep their `error_id` but nothing else, as their
+    /// details aren't sent.
+    pub fn from_problem(
+        problem: ProblemDetails,
+        retry_after: Option<u64>,
+    ) -> Self {
+        let detail = problem.detail.unwrap_or_default();
+        match problem
+            .code
+            .trim_start_matches("LibError::")
+        {
+            "Internal" => Self::Internal(BoxedError {
+                id: problem
+                    .error_id
+                    .unwrap_or_else(error_id),
+                error: Box::new(std::io::Error::other(detail)),
+            }),
+            "EmailTaken" => Self::EmailTaken,
+            "UserTaken" => Self::UserTaken,
+            "Validation" => Self::Validation {
+                fields: problem.fields,
+            },
+            "InvalidCredentials" => Self::InvalidCredentials,
+            "MissingCredentials" => Self::MissingCredentials,
+            "ExpiredCredentials" => Self::ExpiredCredentials,
+            "InvalidToken" => Self::InvalidToken,
+            "NotFound" => Self::NotFound,
+            "EmailNotVerified" => Self::EmailNotVerified,
+            "InvalidOrigin" => Self::InvalidOrigin,
+            "TooManyAttempts" => Self::TooManyAttempts {
+                retry_after: retry_after.unwrap_or_default(),
+            },
+            "InvalidTwoFactorCode" => Self::InvalidTwoFactorCode,
+            "TwoFactorAlreadyEnabled" => Self::TwoFactorAlreadyEnabled,
+            "IdentityProviderError" => Self::IdentityProviderError,
+            "ConfigError" => Self::ConfigError(detail),
+            "BindError" => Self::BindError {
+                address: String::new(),
+                reason: detail,
+            },
+            _ => Self::UnknownError,
+        }
     }
 }
 
-impl From<LibError> for Response {
-    fn from(error: LibError) -> Self {
-        use LibError::*;
-        match error {
-            IOError(err) => IOError(err).into(),
-            EmailTaken => EmailTaken.into(),
-            UserTaken => UserTaken.into(),
-            PasswordInvalid => PasswordInvalid.into(),
-            _ => UnknownError.into(),
+impl fmt::Display for LibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            &self
+                .text(i18n::system_catalog())
+                .message,
+        )
+    }
+}
+
+impl Error for LibError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Internal(internal) => Some(internal.error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for LibError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(format!("LibError::{}", self.kind())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.text(i18n::system_catalog())
+            .help
+            .map(|help| Box::new(help) as Box<dyn fmt::Display>)
+    }
+}
+
+/// `EmailTaken` becomes `email-taken`.
+fn kebab_case(name: &str) -> String {
+    let mut kebab = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            kebab.push('-');
+        }
+        kebab.push(c.to_ascii_lowercase());
+    }
+    kebab
+}
+
+impl From<std::io::Error> for LibError {
+    fn from(error: std::io::Error) -> Self {
+        Self::internal(error)
+    }
+}
+
+impl From<serde_json::Error> for LibError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::internal(error)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl From<sqlx::Error> for LibError {
+    fn from(error: sqlx::Error) -> Self {
+        Self::internal(error)
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<reqwest::Error> for LibError {
+    fn from(error: reqwest::Error) -> Self {
+        Self::internal(error)
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_status_per_variant() -> miette::Result<()> {
+        let cases = [
+            (LibError::EmailTaken, 409),
+            (LibError::UserTaken, 409),
+            (LibError::Validation { fields: Vec::new() }, 422),
+            (LibError::UnknownError, 500),
+            (LibError::from(io::Error::other("disk full")), 500),
+            (LibError::InvalidToken, 401),
+            (LibError::NotFound, 404),
+        ];
+        for (error, status) in cases {
+            miette::ensure!(
+                error
+                    .to_problem(i18n::default_catalog())
+                    .status
+                    == status,
+                "Error: {error:?} not answered with {status}"
+            );
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_problem_body() -> miette::Result<()> {
+        let error = LibError::Validation {
+            fields: vec![FieldError::new("email", "email.invalid", &[])],
+        };
+        let problem = error.to_problem(i18n::default_catalog());
+
+        miette::ensure!(
+            problem.problem_type == "urn:sight-agent:error:validation"
+                && problem.code == "LibError::Validation"
+                && problem.help.is_some()
+                && problem.fields.len() == 1,
+            "Error: unexpected problem {problem:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_internal_details_not_sent() -> miette::Result<()> {
+        let read_config = || -> Result<(), LibError> {
+            Err(io::Error::other("password=hunter2"))?;
+            Ok(())
+        };
+        let Err(error) = read_config() else {
+            miette::bail!("Error: IO error lost");
+        };
+        let problem = error.to_problem(i18n::default_catalog());
+        let body = format!("{problem:?}");
+        let LibError::Internal(internal) = error else {
+            miette::bail!("Error: IO error not wrapped as internal");
+        };
+
+        miette::ensure!(
+            !body.contains("hunter2")
+                && problem.error_id.as_ref() == Some(&internal.id)
+                && internal.chain().contains("hunter2"),
+            "Error: internal detail sent to the client"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_problem() -> miette::Result<()> {
+        let cases = [
+            LibError::EmailTaken,
+            LibError::Validation {
+                fields: vec![FieldError::new("email", "email.invalid", &[])],
+            },
+            LibError::TooManyAttempts { retry_after: 30 },
+            LibError::from(io::Error::other("disk full")),
+        ];
+        for error in cases {
+            let problem = error.to_problem(i18n::default_catalog());
+            let rebuilt = LibError::from_problem(problem.clone(), Some(30));
+
+            miette::ensure!(
+                rebuilt.to_problem(i18n::default_catalog()) == problem,
+                "Error: {error:?} rebuilt as {rebuilt:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_chain() -> miette::Result<()> {
+        let parse_error = serde_json::from_str::<FieldError>("{").unwrap_err();
+        let first = BoxedError::from(io::Error::new(
+            io::ErrorKind::InvalidData,
+            parse_error,
+        ));
+        let second = BoxedError::from(io::Error::other("other"));
+
+        miette::ensure!(
+            first.chain().contains("EOF") && first.id != second.id,
+            "Error: unexpected chain {first:?}"
+        );
+        Ok(())
     }
 }
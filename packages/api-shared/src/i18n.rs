@@ -0,0 +1,290 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+
+/// Language the messages were first written in. Keys missing from another
+/// catalog, and callers accepting no available language, get this one.
+pub const DEFAULT_LOCALE: &str = "pt-BR";
+
+// `CATALOG_FILES`: tag and contents of every `locales/<tag>.toml`, see
+// build.rs
+include!(concat!(env!("OUT_DIR"), "/catalogs.rs"));
+
+/// Texts of one `LibError` variant, see `locales/pt-BR.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ErrorText {
+    /// short summary, the same for every occurrence
+    pub title: String,
+    /// `Display` of the error
+    pub message: String,
+    /// explanation sent to clients, `message` when absent
+    pub detail: Option<String>,
+    /// what the user can do about it
+    pub help: Option<String>,
+}
+
+/// Messages of one language, read from `locales/<locale>.toml`.
+#[derive(Debug, Deserialize)]
+pub struct Catalog {
+    #[serde(skip)]
+    locale: &'static str,
+    /// keyed by variant name, i.e. the diagnostic code without `LibError::`
+    #[serde(rename = "LibError", default)]
+    errors: HashMap<String, ErrorText>,
+    /// keyed by the code of the failed rule, see `FieldError`
+    #[serde(default)]
+    fields: HashMap<String, String>,
+    /// texts sent to users, such as mails and task reminders, keyed like
+    /// `reminder.subject`
+    #[serde(default)]
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Language tag of the catalog, e.g. `pt-BR`.
+    pub fn locale(&self) -> &'static str {
+        self.locale
+    }
+
+    /// Texts of the error with the diagnostic `code`, e.g.
+    /// `LibError::EmailTaken`, with `{name}` placeholders filled from
+    /// `args`.
+    pub fn error(
+        &self,
+        code: &str,
+        args: &BTreeMap<String, String>,
+    ) -> ErrorText {
+        let kind = code.trim_start_matches("LibError::");
+        let Some(text) = self
+            .errors
+            .get(kind)
+            .or_else(|| default_catalog().errors.get(kind))
+        else {
+            return ErrorText {
+                title: code.to_string(),
+                message: code.to_string(),
+                ..ErrorText::default()
+            };
+        };
+
+        ErrorText {
+            title: interpolate(&text.title, args),
+            message: interpolate(&text.message, args),
+            detail: text
+                .detail
+                .as_ref()
+                .map(|detail| interpolate(detail, args)),
+            help: text
+                .help
+                .as_ref()
+                .map(|help| interpolate(help, args)),
+        }
+    }
+
+    /// Message of a failed field rule, e.g. `password.too_short`.
+    pub fn field(
+        &self,
+        code: &str,
+        params: &BTreeMap<String, String>,
+    ) -> String {
+        self.fields
+            .get(code)
+            .or_else(|| default_catalog().fields.get(code))
+            .map_or_else(
+                || code.to_string(),
+                |message| interpolate(message, params),
+            )
+    }
+
+    /// Text sent to users, e.g. `reminder.subject`, with `{name}`
+    /// placeholders filled from `args`.
+    pub fn message(
+        &self,
+        key: &str,
+        args: &BTreeMap<String, String>,
+    ) -> String {
+        self.messages
+            .get(key)
+            .or_else(|| default_catalog().messages.get(key))
+            .map_or_else(|| key.to_string(), |text| interpolate(text, args))
+    }
+}
+
+/// Tags of every available catalog, e.g. `en` and `pt-BR`.
+pub fn locales() -> impl Iterator<Item = &'static str> {
+    catalogs().iter().map(Catalog::locale)
+}
+
+/// Catalog of `DEFAULT_LOCALE`.
+pub fn default_catalog() -> &'static Catalog {
+    find(DEFAULT_LOCALE).expect("locales/pt-BR.toml is missing")
+}
+
+/// Catalog for a language tag such as `pt-BR` or `pt_BR`: the one with that
+/// exact tag, or else one of the same language.
+pub fn find(tag: &str) -> Option<&'static Catalog> {
+    let tag = tag.trim().replace('_', "-");
+    let language = primary_language(&tag);
+
+    catalogs()
+        .iter()
+        .find(|catalog| {
+            catalog
+                .locale
+                .eq_ignore_ascii_case(&tag)
+        })
+        .or_else(|| {
+            catalogs().iter().find(|catalog| {
+                primary_language(catalog.locale).eq_ignore_ascii_case(language)
+            })
+        })
+}
+
+/// Best catalog for an `Accept-Language` header such as
+/// `en-US,en;q=0.9,pt;q=0.8`, or the default one when none is accepted.
+pub fn negotiate(accept_language: &str) -> &'static Catalog {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = match parts.find_map(|p| p.trim().strip_prefix("q="))
+            {
+                Some(quality) => quality.trim().parse().ok()?,
+                None => 1.0,
+            };
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // the sort is stable, so ranges of equal quality keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| find(tag))
+        .unwrap_or_else(default_catalog)
+}
+
+/// Catalog for the process' own output, such as `miette` reports, picked
+/// from `LC_ALL`, `LC_MESSAGES` or `LANG` like gettext does.
+pub fn system_catalog() -> &'static Catalog {
+    static SYSTEM: OnceLock<&'static Catalog> = OnceLock::new();
+
+    SYSTEM.get_or_init(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            // `pt_BR.UTF-8` names the `pt_BR` language
+            .and_then(|value| find(value.split(['.', '@']).next()?))
+            .unwrap_or_else(default_catalog)
+    })
+}
+
+fn catalogs() -> &'static [Catalog] {
+    static CATALOGS: OnceLock<Vec<Catalog>> = OnceLock::new();
+
+    CATALOGS.get_or_init(|| {
+        CATALOG_FILES
+            .iter()
+            .map(|(locale, source)| {
+                let mut catalog: Catalog = toml::from_str(source)
+                    .unwrap_or_else(|err| {
+                        panic!("locales/{locale}.toml: {err}")
+                    });
+                catalog.locale = locale;
+                catalog
+            })
+            .collect()
+    })
+}
+
+/// `pt` for `pt-BR`.
+fn primary_language(tag: &str) -> &str {
+    tag.split('-')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Replaces each `{name}` of `template` by `args[name]`.
+fn interpolate(template: &str, args: &BTreeMap<String, String>) -> String {
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs_are_complete() -> miette::Result<()> {
+        let default = default_catalog();
+        for catalog in catalogs() {
+            let missing: Vec<&String> = default
+                .errors
+                .keys()
+                .chain(default.fields.keys())
+                .chain(default.messages.keys())
+                .filter(|key| {
+                    !catalog.errors.contains_key(*key)
+                        && !catalog.fields.contains_key(*key)
+                        && !catalog.messages.contains_key(*key)
+                })
+                .collect();
+
+            miette::ensure!(
+                missing.is_empty(),
+                "Error: locales/{}.toml lacks {missing:?}",
+                catalog.locale
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_negotiate() -> miette::Result<()> {
+        let cases = [
+            ("en-US,en;q=0.9,pt;q=0.8", "en"),
+            ("pt-BR", "pt-BR"),
+            ("pt-PT", "pt-BR"),
+            ("fr-FR, en;q=0.5", "en"),
+            ("en;q=0.2, pt;q=0.7", "pt-BR"),
+            ("en;q=0, fr", "pt-BR"),
+            ("*", "pt-BR"),
+            ("", "pt-BR"),
+        ];
+        for (header, locale) in cases {
+            let chosen = negotiate(header).locale();
+            miette::ensure!(
+                chosen == locale,
+                "Error: {header:?} negotiated {chosen} instead of {locale}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpolation_and_fallback() -> miette::Result<()> {
+        let args =
+            BTreeMap::from([("retry_after".to_string(), "30".to_string())]);
+        let en = find("en_US").unwrap();
+        let text = en.error("LibError::TooManyAttempts", &args);
+        let unknown = en.error("LibError::Missing", &args);
+
+        miette::ensure!(
+            text.detail.as_deref() == Some("Try again in 30 seconds")
+                && unknown.title == "LibError::Missing"
+                && en.field("no.such.rule", &args) == "no.such.rule"
+                && en.message("no.such.text", &args) == "no.such.text",
+            "Error: unexpected texts {text:?} {unknown:?}"
+        );
+        Ok(())
+    }
+}
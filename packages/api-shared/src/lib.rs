@@ -1,2 +1,11 @@
+#[cfg(feature = "client")]
+pub mod client;
+pub mod dto;
 pub mod error;
+pub mod i18n;
 pub mod prelude;
+pub mod problem;
+pub mod recurrence;
+#[cfg(feature = "server")]
+pub mod response;
+pub mod validation;
@@ -1,6 +1,9 @@
 use std::fmt::{self, Debug, Display};
 
-pub use crate::error::LibError;
+pub use crate::{
+    error::{FieldError, LibError},
+    problem::ProblemDetails,
+};
 
 // Generic newtype pattern wrapper for conveniently implementing
 // external `Traits` on external `Types` living in different crates
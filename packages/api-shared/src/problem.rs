@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::FieldError, i18n::Catalog};
+
+/// Media type of `ProblemDetails` bodies.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Body of every error response of api-server, as described by RFC 7807.
+///
+/// Clients branch on `code`, which stays the same across releases, and
+/// show `title`, `detail` and `help` to the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    /// URI naming the kind of problem, `urn:sight-agent:error:<kind>`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// short summary, the same for every occurrence of the problem
+    pub title: String,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// explanation specific to this occurrence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// diagnostic code of the error, e.g. `LibError::EmailTaken`
+    pub code: String,
+    /// what the user can do about it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    /// every invalid field, for `LibError::Validation`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    /// id under which a server failure was logged, for `LibError::Internal`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<String>,
+}
+
+impl ProblemDetails {
+    /// Fills the texts of the problem, and of its fields, from `catalog`.
+    /// `args` are the values the texts refer to, see `LibError::args`.
+    pub fn localize(
+        &mut self,
+        catalog: &Catalog,
+        args: &BTreeMap<String, String>,
+    ) {
+        let text = catalog.error(&self.code, args);
+        // server failures only show the id they were logged under, their
+        // details may expose internals
+        let has_detail =
+            (400..500).contains(&self.status) || self.error_id.is_some();
+
+        self.title = text.title;
+        self.detail = has_detail.then(|| text.detail.unwrap_or(text.message));
+        self.help = text.help;
+        for field in &mut self.fields {
+            field.message = catalog.field(&field.code, &field.params);
+        }
+    }
+}
@@ -0,0 +1,469 @@
+//! The subset of RFC 5545 recurrence rules tasks repeat by: `FREQ` daily,
+//! weekly or monthly, `INTERVAL`, `BYDAY` (with an ordinal for monthly
+//! rules, e.g. `2TU` or `-1FR`), and either `COUNT` or `UNTIL`.
+//!
+//! Rules repeat dates, not instants: a task happens on a day, whatever
+//! the time zone of whoever looks at it.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+/// Longest stretch searched for the next date. A 5th Monday of February
+/// comes back within decades; a daily rule every 7 days on another
+/// weekday than its first date never does.
+const MAX_GAP_DAYS: i64 = 366 * 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A `BYDAY` entry: the weekday, and for monthly rules which one of the
+/// month, counted from its end when negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    pub ordinal: Option<i8>,
+    pub weekday: Weekday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    /// every how many days, weeks or months, at least 1
+    pub interval: u32,
+    /// empty to repeat on the weekday, or the day of the month, of the
+    /// first date
+    pub by_day: Vec<ByDay>,
+    /// how many dates at most, the first one included
+    pub count: Option<u32>,
+    /// last day the rule may fall on
+    pub until: Option<NaiveDate>,
+}
+
+impl RecurrenceRule {
+    /// Reads an `RRULE` value such as `FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10`,
+    /// with or without the `RRULE:` prefix. Parts out of the subset, and
+    /// rules with both `COUNT` and `UNTIL`, are refused.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let value = value
+            .strip_prefix("RRULE:")
+            .unwrap_or(value);
+
+        let mut frequency = None;
+        let mut interval = None;
+        let mut by_day = None;
+        let mut count = None;
+        let mut until = None;
+        for part in value.split(';') {
+            let (name, value) = part.split_once('=')?;
+            let is_new = match name {
+                "FREQ" => frequency
+                    .replace(parse_frequency(value)?)
+                    .is_none(),
+                "INTERVAL" => interval
+                    .replace(
+                        value
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|n| *n > 0)?,
+                    )
+                    .is_none(),
+                "BYDAY" => by_day
+                    .replace(
+                        value
+                            .split(',')
+                            .map(parse_by_day)
+                            .collect::<Option<Vec<_>>>()?,
+                    )
+                    .is_none(),
+                "COUNT" => count
+                    .replace(
+                        value
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|n| *n > 0)?,
+                    )
+                    .is_none(),
+                "UNTIL" => until
+                    .replace(parse_until(value)?)
+                    .is_none(),
+                _ => false,
+            };
+            if !is_new {
+                return None;
+            }
+        }
+
+        let frequency = frequency?;
+        let by_day = by_day.unwrap_or_default();
+        // ordinals only make sense within a month
+        let has_ordinal = by_day
+            .iter()
+            .any(|day| day.ordinal.is_some());
+        if (count.is_some() && until.is_some())
+            || (has_ordinal && frequency != Frequency::Monthly)
+        {
+            return None;
+        }
+
+        Some(Self {
+            frequency,
+            interval: interval.unwrap_or(1),
+            by_day,
+            count,
+            until,
+        })
+    }
+
+    /// The dates of the rule from `starts_on` on, in order. `starts_on`
+    /// anchors the intervals and is only one of them when the rule falls
+    /// on it. Never ends without `COUNT` or `UNTIL`, so bound it.
+    pub fn dates(&self, starts_on: NaiveDate) -> Dates<'_> {
+        Dates {
+            rule: self,
+            starts_on,
+            next: Some(starts_on),
+            last: starts_on,
+            emitted: 0,
+        }
+    }
+
+    /// The dates of `dates(starts_on)` from `from` on. Without `COUNT`
+    /// the walk starts at `from`, since no earlier date matters; with it,
+    /// the earlier dates are still walked to be counted.
+    pub fn dates_from(
+        &self,
+        starts_on: NaiveDate,
+        from: NaiveDate,
+    ) -> impl Iterator<Item = NaiveDate> + '_ {
+        let first = match self.count {
+            Some(_) => starts_on,
+            None => starts_on.max(from),
+        };
+        let dates = Dates {
+            rule: self,
+            starts_on,
+            next: Some(first),
+            last: first,
+            emitted: 0,
+        };
+
+        dates.skip_while(move |date| *date < from)
+    }
+
+    fn falls_on(&self, starts_on: NaiveDate, date: NaiveDate) -> bool {
+        match self.frequency {
+            Frequency::Daily => {
+                let days = (date - starts_on).num_days();
+                days % self.interval as i64 == 0
+                    && (self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|day| day.weekday == date.weekday()))
+            }
+            Frequency::Weekly => {
+                let weeks =
+                    (monday_of(date) - monday_of(starts_on)).num_days() / 7;
+                let weekday = date.weekday();
+                weeks % self.interval as i64 == 0
+                    && if self.by_day.is_empty() {
+                        weekday == starts_on.weekday()
+                    } else {
+                        self.by_day
+                            .iter()
+                            .any(|day| day.weekday == weekday)
+                    }
+            }
+            Frequency::Monthly => {
+                let months = month_index(date) - month_index(starts_on);
+                months % self.interval as i64 == 0
+                    && if self.by_day.is_empty() {
+                        date.day() == starts_on.day()
+                    } else {
+                        self.by_day
+                            .iter()
+                            .any(|day| is_nth_weekday(date, *day))
+                    }
+            }
+        }
+    }
+}
+
+/// Lazy iterator over the dates of a `RecurrenceRule`.
+#[derive(Debug, Clone)]
+pub struct Dates<'a> {
+    rule: &'a RecurrenceRule,
+    starts_on: NaiveDate,
+    next: Option<NaiveDate>,
+    /// the last date given, else `starts_on`
+    last: NaiveDate,
+    emitted: u32,
+}
+
+impl Iterator for Dates<'_> {
+    type Item = NaiveDate;
+
+    fn next(&mut self) -> Option<NaiveDate> {
+        if self
+            .rule
+            .count
+            .is_some_and(|count| self.emitted >= count)
+        {
+            return None;
+        }
+        while let Some(date) = self.next {
+            let is_over = self
+                .rule
+                .until
+                .is_some_and(|until| date > until);
+            if is_over || (date - self.last).num_days() > MAX_GAP_DAYS {
+                break;
+            }
+            self.next = date.succ_opt();
+            if self.rule.falls_on(self.starts_on, date) {
+                self.emitted += 1;
+                self.last = date;
+                return Some(date);
+            }
+        }
+
+        self.next = None;
+        None
+    }
+}
+
+fn parse_frequency(value: &str) -> Option<Frequency> {
+    match value {
+        "DAILY" => Some(Frequency::Daily),
+        "WEEKLY" => Some(Frequency::Weekly),
+        "MONTHLY" => Some(Frequency::Monthly),
+        _ => None,
+    }
+}
+
+/// `MO`, `2TU`, `-1FR`...
+fn parse_by_day(value: &str) -> Option<ByDay> {
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, weekday) = (value.get(..split)?, value.get(split..)?);
+    let weekday = match weekday {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => Some(
+            ordinal
+                .parse::<i8>()
+                .ok()
+                .filter(|n| (1..=5).contains(&n.abs()))?,
+        ),
+    };
+
+    Some(ByDay { ordinal, weekday })
+}
+
+/// `20261231`, or a date-time such as `20261231T235959Z` whose time is
+/// dropped.
+fn parse_until(value: &str) -> Option<NaiveDate> {
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    if let Some(time) = time {
+        let time = time.strip_suffix('Z').unwrap_or(time);
+        if time.len() != 6 || !time.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+    }
+
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+fn monday_of(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn month_index(date: NaiveDate) -> i64 {
+    date.year() as i64 * 12 + date.month0() as i64
+}
+
+fn is_nth_weekday(date: NaiveDate, day: ByDay) -> bool {
+    if date.weekday() != day.weekday {
+        return false;
+    }
+
+    match day.ordinal {
+        None => true,
+        Some(n) if n > 0 => (date.day0() / 7 + 1) as i8 == n,
+        Some(n) => {
+            let days_after = days_in_month(date) - date.day();
+            -((days_after / 7 + 1) as i8) == n
+        }
+    }
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|first| first.pred_opt())
+        .map_or(31, |last| last.day())
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn dates(rule: &str, starts_on: &str, take: usize) -> Vec<String> {
+        RecurrenceRule::parse(rule)
+            .unwrap()
+            .dates(date(starts_on))
+            .take(take)
+            .map(|date| date.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() -> miette::Result<()> {
+        let rule = RecurrenceRule::parse(
+            "RRULE:FREQ=MONTHLY;BYDAY=2TU,-1FR;UNTIL=20261231T235959Z",
+        );
+        miette::ensure!(
+            rule == Some(RecurrenceRule {
+                frequency: Frequency::Monthly,
+                interval: 1,
+                by_day: vec![
+                    ByDay {
+                        ordinal: Some(2),
+                        weekday: Weekday::Tue
+                    },
+                    ByDay {
+                        ordinal: Some(-1),
+                        weekday: Weekday::Fri
+                    },
+                ],
+                count: None,
+                until: Some(date("2026-12-31")),
+            }),
+            "Error: unexpected rule {rule:?}"
+        );
+
+        for invalid in [
+            "",
+            "FREQ=YEARLY",
+            "INTERVAL=2",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;FREQ=WEEKLY",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=MONTHLY;BYDAY=6MO",
+            "FREQ=WEEKLY;BYDAY=XX",
+            "FREQ=DAILY;COUNT=3;UNTIL=20261231",
+            "FREQ=DAILY;UNTIL=2026-12-31",
+            "FREQ=DAILY;BYMONTH=1",
+            "freq=daily",
+        ] {
+            miette::ensure!(
+                RecurrenceRule::parse(invalid).is_none(),
+                "Error: {invalid:?} accepted"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dates() -> miette::Result<()> {
+        let cases: &[(&str, &str, &[&str])] = &[
+            (
+                "FREQ=DAILY;INTERVAL=2;COUNT=3",
+                "2026-10-30",
+                &["2026-10-30", "2026-11-01", "2026-11-03"],
+            ),
+            (
+                // every other week, starting on a Wednesday
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR",
+                "2026-10-14",
+                &["2026-10-16", "2026-10-26", "2026-10-30", "2026-11-09"],
+            ),
+            (
+                "FREQ=WEEKLY;UNTIL=20261101",
+                "2026-10-18",
+                &["2026-10-18", "2026-10-25", "2026-11-01"],
+            ),
+            (
+                // months without a 31st are skipped
+                "FREQ=MONTHLY;COUNT=3",
+                "2026-01-31",
+                &["2026-01-31", "2026-03-31", "2026-05-31"],
+            ),
+            (
+                "FREQ=MONTHLY;BYDAY=-1FR",
+                "2026-10-01",
+                &["2026-10-30", "2026-11-27", "2026-12-25", "2027-01-29"],
+            ),
+            (
+                "FREQ=MONTHLY;INTERVAL=3;BYDAY=1MO",
+                "2026-10-18",
+                &["2027-01-04", "2027-04-05", "2027-07-05", "2027-10-04"],
+            ),
+        ];
+        for (rule, starts_on, expected) in cases {
+            let dates = dates(rule, starts_on, 4);
+            miette::ensure!(
+                dates == *expected,
+                "Error: {rule} from {starts_on} gave {dates:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dates_from_distant_start() -> miette::Result<()> {
+        let from = date("2026-10-18");
+        let dates_from = |rule: &str, starts_on: NaiveDate| -> Vec<String> {
+            RecurrenceRule::parse(rule)
+                .unwrap()
+                .dates_from(starts_on, from)
+                .take(3)
+                .map(|date| date.to_string())
+                .collect()
+        };
+
+        // walked from 2026, not from year -200000
+        let open = dates_from("FREQ=DAILY;INTERVAL=2", NaiveDate::MIN);
+        let expected: Vec<String> = RecurrenceRule::parse("FREQ=DAILY")
+            .unwrap()
+            .dates(from)
+            .filter(|date| (*date - NaiveDate::MIN).num_days() % 2 == 0)
+            .take(3)
+            .map(|date| date.to_string())
+            .collect();
+        let counted = dates_from("FREQ=WEEKLY;COUNT=3", date("2026-10-04"));
+        let later = dates_from("FREQ=WEEKLY", date("2026-11-01"));
+
+        miette::ensure!(
+            open == expected
+                && counted == ["2026-10-18"]
+                && later == ["2026-11-01", "2026-11-08", "2026-11-15"],
+            "Error: unexpected dates {open:?} {counted:?} {later:?}"
+        );
+        Ok(())
+    }
+}
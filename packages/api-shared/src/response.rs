@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    body,
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{
+    error::LibError,
+    i18n::{self, Catalog},
+    problem::{ProblemDetails, PROBLEM_JSON},
+};
+
+// Axum glue of `LibError`, behind the `server` feature so the rest of the
+// crate builds for the browser.
+
+/// Kept in the extensions of every `LibError` response, so a layer that
+/// knows the caller's language can render it again.
+#[derive(Debug, Clone)]
+pub struct LocalizableProblem {
+    problem: ProblemDetails,
+    args: BTreeMap<String, String>,
+}
+
+impl LocalizableProblem {
+    /// Diagnostic code of the error, e.g. `LibError::EmailTaken`.
+    pub fn code(&self) -> &str {
+        &self.problem.code
+    }
+}
+
+/// Renders the body of a `LibError` response again in the language of
+/// `catalog`. Other responses are returned untouched.
+pub fn localize_response(
+    mut response: Response,
+    catalog: &Catalog,
+) -> Response {
+    let Some(LocalizableProblem { mut problem, args }) =
+        response.extensions_mut().remove()
+    else {
+        return response;
+    };
+    problem.localize(catalog, &args);
+    let Ok(body) = serde_json::to_vec(&problem) else {
+        return response;
+    };
+
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_LENGTH);
+    headers.insert(
+        header::CONTENT_LANGUAGE,
+        HeaderValue::from_static(catalog.locale()),
+    );
+    *response.body_mut() = body::boxed(body::Full::from(body));
+    response
+}
+
+// implementing Axum IntoResponse for custom errors
+impl IntoResponse for LibError {
+    fn into_response(self) -> Response {
+        // on the span of the request, when the server declared the field
+        tracing::Span::current().record(
+            "error",
+            tracing::field::display(format_args!("LibError::{}", self.kind())),
+        );
+        // the only place the whole chain shows up, under the id the client
+        // was given
+        if let Self::Internal(internal) = &self {
+            tracing::error!(error_id = %internal.id, "{}", internal.chain());
+        }
+        let catalog = i18n::default_catalog();
+        let problem = self.to_problem(catalog);
+        let headers = [
+            (header::CONTENT_TYPE, PROBLEM_JSON),
+            (header::CONTENT_LANGUAGE, catalog.locale()),
+        ];
+        let localizable = LocalizableProblem {
+            problem: problem.clone(),
+            args: self.args(),
+        };
+
+        let mut response = match self {
+            Self::TooManyAttempts { retry_after } => (
+                self.status(),
+                headers,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(problem),
+            )
+                .into_response(),
+            _ => (self.status(), headers, Json(problem)).into_response(),
+        };
+        response
+            .extensions_mut()
+            .insert(localizable);
+        response
+    }
+}
+
+impl From<LibError> for Response {
+    fn from(error: LibError) -> Self {
+        error.into_response()
+    }
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FieldError;
+
+    #[test]
+    fn test_problem_content_type() -> miette::Result<()> {
+        let response =
+            LibError::TooManyAttempts { retry_after: 30 }.into_response();
+        let headers = response.headers();
+
+        miette::ensure!(
+            headers[header::CONTENT_TYPE] == PROBLEM_JSON
+                && headers[header::RETRY_AFTER] == "30",
+            "Error: unexpected headers {headers:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_localize_response() -> miette::Result<()> {
+        let error = LibError::Validation {
+            fields: vec![FieldError::new(
+                "password",
+                "password.too_short",
+                &[("min", "8".to_string())],
+            )],
+        };
+        let en = i18n::find("en").unwrap();
+        let response = localize_response(error.into_response(), en);
+        let language = response.headers()[header::CONTENT_LANGUAGE].clone();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let problem: ProblemDetails = serde_json::from_slice(&body).unwrap();
+
+        miette::ensure!(
+            language == "en"
+                && problem.title == "Invalid fields"
+                && problem.fields[0].message
+                    == "The password must have at least 8 characters",
+            "Error: response not localized {problem:?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,724 @@
+use std::{collections::HashSet, sync::OnceLock};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::{
+    dto::Recurrence,
+    error::{FieldError, LibError},
+    recurrence::RecurrenceRule,
+};
+
+pub const EMAIL_MAX_LEN: usize = 254;
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 32;
+pub const PASSWORD_MIN_LEN: usize = 8;
+/// Bounds the work a single sign up can ask from the password hasher.
+pub const PASSWORD_MAX_LEN: usize = 128;
+/// Rough brute-force cost below which a password is refused as weak.
+pub const PASSWORD_MIN_ENTROPY_BITS: f64 = 36.0;
+
+pub const TASK_TITLE_MAX_LEN: usize = 120;
+pub const TASK_SUMMARY_MAX_LEN: usize = 280;
+/// Keeps a single task well below what a request body may carry.
+pub const TASK_CONTENT_MAX_LEN: usize = 20_000;
+pub const TASK_TAG_MAX_LEN: usize = 32;
+pub const TASK_MAX_TAGS: usize = 16;
+pub const TASK_MAX_EXCEPTIONS: usize = 366;
+pub const TASK_MAX_REMINDERS: usize = 5;
+pub const REMINDER_MAX_OFFSET_DAYS: u32 = 7;
+/// How far from today a repetition may start, and how long after its start
+/// one with `COUNT` may end, since those are walked from their start.
+pub const RECURRENCE_MAX_YEARS: i64 = 10;
+/// Bounds the occurrences a single request generates.
+pub const OCCURRENCES_MAX_DAYS: i64 = 366;
+
+/// Usernames that could pass for the service itself or collide with app
+/// routes, compared case-insensitively.
+const RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "api",
+    "help",
+    "me",
+    "moderator",
+    "new_task",
+    "null",
+    "root",
+    "security",
+    "settings",
+    "sight",
+    "signin",
+    "signup",
+    "staff",
+    "support",
+    "system",
+    "tempowise",
+    "undefined",
+];
+
+/// Collects every failing field of a form, so they are all reported in a
+/// single `LibError::Validation` instead of one per request.
+#[derive(Debug, Default)]
+pub struct Validator {
+    fields: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the syntax of an already trimmed and lowercased email.
+    pub fn email(&mut self, field: &str, email: &str) -> &mut Self {
+        if email.is_empty() {
+            return self.fail(field, "email.required", &[]);
+        }
+        if email.len() > EMAIL_MAX_LEN || !is_email(email) {
+            return self.fail(field, "email.invalid", &[]);
+        }
+
+        self
+    }
+
+    pub fn username(&mut self, field: &str, username: &str) -> &mut Self {
+        let len = username.chars().count();
+        if !(USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&len) {
+            self.fail(
+                field,
+                "username.length",
+                &[
+                    ("min", USERNAME_MIN_LEN.to_string()),
+                    ("max", USERNAME_MAX_LEN.to_string()),
+                ],
+            );
+        }
+
+        let is_charset_valid = username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        let starts_alphanumeric = username
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric());
+        if !is_charset_valid || (len > 0 && !starts_alphanumeric) {
+            self.fail(field, "username.charset", &[]);
+        }
+
+        let lowercase = username.to_lowercase();
+        if RESERVED_USERNAMES.contains(&lowercase.as_str()) {
+            self.fail(field, "username.reserved", &[]);
+        }
+
+        self
+    }
+
+    /// Checks password strength. `personal` holds values the password must
+    /// not contain, such as the username or the email's local part.
+    pub fn password(
+        &mut self,
+        field: &str,
+        password: &str,
+        personal: &[&str],
+    ) -> &mut Self {
+        let len = password.chars().count();
+        if len < PASSWORD_MIN_LEN {
+            return self.fail(
+                field,
+                "password.too_short",
+                &[("min", PASSWORD_MIN_LEN.to_string())],
+            );
+        }
+        if len > PASSWORD_MAX_LEN {
+            return self.fail(
+                field,
+                "password.too_long",
+                &[("max", PASSWORD_MAX_LEN.to_string())],
+            );
+        }
+
+        let lowercase = password.to_lowercase();
+        if common_passwords().contains(lowercase.as_str()) {
+            self.fail(field, "password.common", &[]);
+        } else if entropy_bits(password) < PASSWORD_MIN_ENTROPY_BITS {
+            self.fail(field, "password.weak", &[]);
+        }
+
+        let is_personal = personal
+            .iter()
+            .map(|value| value.trim().to_lowercase())
+            .filter(|value| value.chars().count() >= USERNAME_MIN_LEN)
+            .any(|value| lowercase.contains(&value));
+        if is_personal {
+            self.fail(field, "password.personal", &[]);
+        }
+
+        self
+    }
+
+    /// Checks an already trimmed title, which can't be empty.
+    pub fn title(&mut self, field: &str, title: &str) -> &mut Self {
+        if title.is_empty() {
+            return self.fail(field, "title.required", &[]);
+        }
+
+        self.text(field, title, TASK_TITLE_MAX_LEN)
+    }
+
+    /// Checks the length of free text, which may be empty.
+    pub fn text(&mut self, field: &str, text: &str, max: usize) -> &mut Self {
+        if text.chars().count() > max {
+            return self.fail(
+                field,
+                "text.too_long",
+                &[("max", max.to_string())],
+            );
+        }
+
+        self
+    }
+
+    /// Checks already trimmed tags, none of them empty.
+    pub fn tags(&mut self, field: &str, tags: &[String]) -> &mut Self {
+        if tags.len() > TASK_MAX_TAGS {
+            self.fail(
+                field,
+                "tags.too_many",
+                &[("max", TASK_MAX_TAGS.to_string())],
+            );
+        }
+        if tags
+            .iter()
+            .any(|tag| tag.chars().count() > TASK_TAG_MAX_LEN)
+        {
+            self.fail(
+                field,
+                "tags.too_long",
+                &[("max", TASK_TAG_MAX_LEN.to_string())],
+            );
+        }
+
+        self
+    }
+
+    /// Checks a `#rrggbb` color.
+    pub fn color(&mut self, field: &str, color: &str) -> &mut Self {
+        let is_hex = color.len() == 7
+            && color.starts_with('#')
+            && color[1..]
+                .chars()
+                .all(|c| c.is_ascii_hexdigit());
+        if !is_hex {
+            return self.fail(field, "color.invalid", &[]);
+        }
+
+        self
+    }
+
+    /// Checks that the rule parses, starts within `RECURRENCE_MAX_YEARS`
+    /// of `today` and doesn't end before it starts.
+    pub fn recurrence(
+        &mut self,
+        field: &str,
+        recurrence: &Recurrence,
+        today: NaiveDate,
+    ) -> &mut Self {
+        let Some(rule) = RecurrenceRule::parse(&recurrence.rule) else {
+            return self.fail(field, "recurrence.invalid", &[]);
+        };
+        let max_days = RECURRENCE_MAX_YEARS * 366;
+        let max = [("max", RECURRENCE_MAX_YEARS.to_string())];
+        if (recurrence.starts_on - today)
+            .num_days()
+            .abs()
+            > max_days
+        {
+            return self.fail(field, "recurrence.starts_too_far", &max);
+        }
+        let last_day = recurrence.starts_on + Duration::days(max_days);
+        if rule.count.is_some()
+            && rule
+                .dates(recurrence.starts_on)
+                .any(|date| date > last_day)
+        {
+            self.fail(field, "recurrence.ends_too_far", &max);
+        }
+        if rule
+            .until
+            .is_some_and(|until| until < recurrence.starts_on)
+        {
+            self.fail(field, "recurrence.ends_before_start", &[]);
+        }
+        if recurrence.exceptions.len() > TASK_MAX_EXCEPTIONS {
+            self.fail(
+                field,
+                "recurrence.too_many_exceptions",
+                &[("max", TASK_MAX_EXCEPTIONS.to_string())],
+            );
+        }
+
+        self
+    }
+
+    /// Checks that a task isn't due before it starts.
+    pub fn schedule(
+        &mut self,
+        field: &str,
+        starts_at: Option<DateTime<Utc>>,
+        due_at: Option<DateTime<Utc>>,
+    ) -> &mut Self {
+        if let (Some(starts_at), Some(due_at)) = (starts_at, due_at) {
+            if due_at < starts_at {
+                return self.fail(field, "due_at.before_start", &[]);
+            }
+        }
+
+        self
+    }
+
+    /// Checks the reminder offsets, in minutes, of a task that is due, or
+    /// that there are none.
+    pub fn reminders(
+        &mut self,
+        field: &str,
+        reminders: &[u32],
+        is_due: bool,
+    ) -> &mut Self {
+        if reminders.is_empty() {
+            return self;
+        }
+        if !is_due {
+            return self.fail(field, "reminders.without_due", &[]);
+        }
+        if reminders.len() > TASK_MAX_REMINDERS {
+            self.fail(
+                field,
+                "reminders.too_many",
+                &[("max", TASK_MAX_REMINDERS.to_string())],
+            );
+        }
+        if reminders
+            .iter()
+            .any(|minutes| *minutes > REMINDER_MAX_OFFSET_DAYS * 24 * 60)
+        {
+            self.fail(
+                field,
+                "reminders.too_early",
+                &[("max", REMINDER_MAX_OFFSET_DAYS.to_string())],
+            );
+        }
+
+        self
+    }
+
+    /// Checks that `from` comes first, at most `OCCURRENCES_MAX_DAYS`
+    /// before `to`.
+    pub fn date_range(
+        &mut self,
+        field: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> &mut Self {
+        let days = (to - from).num_days();
+        if !(0..OCCURRENCES_MAX_DAYS).contains(&days) {
+            return self.fail(
+                field,
+                "date_range.invalid",
+                &[("max", OCCURRENCES_MAX_DAYS.to_string())],
+            );
+        }
+
+        self
+    }
+
+    /// `Err(LibError::Validation)` listing every failure, if any.
+    pub fn finish(&mut self) -> Result<(), LibError> {
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+
+        Err(LibError::Validation {
+            fields: std::mem::take(&mut self.fields),
+        })
+    }
+
+    /// Records a failed rule. Its message comes from the catalogs of
+    /// api-shared, where `params` fill the `{name}` placeholders.
+    fn fail(
+        &mut self,
+        field: &str,
+        code: &str,
+        params: &[(&str, String)],
+    ) -> &mut Self {
+        self.fields
+            .push(FieldError::new(field, code, params));
+
+        self
+    }
+}
+
+/// `local@domain` where the local part uses the unquoted RFC 5322 atom
+/// characters and the domain is a dotted host name with an alphabetic TLD.
+fn is_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+
+    let is_local_valid = (1..=64).contains(&local.len())
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local.chars().all(|c| {
+            c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~.-".contains(c)
+        });
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let is_domain_valid = labels.len() >= 2
+        && labels.iter().all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| {
+            tld.len() >= 2
+                && tld
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic())
+        });
+
+    is_local_valid && is_domain_valid
+}
+
+/// Bits needed to brute-force `password` over the character classes it
+/// uses. Repeated characters only count once, so `aaaaaaaaaaaa` stays weak.
+fn entropy_bits(password: &str) -> f64 {
+    let (mut lower, mut upper, mut digit, mut symbol, mut other) =
+        (false, false, false, false, false);
+    for c in password.chars() {
+        match c {
+            'a'..='z' => lower = true,
+            'A'..='Z' => upper = true,
+            '0'..='9' => digit = true,
+            c if c.is_ascii() => symbol = true,
+            _ => other = true,
+        }
+    }
+    let pool = [
+        (lower, 26),
+        (upper, 26),
+        (digit, 10),
+        (symbol, 33),
+        (other, 100),
+    ]
+    .iter()
+    .filter(|(used, _)| *used)
+    .map(|(_, size)| size)
+    .sum::<u32>();
+
+    let distinct = password
+        .chars()
+        .collect::<HashSet<_>>()
+        .len();
+    let len = password
+        .chars()
+        .count()
+        .min(distinct * 2);
+
+    len as f64 * f64::from(pool).log2()
+}
+
+fn common_passwords() -> &'static HashSet<&'static str> {
+    static LIST: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    LIST.get_or_init(|| {
+        include_str!("common_passwords.txt")
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    })
+}
+
+// SECTION: TESTS...............................................................
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(result: Result<(), LibError>) -> Vec<String> {
+        match result {
+            Err(LibError::Validation { fields }) => fields
+                .into_iter()
+                .map(|f| f.code)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_email() -> miette::Result<()> {
+        for valid in ["user@email.com", "first.last+tag@sub.example.com.br"] {
+            miette::ensure!(
+                Validator::new()
+                    .email("email", valid)
+                    .finish()
+                    .is_ok(),
+                "Error: valid email {valid} refused"
+            );
+        }
+        for invalid in [
+            "user",
+            "user@",
+            "@email.com",
+            "user@email",
+            "user@@email.com",
+            "user name@email.com",
+            ".user@email.com",
+            "user..name@email.com",
+            "user@-email.com",
+            "user@email.c0m",
+        ] {
+            let codes = codes(
+                Validator::new()
+                    .email("email", invalid)
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == ["email.invalid"],
+                "Error: invalid email {invalid} accepted"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_username() -> miette::Result<()> {
+        let cases: [(&str, &[&str]); 6] = [
+            ("user_name-1.0", &[]),
+            ("ab", &["username.length"]),
+            ("nome com espaço", &["username.charset"]),
+            ("_user", &["username.charset"]),
+            ("Admin", &["username.reserved"]),
+            ("çã", &["username.length", "username.charset"]),
+        ];
+        for (username, expected) in cases {
+            let codes = codes(
+                Validator::new()
+                    .username("username", username)
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == expected,
+                "Error: {username} gave {codes:?}, expected {expected:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_password() -> miette::Result<()> {
+        let cases: [(&str, &[&str]); 8] = [
+            ("valid_password", &[]),
+            ("correct horse battery staple", &[]),
+            // 7 characters, 14 bytes
+            ("ããããããã", &["password.too_short"]),
+            ("Password123", &["password.common"]),
+            ("aaaaaaaaaaaaaaaa", &["password.weak"]),
+            ("20231231", &["password.weak"]),
+            ("my_username_rocks", &["password.personal"]),
+            ("me@email.com!", &["password.personal"]),
+        ];
+        for (password, expected) in cases {
+            let codes = codes(
+                Validator::new()
+                    .password(
+                        "password",
+                        password,
+                        &["My_Username", "me@email.com"],
+                    )
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == expected,
+                "Error: {password} gave {codes:?}, expected {expected:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_fields() -> miette::Result<()> {
+        let many_tags: Vec<String> = (0..=TASK_MAX_TAGS)
+            .map(|i| format!("tag{i}"))
+            .collect();
+        let long_tag = ["x".repeat(TASK_TAG_MAX_LEN + 1)];
+        let cases: [(&str, &str, &[String], &[&str]); 5] = [
+            ("Water the plants", "", &[], &[]),
+            ("", "", &[], &["title.required"]),
+            (
+                &"ã".repeat(TASK_TITLE_MAX_LEN + 1),
+                "",
+                &[],
+                &["text.too_long"],
+            ),
+            (
+                "Title",
+                &"s".repeat(TASK_SUMMARY_MAX_LEN + 1),
+                &long_tag,
+                &["text.too_long", "tags.too_long"],
+            ),
+            ("Title", "", &many_tags, &["tags.too_many"]),
+        ];
+        for (title, summary, tags, expected) in cases {
+            let codes = codes(
+                Validator::new()
+                    .title("title", title)
+                    .text("summary", summary, TASK_SUMMARY_MAX_LEN)
+                    .tags("tags", tags)
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == expected,
+                "Error: {title} gave {codes:?}, expected {expected:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_color() -> miette::Result<()> {
+        for (color, expected) in [
+            ("#3b82F6", &[][..]),
+            ("3b82f6", &["color.invalid"][..]),
+            ("#3b82g6", &["color.invalid"]),
+            ("#fff", &["color.invalid"]),
+            ("#ããã", &["color.invalid"]),
+        ] {
+            let codes = codes(
+                Validator::new()
+                    .color("color", color)
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == expected,
+                "Error: {color} gave {codes:?}, expected {expected:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recurrence() -> miette::Result<()> {
+        let date =
+            |value| NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap();
+        let today = date("2026-10-18");
+        let recurrence = |rule: &str, exceptions: usize| Recurrence {
+            rule: rule.to_string(),
+            starts_on: today,
+            exceptions: vec![date("2026-10-25"); exceptions],
+        };
+        let distant = Recurrence {
+            starts_on: date("1900-01-01"),
+            ..recurrence("FREQ=DAILY", 0)
+        };
+        for (recurrence, expected) in [
+            (recurrence("FREQ=WEEKLY;BYDAY=SU", 1), &[][..]),
+            (recurrence("FREQ=HOURLY", 0), &["recurrence.invalid"][..]),
+            (
+                recurrence("FREQ=DAILY;UNTIL=20261017", 0),
+                &["recurrence.ends_before_start"],
+            ),
+            (
+                recurrence("FREQ=DAILY", TASK_MAX_EXCEPTIONS + 1),
+                &["recurrence.too_many_exceptions"],
+            ),
+            (distant, &["recurrence.starts_too_far"]),
+            (recurrence("FREQ=MONTHLY;COUNT=120", 0), &[]),
+            (
+                recurrence("FREQ=MONTHLY;COUNT=4000000000", 0),
+                &["recurrence.ends_too_far"],
+            ),
+        ] {
+            let codes = codes(
+                Validator::new()
+                    .recurrence("recurrence", &recurrence, today)
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == expected,
+                "Error: {recurrence:?} gave {codes:?}, expected {expected:?}"
+            );
+        }
+
+        let codes = codes(
+            Validator::new()
+                .date_range("from", date("2026-10-18"), date("2026-10-18"))
+                .date_range("from", date("2026-10-18"), date("2026-10-17"))
+                .date_range("from", date("2026-01-01"), date("2027-01-02"))
+                .finish(),
+        );
+        miette::ensure!(
+            codes == ["date_range.invalid", "date_range.invalid"],
+            "Error: unexpected range codes {codes:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule() -> miette::Result<()> {
+        let at = |hour| {
+            NaiveDate::from_ymd_opt(2026, 10, 18)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let too_early = REMINDER_MAX_OFFSET_DAYS * 24 * 60 + 1;
+        // start hour, due hour, reminders and the codes expected
+        type Case<'a> = (Option<u32>, Option<u32>, &'a [u32], &'a [&'a str]);
+        let cases: &[Case] = &[
+            (Some(9), Some(18), &[0, 60], &[]),
+            (None, None, &[], &[]),
+            (Some(18), Some(9), &[], &["due_at.before_start"]),
+            (Some(9), None, &[60], &["reminders.without_due"]),
+            (None, Some(9), &[1, 2, 3, 4, 5, 6], &["reminders.too_many"]),
+            (None, Some(9), &[too_early], &["reminders.too_early"]),
+        ];
+        for (starts_at, due_at, reminders, expected) in cases {
+            let codes = codes(
+                Validator::new()
+                    .schedule("due_at", starts_at.map(at), due_at.map(at))
+                    .reminders("reminders", reminders, due_at.is_some())
+                    .finish(),
+            );
+            miette::ensure!(
+                codes == *expected,
+                "Error: {starts_at:?} {due_at:?} {reminders:?} gave {codes:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_every_field() -> miette::Result<()> {
+        let result = Validator::new()
+            .email("email", "invalid")
+            .username("username", "x")
+            .password("password", "short", &[])
+            .finish();
+        let Err(LibError::Validation { fields }) = result else {
+            miette::bail!("Error: invalid form accepted");
+        };
+        let names: Vec<&str> = fields
+            .iter()
+            .map(|f| f.field.as_str())
+            .collect();
+
+        miette::ensure!(
+            names == ["email", "username", "password"],
+            "Error: unexpected fields {names:?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use api_shared::{client::ApiClient, i18n, prelude::LibError};
+use web_sys::wasm_bindgen::JsValue;
+
+/// Base URL of the api-server.
+pub const API_URL: &str = "http://127.0.0.1:3030";
+
+thread_local! {
+    static CLIENT: ApiClient = ApiClient::new(API_URL);
+}
+
+/// Client of the api-server. Requests carry the session cookies set when
+/// signing in, so the pages never handle tokens themselves.
+pub fn client() -> ApiClient {
+    CLIENT.with(ApiClient::clone)
+}
+
+/// User facing message of a failed call, in the browser's language. Field
+/// messages come already translated by the server.
+pub fn error_message(error: &LibError) -> String {
+    if let LibError::Validation { fields } = error {
+        return fields
+            .iter()
+            .map(|field| field.message.as_str())
+            .collect::<Vec<_>>()
+            .join(". ");
+    }
+
+    let language = web_sys::window()
+        .and_then(|window| window.navigator().language())
+        .unwrap_or_default();
+    let text = error.text(i18n::negotiate(&language));
+    match text.help {
+        Some(help) => format!("{}. {help}", text.title),
+        None => text.title,
+    }
+}
+
+/// Reads a parameter from the query string of the current page, e.g. the
+/// token of a mailed link.
+pub fn query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Takes a parameter out of the fragment of the current page, where the
+/// server puts what shouldn't reach logs, e.g. the 2FA challenge of an
+/// OIDC sign-in. The fragment is then dropped from the address bar, and so
+/// from the history.
+pub fn take_fragment_param(name: &str) -> Option<String> {
+    let window = web_sys::window()?;
+    let location = window.location();
+    let fragment = location.hash().ok()?;
+    let value = fragment
+        .trim_start_matches('#')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())?;
+
+    let url = format!("{}{}", location.pathname().ok()?, location.search().ok()?);
+    let _ = window
+        .history()
+        .ok()?
+        .replace_state_with_url(&JsValue::NULL, "", Some(&url));
+    Some(value)
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
 use dioxus::prelude::*;
 // use wasm_bindgen::prelude::*;
 // use web_sys::{window, CanvasRenderingContext2d, HtmlCanvasElement};
@@ -7,16 +8,40 @@ pub struct LineChart {
     progress: Vec<f32>,
 }
 impl LineChart {
-    fn week_chart() -> Self {
+    fn week_chart(progress: [f32; 7]) -> Self {
         LineChart {
             days: vec!["M", "T", "W", "T", "F", "S", "S"],
-            progress: vec![0.2, 0.1, 0.5, 0.6, 1.0, 0.8, 0.7],
+            progress: progress.to_vec(),
         }
     }
+
+    /// Tasks completed on each day of the week of `today`, Monday first,
+    /// relative to the busiest of those days.
+    pub fn week_progress(completed: &[DateTime<Utc>], today: NaiveDate) -> [f32; 7] {
+        let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let mut counts = [0u32; 7];
+        for at in completed {
+            let day = (at.with_timezone(&Local).date_naive() - monday).num_days();
+            if (0..7).contains(&day) {
+                counts[day as usize] += 1;
+            }
+        }
+        let busiest = counts.iter().copied().max().unwrap_or_default().max(1);
+
+        counts.map(|count| count as f32 / busiest as f32)
+    }
+}
+
+#[derive(Props, PartialEq)]
+pub struct ChartProps {
+    /// from 0 to 1 for each day, see `LineChart::week_progress`
+    progress: [f32; 7],
 }
 
-pub fn Chart(cx: Scope) -> Element {
-    let week_chart = LineChart::week_chart();
+pub fn Chart(cx: Scope<ChartProps>) -> Element {
+    let week_chart = LineChart::week_chart(cx.props.progress);
+    // borrowed, the closure of each point can't move out of `week_chart`
+    let progress = week_chart.progress.as_slice();
     cx.render(rsx! {
         svg { class: "@apply rounded-xl max-w-full", width: "100%", height: "600",
             defs {
@@ -30,15 +55,14 @@ pub fn Chart(cx: Scope) -> Element {
             rect { id: "my-rect", x: "0", y: "0", width: "100%", height: "100%", fill: "none" }
             week_chart.days.iter().enumerate().map(|(i, day)| {
                     let x = (i as f32 * 100.0) + 50.0;
-                    let y = 500.0 - (week_chart.progress[i] * 400.0);
+                    let y = 500.0 - (progress[i] * 400.0);
                     rsx! {
                         g {
                             width: "100%", height: "100%",
                             text { x: "{x}", y: "20.0", r#"text-anchor"#: "middle", "{day}" },
                             if i > 0 {
-                                let week_chart = LineChart::week_chart();
                                 let prev_x = ((i - 1) as f32 * 100.0) + 50.0;
-                                let prev_y = 500.0 - (week_chart.progress[i - 1] * 400.0);
+                                let prev_y = 500.0 - (progress[i - 1] * 400.0);
                                 let c1_x = prev_x + (x - prev_x) / 4.0;
                                 let c1_y = prev_y + (y - prev_y) / 4.0;
                                 let c2_x = prev_x + 3.0 * (x - prev_x) / 4.0;
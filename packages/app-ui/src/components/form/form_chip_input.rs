@@ -0,0 +1,125 @@
+use api_shared::dto::Tag;
+use dioxus::{events::KeyboardEvent, prelude::*};
+
+/// How many known tags are offered at once.
+const MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Props)]
+pub struct FormChipInputProps<'a> {
+    /// the chips, in order
+    tags: Vec<String>,
+    /// known tags, offered while typing and giving the chips their color
+    suggestions: Vec<Tag>,
+    onchange: EventHandler<'a, Vec<String>>,
+
+    #[props(optional)]
+    placeholder: Option<String>,
+}
+
+/// Text input turning what's typed into chips, on Enter, a comma or when
+/// leaving the field. Tags are compared like the server does, so the same
+/// tag isn't added twice in another case.
+pub fn FormChipInput<'a>(cx: Scope<'a, FormChipInputProps<'a>>) -> Element<'a> {
+    let draft = use_state(cx, String::new);
+    let place_holder = cx.props.placeholder.clone().unwrap_or_default();
+
+    let add = move |names: Vec<String>| {
+        let mut tags = cx.props.tags.clone();
+        for name in names {
+            let name = name.trim().to_string();
+            let key = Tag::key(&name);
+            if !name.is_empty() && !tags.iter().any(|tag| Tag::key(tag) == key) {
+                tags.push(name);
+            }
+        }
+        if tags != cx.props.tags {
+            cx.props.onchange.call(tags);
+        }
+    };
+    let remove = move |index: usize| {
+        let mut tags = cx.props.tags.clone();
+        tags.remove(index);
+        cx.props.onchange.call(tags);
+    };
+
+    let draft_key = Tag::key(draft.get());
+    let matches: Vec<Tag> = if draft_key.is_empty() {
+        Vec::new()
+    } else {
+        cx.props.suggestions
+            .iter()
+            .filter(|tag| Tag::key(&tag.name).contains(&draft_key))
+            .filter(|tag| !cx.props.tags.iter().any(|chosen| Tag::key(chosen) == Tag::key(&tag.name)))
+            .take(MAX_SUGGESTIONS)
+            .cloned()
+            .collect()
+    };
+    let color_of = |name: &str| {
+        cx.props.suggestions
+            .iter()
+            .find(|tag| Tag::key(&tag.name) == Tag::key(name))
+            .map_or_else(|| Tag::default_color(name).to_string(), |tag| tag.color.clone())
+    };
+
+    cx.render(rsx! {
+        fieldset { class: "@apply grid gap4",
+            div { class: "tag-list",
+                cx.props.tags.iter().enumerate().map(|(index, tag)| {
+                    let color = color_of(tag);
+                    rsx! {
+                        span { key: "{tag}", class: "chip", style: "background-color: {color}",
+                            "{tag}"
+                            button { r#type: "button", onclick: move |_| remove(index), "×" }
+                        }
+                    }
+                })
+            }
+            input {
+                class: "list-itemdark bg-white bg-opacity-0",
+                r#type: "text",
+                value: "{draft}",
+                placeholder: "{place_holder}",
+                oninput: move |e| {
+                    // a comma closes every tag typed before it
+                    let value = e.value.clone();
+                    match value.rsplit_once(',') {
+                        Some((done, rest)) => {
+                            add(done.split(',').map(str::to_string).collect());
+                            draft.set(rest.to_string());
+                        }
+                        None => draft.set(value),
+                    }
+                },
+                onkeydown: move |e: KeyboardEvent| {
+                    if e.key().to_string() == "Enter" {
+                        add(vec![draft.get().clone()]);
+                        draft.set(String::new());
+                    }
+                },
+                onblur: move |_| {
+                    add(vec![draft.get().clone()]);
+                    draft.set(String::new());
+                }
+            }
+            if !matches.is_empty() {
+                rsx! {
+                    ul { class: "chip-suggestions",
+                        matches.iter().map(|tag| {
+                            let name = tag.name.clone();
+                            rsx! {
+                                li { key: "{tag.name}", class: "btn-transparent", style: "color: {tag.color}",
+                                    // before the input's blur, which would add the draft instead
+                                    onmousedown: move |_| {
+                                        add(vec![name.clone()]);
+                                        draft.set(String::new());
+                                    },
+                                    "{tag.name} ({tag.usage_count})"
+                                }
+                            }
+                        })
+                    }
+                }
+            }
+        }
+    })
+}
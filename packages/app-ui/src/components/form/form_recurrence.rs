@@ -0,0 +1,105 @@
+use dioxus::prelude::*;
+
+/// `BYDAY` codes and their labels, Monday first.
+const WEEKDAYS: [(&str, &str); 7] = [
+    ("MO", "M"),
+    ("TU", "T"),
+    ("WE", "W"),
+    ("TH", "T"),
+    ("FR", "F"),
+    ("SA", "S"),
+    ("SU", "S"),
+];
+
+#[derive(Props)]
+pub struct FormRecurrenceProps<'a> {
+    /// the `RRULE` picked, `None` for a one-off task
+    onchange: EventHandler<'a, Option<String>>,
+}
+
+/// Picks how a task repeats: never, daily, weekly on some weekdays, or
+/// monthly, optionally a number of times only.
+pub fn FormRecurrence<'a>(cx: Scope<'a, FormRecurrenceProps<'a>>) -> Element<'a> {
+    let frequency = use_state(cx, String::new);
+    let weekdays = use_state(cx, Vec::<&'static str>::new);
+    let count = use_state(cx, String::new);
+
+    let notify = move |frequency: &str, weekdays: &[&str], count: &str| {
+        if frequency.is_empty() {
+            return cx.props.onchange.call(None);
+        }
+        let mut rule = format!("FREQ={frequency}");
+        if frequency == "WEEKLY" && !weekdays.is_empty() {
+            // in the order of the week, whatever the order they were picked
+            let by_day: Vec<&str> = WEEKDAYS
+                .iter()
+                .map(|(code, _)| *code)
+                .filter(|code| weekdays.contains(code))
+                .collect();
+            rule.push_str(&format!(";BYDAY={}", by_day.join(",")));
+        }
+        if let Ok(count) = count.trim().parse::<u32>() {
+            rule.push_str(&format!(";COUNT={count}"));
+        }
+        cx.props.onchange.call(Some(rule));
+    };
+
+    cx.render(rsx! {
+        fieldset { class: "@apply grid gap4",
+            select {
+                class: "list-itemdark bg-white bg-opacity-0",
+                onchange: move |e| {
+                    notify(&e.value, weekdays.get(), count.get());
+                    frequency.set(e.value.clone());
+                },
+                option { value: "", "Doesn't repeat" }
+                option { value: "DAILY", "Every day" }
+                option { value: "WEEKLY", "Every week" }
+                option { value: "MONTHLY", "Every month" }
+            }
+            if frequency.get() == "WEEKLY" {
+                rsx! {
+                    div { class: "tag-list",
+                        WEEKDAYS.iter().map(|(code, label)| {
+                            let is_picked = weekdays.contains(code);
+                            let opacity = if is_picked { "1" } else { "0.6" };
+                            rsx! {
+                                button {
+                                    key: "{code}",
+                                    class: "chip bg-orange",
+                                    r#type: "button",
+                                    style: "opacity: {opacity}",
+                                    onclick: move |_| {
+                                        let mut picked = weekdays.get().clone();
+                                        if is_picked {
+                                            picked.retain(|picked| picked != code);
+                                        } else {
+                                            picked.push(*code);
+                                        }
+                                        notify(frequency.get(), &picked, count.get());
+                                        weekdays.set(picked);
+                                    },
+                                    "{label}"
+                                }
+                            }
+                        })
+                    }
+                }
+            }
+            if !frequency.is_empty() {
+                rsx! {
+                    input {
+                        class: "list-itemdark bg-white bg-opacity-0",
+                        r#type: "number",
+                        min: "1",
+                        placeholder: "How many times (empty: no end)",
+                        oninput: move |e| {
+                            notify(frequency.get(), weekdays.get(), &e.value);
+                            count.set(e.value.clone());
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -5,4 +5,10 @@ mod form_button;
 pub use form_button::*;
 
 mod form_textarea;
-pub use form_textarea::*;
\ No newline at end of file
+pub use form_textarea::*;
+
+mod form_chip_input;
+pub use form_chip_input::*;
+
+mod form_recurrence;
+pub use form_recurrence::*;
\ No newline at end of file
@@ -1,9 +1,10 @@
 #![allow(non_snake_case)]
 use crate::components::{Footer, NavBar, Toast};
-use crate::pages::{Home, NewTask, Settings, SignIn, SignUp};
+use crate::pages::{ForgotPassword, Home, NewTask, ResetPassword, Settings, SignIn, SignUp};
 use dioxus::prelude::*;
 use dioxus_router::{Route, Router};
 
+mod api;
 mod components;
 mod pages;
 
@@ -36,6 +37,8 @@ fn App(cx: Scope) -> Element {
                     Route { to: "/settings", Settings {} }
                     Route { to: "/signin", SignIn {} }
                     Route { to: "/signup", SignUp {} }
+                    Route { to: "/forgot_password", ForgotPassword {} }
+                    Route { to: "/reset_password", ResetPassword {} }
                     Footer {}
                 }
             }
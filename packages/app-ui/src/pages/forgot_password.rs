@@ -0,0 +1,54 @@
+use dioxus::{
+    events::{FormData, MouseEvent},
+    prelude::*,
+};
+use api_shared::dto::EmailForm;
+use dioxus_router::Link;
+
+use crate::{api, components::{FormButton, FormInput}, DarkMode, ToastMessage};
+
+pub fn ForgotPassword(cx: Scope) -> Element {
+    let email = use_state(cx, String::new);
+    let error = use_state(cx, String::new);
+
+    let dark_mode = use_shared_state::<DarkMode>(cx).unwrap();
+    let is_dark = dark_mode.read().0;
+    let dark = if is_dark {"dark"} else {""};
+    let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+    let error_class = if error.is_empty() {"invisible"} else {""};
+
+    cx.render(rsx! {
+        div { class: "@apply signin md:w-screen-sm lg:w-screen-md md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
+            h2 { class: "breadcrumb", "Tempowise / Forgot Password" }
+            section { class: "signin-section base-container{dark} grid p8",
+                div { class: "form-data header-wrapper",
+                    p { class: "btn-transparent",
+                        Link { to: "/signin", "Remember your password?" }
+                    }
+
+                    div { class: "error-message {error_class}", p { "{error}" } }
+
+                    form { class: "grid gap4",
+                        FormInput { oninput: move |s: FormData| email.set(s.value), placeholder: "Email".to_string() }
+                        FormButton {
+                            onclick: move |_: MouseEvent| {
+                                to_owned![email, error, toast_message];
+                                cx.spawn(async move {
+                                    let form = EmailForm { email: email.get().clone() };
+                                    match api::client().forgot_password(&form).await {
+                                        Ok(()) => {
+                                            error.set(String::new());
+                                            toast_message.write().0 = "Check your inbox for a reset link";
+                                        }
+                                        Err(err) => error.set(api::error_message(&err)),
+                                    }
+                                });
+                            },
+                            label: "Send reset link".to_string()
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
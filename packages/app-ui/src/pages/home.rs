@@ -1,7 +1,9 @@
+use api_shared::dto::{Occurrence, OccurrencePatch, OccurrenceQuery, Tag, Task, TaskPatch, TaskQuery, TaskStatus};
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
 use dioxus::prelude::*;
 use dioxus_router::Link;
 
-use crate::{components::Chart, DarkMode};
+use crate::{api, components::{Chart, LineChart}, DarkMode, ToastMessage};
 
 pub fn Home(cx: Scope) -> Element {
     let dark_mode = use_shared_state::<DarkMode>(cx).unwrap();
@@ -15,6 +17,147 @@ pub fn Home(cx: Scope) -> Element {
         "list-item"
     };
 
+    let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+
+    // `None` until loaded; toggles change it before the server answers
+    let tasks = use_ref(cx, || None::<Vec<Task>>);
+    let error = use_state(cx, String::new);
+    // the tag the list is filtered by, if any
+    let selected_tag = use_state(cx, || None::<String>);
+    let load = use_future(cx, (selected_tag.get(),), |(tag,)| {
+        to_owned![tasks, error];
+        async move {
+            let query = TaskQuery { tag, ..TaskQuery::default() };
+            match api::client().list_tasks(&query).await {
+                Ok(list) => tasks.set(Some(list)),
+                Err(err) => error.set(api::error_message(&err)),
+            }
+        }
+    });
+
+    // occurrences of the recurring tasks this week, for the chart, and
+    // today's to check them off
+    let today = Local::now().date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let occurrences = use_ref(cx, Vec::<Occurrence>::new);
+    use_future(cx, (), |_| {
+        to_owned![occurrences];
+        async move {
+            let query = OccurrenceQuery { from: monday, to: monday + Duration::days(6) };
+            match api::client().list_occurrences(&query).await {
+                Ok(list) => occurrences.set(list),
+                Err(err) => log::error!("[Home] failed to load occurrences: {}", err),
+            }
+        }
+    });
+
+    let known_tags = use_future(cx, (), |_| async move { api::client().list_tags().await });
+    // borrowed, so the closures below can all share it
+    let tag_list: &[Tag] = match known_tags.value() {
+        Some(Ok(list)) => list,
+        _ => &[],
+    };
+    let color_of = |name: &str| {
+        tag_list
+            .iter()
+            .find(|tag| Tag::key(&tag.name) == Tag::key(name))
+            .map_or_else(|| Tag::default_color(name).to_string(), |tag| tag.color.clone())
+    };
+
+    let toggle = move |task: Task| {
+        to_owned![tasks, toast_message];
+        cx.spawn(async move {
+            let done = task.status != TaskStatus::Done;
+            let status = if done { TaskStatus::Done } else { TaskStatus::Open };
+            // shown at once, rolled back if the server refuses it
+            replace_task(&tasks, Task { status, completed_at: done.then(Utc::now), ..task.clone() });
+            let patch = TaskPatch { status: Some(status), ..TaskPatch::default() };
+            match api::client().update_task(task.id, &patch).await {
+                Ok(updated) => replace_task(&tasks, updated),
+                Err(err) => {
+                    log::error!("[Home] failed to update task {}: {}", task.id, err);
+                    replace_task(&tasks, task);
+                    toast_message.write().0 = "Could not update the task, try again";
+                }
+            }
+        });
+    };
+
+    let toggle_occurrence = move |occurrence: Occurrence| {
+        to_owned![occurrences, toast_message];
+        cx.spawn(async move {
+            let done = occurrence.completed_at.is_none();
+            // shown at once, rolled back if the server refuses it
+            replace_occurrence(&occurrences, Occurrence { completed_at: done.then(Utc::now), ..occurrence.clone() });
+            let patch = OccurrencePatch { done };
+            match api::client().update_occurrence(occurrence.task_id, occurrence.date, &patch).await {
+                Ok(updated) => replace_occurrence(&occurrences, updated),
+                Err(err) => {
+                    log::error!("[Home] failed to update occurrence of task {}: {}", occurrence.task_id, err);
+                    replace_occurrence(&occurrences, occurrence);
+                    toast_message.write().0 = "Could not update the task, try again";
+                }
+            }
+        });
+    };
+
+    let task_list = tasks.read().clone();
+    let week = occurrences.read().clone();
+    let completed: Vec<DateTime<Utc>> = task_list
+        .iter()
+        .flatten()
+        .filter_map(|task| task.completed_at)
+        .chain(week.iter().filter_map(|occurrence| occurrence.completed_at))
+        .collect();
+    let today_list: Vec<Occurrence> = week
+        .into_iter()
+        .filter(|occurrence| occurrence.date == today)
+        .collect();
+    let progress = LineChart::week_progress(&completed, Local::now().date_naive());
+
+    let items = match &task_list {
+        _ if !error.is_empty() => rsx! {
+            li { class: "error-message",
+                p { "{error}" }
+                button {
+                    class: "btn-transparent",
+                    r#type: "button",
+                    onclick: move |_| {
+                        error.set(String::new());
+                        load.restart();
+                    },
+                    "Try again"
+                }
+            }
+        },
+        None => rsx! { li { class: "p-description", "Loading your tasks..." } },
+        Some(list) if list.is_empty() => rsx! { li { class: "p-description", "No goals yet, create your first task" } },
+        Some(list) => rsx! {
+            list.iter().map(|task| {
+                let (id, title) = (task.id, task.title.clone());
+                let is_done = task.status == TaskStatus::Done;
+                let chips: Vec<(String, String)> = task.tags.iter().map(|tag| (tag.clone(), color_of(tag))).collect();
+                // in the user's time zone
+                let due = task.due_at.map(|at| at.with_timezone(&Local).format("%d/%m %H:%M").to_string());
+                let task = task.clone();
+                rsx! {
+                    li { key: "{id}", class: "{task_item_theme}",
+                        input { class: "mr4", r#type: "checkbox", checked: "{is_done}", onclick: move |_| toggle(task.clone()) }
+                        label { "{title}" }
+                        if let Some(due) = due {
+                            rsx! { span { class: "p-description ml4", "due {due}" } }
+                        }
+                        span { class: "tag-list ml4",
+                            chips.iter().map(|(name, color)| rsx! {
+                                span { key: "{name}", class: "chip text-xs", style: "background-color: {color}", "{name}" }
+                            })
+                        }
+                    }
+                }
+            })
+        },
+    };
+
     cx.render(rsx! {
         div { class: "home col-span-full md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
             h2 { class: "breadcrumb", "Tempowise / Dashboard" }
@@ -26,27 +169,53 @@ pub fn Home(cx: Scope) -> Element {
                             h3 { class: "h-title", "Today's latest goals" }
                             p { class: "p-description", "plan your business strategy and set timely goals" }
                         }
-                        ul { class: if is_dark { "block-wrapperdark mt4" } else { "block-wrapper mt4" },
-                            li { class: "{task_item_theme}",
-                                input { class: "mr4", r#type: "checkbox", checked: true }
-                                label { "Daily work" }
-                            }
-                            li { class: "{task_item_theme}",
-                                input { class: "mr4", r#type: "checkbox", checked: true }
-                                label { "Running" }
+                        if !tag_list.is_empty() {
+                            rsx! {
+                                div { class: "tag-list mt4",
+                                    tag_list.iter().map(|tag| {
+                                        let name = tag.name.clone();
+                                        let is_selected = selected_tag.get().as_deref().map(Tag::key) == Some(Tag::key(&name));
+                                        // the selected chip stands out, clicking it again clears the filter
+                                        let opacity = if is_selected { "1" } else { "0.6" };
+                                        rsx! {
+                                            button {
+                                                key: "{tag.name}",
+                                                class: "chip",
+                                                r#type: "button",
+                                                style: "background-color: {tag.color}; opacity: {opacity}",
+                                                onclick: move |_| {
+                                                    tasks.set(None);
+                                                    selected_tag.set(if is_selected { None } else { Some(name.clone()) });
+                                                },
+                                                "{tag.name} ({tag.usage_count})"
+                                            }
+                                        }
+                                    })
+                                }
                             }
-                            li { class: "{task_item_theme}",
-                                input { class: "mr4", r#type: "checkbox", checked: true }
-                                label { "Fill weekly appointments" }
-                            }
-                            li { class: "{task_item_theme}",
-                                input { class: "mr4", r#type: "checkbox", checked: true }
-                                label { "Get more clients" }
-                            }
-                            li { class: "{task_item_theme}",
-                                input { class: "mr4", r#type: "checkbox", checked: true }
-                                label { "Rinse and repeat" }
+                        }
+                        if !today_list.is_empty() {
+                            rsx! {
+                                h3 { class: "h-title mt4", "Today" }
+                                ul { class: if is_dark { "block-wrapperdark mt4" } else { "block-wrapper mt4" },
+                                    today_list.iter().map(|occurrence| {
+                                        let id = occurrence.task_id;
+                                        let is_done = occurrence.completed_at.is_some();
+                                        let occurrence = occurrence.clone();
+                                        let title = occurrence.title.clone();
+                                        rsx! {
+                                            li { key: "{id}", class: "{task_item_theme}",
+                                                input { class: "mr4", r#type: "checkbox", checked: "{is_done}", onclick: move |_| toggle_occurrence(occurrence.clone()) }
+                                                label { "{title}" }
+                                                i { class: "i-line-md:rotate-270 ml2", title: "Repeats" }
+                                            }
+                                        }
+                                    })
+                                }
                             }
+                        }
+                        ul { class: if is_dark { "block-wrapperdark mt4" } else { "block-wrapper mt4" },
+                            items
                             Link { class: "btn-primary flex items-center justify-center", to: "/new_task",
                                 i { class: "i-line-md:edit-twotone mr1" }
                                 "New task"
@@ -78,7 +247,7 @@ pub fn Home(cx: Scope) -> Element {
     "overview block-wrapper mt4 max-w-full"
 },
                             // Link { to: "#overview", i { class: "i-flat-color-icons:statistics text-9xl md:text-[16rem] saturate-50" } }
-                            Chart {}
+                            Chart { progress: progress }
                         }
                     }
                 }
@@ -86,3 +255,26 @@ pub fn Home(cx: Scope) -> Element {
         }
     })
 }
+
+/// Puts `task` in place of the loaded one with the same id.
+fn replace_task(tasks: &UseRef<Option<Vec<Task>>>, task: Task) {
+    if let Some(slot) = tasks
+        .write()
+        .iter_mut()
+        .flatten()
+        .find(|loaded| loaded.id == task.id)
+    {
+        *slot = task;
+    }
+}
+
+/// Puts `occurrence` in place of the loaded one of the same task and day.
+fn replace_occurrence(occurrences: &UseRef<Vec<Occurrence>>, occurrence: Occurrence) {
+    if let Some(slot) = occurrences
+        .write()
+        .iter_mut()
+        .find(|loaded| loaded.task_id == occurrence.task_id && loaded.date == occurrence.date)
+    {
+        *slot = occurrence;
+    }
+}
@@ -11,4 +11,10 @@ mod new_task;
 pub use new_task::*;
 
 mod settings;
-pub use settings::*;
\ No newline at end of file
+pub use settings::*;
+
+mod forgot_password;
+pub use forgot_password::*;
+
+mod reset_password;
+pub use reset_password::*;
\ No newline at end of file
@@ -2,19 +2,77 @@ use dioxus::{
     events::{FormData, MouseEvent},
     prelude::*,
 };
-use crate::{components::{FormButton, FormInput, FormTextarea}, DarkMode, ToastMessage};
+use api_shared::dto::{Recurrence, TaskForm};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use dioxus_router::{use_router, Link};
+
+use crate::{api, components::{FormButton, FormChipInput, FormInput, FormRecurrence, FormTextarea}, DarkMode, ToastMessage};
 
 pub fn NewTask(cx: Scope) -> Element {
     let title = use_state(cx, String::new);
     let summary = use_state(cx, String::new);
     let content = use_state(cx, String::new);
-    let tags = use_state(cx, String::new);
+    let tags = use_state(cx, Vec::<String>::new);
+    let rule = use_state(cx, || None::<String>);
+    let due = use_state(cx, String::new);
+    let reminder = use_state(cx, String::new);
+    let error = use_state(cx, String::new);
 
     let dark_mode = use_shared_state::<DarkMode>(cx).unwrap();
     let is_dark = dark_mode.read().0;
     let dark = if is_dark {"dark"} else {""};
 
     let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+    let router = use_router(cx);
+    let error_class = if error.is_empty() {"invisible"} else {""};
+
+    // tasks belong to an account, so the editor needs a signed-in user
+    let profile = use_future(cx, (), |_| async move { api::client().profile().await });
+    let signed_out = matches!(profile.value(), Some(Err(_)));
+    let profile_error = match profile.value() {
+        Some(Err(err)) => api::error_message(err),
+        _ => String::new(),
+    };
+    // tags already used, suggested while typing new ones
+    let known_tags = use_future(cx, (), |_| async move { api::client().list_tags().await });
+    let suggestions = match known_tags.value() {
+        Some(Ok(known)) => known.clone(),
+        _ => Vec::new(),
+    };
+
+    let add_task = move |_: MouseEvent| {
+        to_owned![title, summary, content, tags, rule, due, reminder, error, toast_message, router];
+        cx.spawn(async move {
+            let due_at = local_to_utc(due.get());
+            // reminders only make sense before a due time
+            let reminders = match due_at {
+                Some(_) => reminder.get().parse::<u32>().ok().into_iter().collect(),
+                None => Vec::new(),
+            };
+            let form = TaskForm {
+                title: title.get().clone(),
+                summary: summary.get().clone(),
+                content: content.get().clone(),
+                tags: tags.get().clone(),
+                // repeating from today, in the user's time zone
+                recurrence: rule.get().clone().map(|rule| Recurrence {
+                    rule,
+                    starts_on: Local::now().date_naive(),
+                    exceptions: Vec::new(),
+                }),
+                starts_at: None,
+                due_at,
+                reminders,
+            };
+            match api::client().create_task(&form).await {
+                Ok(_) => {
+                    toast_message.write().0 = "Task created, check it on your dashboard";
+                    router.navigate_to("/");
+                }
+                Err(err) => error.set(api::error_message(&err)),
+            }
+        });
+    };
 
     cx.render(rsx! {
         div { class: "@apply tasks md:w-screen-sm lg:w-screen-md md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
@@ -25,7 +83,18 @@ pub fn NewTask(cx: Scope) -> Element {
                     p { class: "p-description", "Fill the forms with your desired goals for the week" }
                 }
 
+                if signed_out {
+                    rsx! {
+                        div { class: "error-message",
+                            p { "{profile_error}" }
+                            p { class: "btn-transparent", Link { to: "/signin", "Sign in to create tasks" } }
+                        }
+                    }
+                }
+
                 section { class: "form-data p4 md:p8 my4 rounded-xl",
+                    div { class: "error-message {error_class}", p { "{error}" } }
+
                     form { class: "grid gap4",
                         FormInput {
                             oninput: move |s: FormData| title.set(s.value),
@@ -40,21 +109,42 @@ pub fn NewTask(cx: Scope) -> Element {
                             placeholder: "Task description".to_string(),
                             cols: 24
                         }
-                        FormInput {
-                            oninput: move |s: FormData| tags.set(s.value),
-                            placeholder: "Enter tags related to this task".to_string()
+                        FormChipInput {
+                            tags: tags.get().clone(),
+                            suggestions: suggestions,
+                            onchange: move |chosen: Vec<String>| tags.set(chosen),
+                            placeholder: "Enter tags related to this task, separated by commas".to_string()
                         }
-                        div { class: "tag-list" }
-                        FormButton {
-                            onclick: move |_: MouseEvent| {
-                                log::info!("[TOAST] wild toast appears.");
-                                toast_message.write().0 = r"SUCESS! Check your new task: {dashboard}";
-                            },
-                            label: "Add".to_string()
+                        FormRecurrence { onchange: move |picked: Option<String>| rule.set(picked) }
+                        input {
+                            class: "list-itemdark bg-white bg-opacity-0",
+                            r#type: "datetime-local",
+                            title: "Due",
+                            oninput: move |e| due.set(e.value.clone())
                         }
+                        if !due.is_empty() {
+                            rsx! {
+                                select {
+                                    class: "list-itemdark bg-white bg-opacity-0",
+                                    onchange: move |e| reminder.set(e.value.clone()),
+                                    option { value: "", "No reminder" }
+                                    option { value: "10", "10 minutes before" }
+                                    option { value: "60", "1 hour before" }
+                                    option { value: "1440", "1 day before" }
+                                }
+                            }
+                        }
+                        FormButton { onclick: add_task, label: "Add".to_string() }
                     }
                 }
             }
         }
     })
 }
+
+/// The value of a `datetime-local` input, `2026-10-18T14:30` in the
+/// user's time zone, as UTC.
+fn local_to_utc(value: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").ok()?;
+    Local.from_local_datetime(&naive).earliest().map(|at| at.with_timezone(&Utc))
+}
@@ -0,0 +1,60 @@
+use dioxus::{
+    events::{FormData, MouseEvent},
+    prelude::*,
+};
+use api_shared::dto::PasswordResetForm;
+use dioxus_router::{use_router, Link};
+
+use crate::{api, components::{FormButton, FormInput}, DarkMode, ToastMessage};
+
+/// Landing page of the mailed password reset link.
+pub fn ResetPassword(cx: Scope) -> Element {
+    let token = use_state(cx, || api::query_param("token").unwrap_or_default());
+    let password = use_state(cx, String::new);
+    let error = use_state(cx, String::new);
+
+    let dark_mode = use_shared_state::<DarkMode>(cx).unwrap();
+    let is_dark = dark_mode.read().0;
+    let dark = if is_dark {"dark"} else {""};
+    let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+    let router = use_router(cx);
+    let error_class = if error.is_empty() {"invisible"} else {""};
+
+    cx.render(rsx! {
+        div { class: "@apply signin md:w-screen-sm lg:w-screen-md md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
+            h2 { class: "breadcrumb", "Tempowise / Reset Password" }
+            section { class: "signin-section base-container{dark} grid p8",
+                div { class: "form-data header-wrapper",
+                    p { class: "btn-transparent",
+                        Link { to: "/forgot_password", "Need a new link?" }
+                    }
+
+                    div { class: "error-message {error_class}", p { "{error}" } }
+
+                    form { class: "grid gap4",
+                        FormInput {
+                            oninput: move |s: FormData| password.set(s.value),
+                            placeholder: "New password".to_string()
+                        }
+                        FormButton {
+                            onclick: move |_: MouseEvent| {
+                                to_owned![token, password, error, toast_message, router];
+                                cx.spawn(async move {
+                                    let form = PasswordResetForm { token: token.get().clone(), password: password.get().clone() };
+                                    match api::client().reset_password(&form).await {
+                                        Ok(()) => {
+                                            toast_message.write().0 = "Password changed, sign in again";
+                                            router.navigate_to("/signin");
+                                        }
+                                        Err(err) => error.set(api::error_message(&err)),
+                                    }
+                                });
+                            },
+                            label: "Change password".to_string()
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
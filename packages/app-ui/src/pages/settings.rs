@@ -3,7 +3,9 @@ use dioxus::{
     prelude::*,
 };
 
-use crate::{components::{FormButton, FormInput, FormTextarea}, DarkMode};
+use api_shared::dto::{CodeForm, LocaleForm, PasswordForm, TotpEnrollment};
+
+use crate::{api, components::{FormButton, FormInput, FormTextarea}, DarkMode, ToastMessage};
 
 pub fn Settings(cx: Scope) -> Element {
     let profilePictureURL = use_state(cx, String::new);
@@ -16,6 +18,111 @@ pub fn Settings(cx: Scope) -> Element {
     let is_dark = dark_mode.read().0;
     let dark = if is_dark {"dark"} else {""};
 
+    let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+    // bumped to reload the device list
+    let sessions_version = use_state(cx, || 0);
+    let sessions = use_future(cx, (sessions_version.get(),), |_| async move { api::client().list_sessions().await });
+    let devices = match sessions.value() {
+        Some(Ok(devices)) => devices.clone(),
+        _ => Vec::new(),
+    };
+    let other_devices: Vec<_> = devices.iter().filter(|device| !device.current).cloned().collect();
+    let has_other_devices = !other_devices.is_empty();
+
+    // bumped to reload the 2FA status
+    let two_factor_version = use_state(cx, || 0);
+    let two_factor = use_future(cx, (two_factor_version.get(),), |_| async move { api::client().two_factor_status().await });
+    let two_factor_enabled = matches!(two_factor.value(), Some(Ok(status)) if status.enabled);
+    let enrollment = use_state(cx, || None::<TotpEnrollment>);
+    let recovery_codes = use_state(cx, Vec::<String>::new);
+    let two_factor_code = use_state(cx, String::new);
+    let two_factor_password = use_state(cx, String::new);
+
+    let start_two_factor = move |_: MouseEvent| {
+        to_owned![enrollment, toast_message];
+        cx.spawn(async move {
+            match api::client().start_two_factor().await {
+                Ok(started) => enrollment.set(Some(started)),
+                Err(err) => {
+                    log::error!("[Settings] failed to start 2FA: {}", err);
+                    toast_message.write().0 = "Could not enable two-factor authentication";
+                }
+            }
+        });
+    };
+
+    let confirm_two_factor = move |_: MouseEvent| {
+        to_owned![enrollment, recovery_codes, two_factor_code, two_factor_version, toast_message];
+        cx.spawn(async move {
+            let form = CodeForm { code: two_factor_code.get().clone() };
+            match api::client().confirm_two_factor(&form).await {
+                Ok(codes) => {
+                    enrollment.set(None);
+                    recovery_codes.set(codes.recovery_codes);
+                    toast_message.write().0 = "Two-factor authentication enabled";
+                    two_factor_version.modify(|version| version + 1);
+                }
+                Err(err) => {
+                    log::error!("[Settings] failed to confirm 2FA: {}", err);
+                    toast_message.write().0 = "Invalid code, try the current one";
+                }
+            }
+        });
+    };
+
+    let disable_two_factor = move |_: MouseEvent| {
+        to_owned![recovery_codes, two_factor_password, two_factor_version, toast_message];
+        cx.spawn(async move {
+            let form = PasswordForm { password: two_factor_password.get().clone() };
+            match api::client().disable_two_factor(&form).await {
+                Ok(()) => {
+                    recovery_codes.set(Vec::new());
+                    toast_message.write().0 = "Two-factor authentication disabled";
+                    two_factor_version.modify(|version| version + 1);
+                }
+                Err(err) => {
+                    log::error!("[Settings] failed to disable 2FA: {}", err);
+                    toast_message.write().0 = "Wrong password";
+                }
+            }
+        });
+    };
+
+    // languages with a message catalog on the server, see api-shared/locales
+    let languages = [("", "Browser default"), ("en", "English"), ("pt-BR", "Português (Brasil)")];
+    let profile = use_future(cx, (), |_| async move { api::client().profile().await });
+    let locale = match profile.value() {
+        Some(Ok(profile)) => profile.locale.clone().unwrap_or_default(),
+        _ => String::new(),
+    };
+
+    let change_locale = move |locale: String| {
+        to_owned![toast_message];
+        cx.spawn(async move {
+            let form = LocaleForm { locale: Some(locale).filter(|locale| !locale.is_empty()) };
+            match api::client().update_locale(&form).await {
+                Ok(_) => toast_message.write().0 = "Language saved",
+                Err(err) => {
+                    log::error!("[Settings] failed to save the language: {}", err);
+                    toast_message.write().0 = "Could not save the language";
+                }
+            }
+        });
+    };
+
+    let sign_out_others = move |_: MouseEvent| {
+        to_owned![sessions_version, toast_message, other_devices];
+        cx.spawn(async move {
+            for device in &other_devices {
+                if let Err(err) = api::client().revoke_session(device.id).await {
+                    log::error!("[Settings] failed to sign out {}: {}", device.id, err);
+                }
+            }
+            toast_message.write().0 = "Signed out of other devices";
+            sessions_version.modify(|version| version + 1);
+        });
+    };
+
     cx.render(rsx! {
         div { class: "@apply settings md:w-screen-sm lg:w-screen-md md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
             h2 { class: "breadcrumb", "Tempowise / Settings" }
@@ -50,6 +157,95 @@ pub fn Settings(cx: Scope) -> Element {
                         }
                     }
                 }
+
+                aside { class: "header-wrapper mt8",
+                    h2 { class: "h-title-header", "Language" }
+                    p { class: "p-description", "Language of the messages sent by the server" }
+                }
+
+                section { class: "block-wrapper{dark} form-data p4 md:p8 my4 rounded-xl",
+                    select {
+                        class: "list-item{dark} bg-white bg-opacity-0",
+                        value: "{locale}",
+                        onchange: move |e| change_locale(e.data.value.clone()),
+                        languages.iter().map(|(tag, label)| rsx! {
+                            option { key: "{tag}", value: "{tag}", "{label}" }
+                        })
+                    }
+                }
+
+                aside { class: "header-wrapper mt8",
+                    h2 { class: "h-title-header", "Devices" }
+                    p { class: "p-description", "Where you're signed in" }
+                }
+
+                section { class: "block-wrapper{dark} p4 md:p8 my4 rounded-xl",
+                    ul { class: "grid gap4",
+                        devices.iter().map(|device| {
+                            let user_agent = device.user_agent.clone().unwrap_or_else(|| "Unknown device".to_string());
+                            rsx! {
+                                li { key: "{device.id}", class: "list-item{dark} flex justify-between",
+                                    span { "{user_agent}" }
+                                    span { class: "p-description",
+                                        if device.current { "This device".to_string() } else { format!("Last seen {}", device.last_seen_at) }
+                                    }
+                                }
+                            }
+                        })
+                    }
+                    if has_other_devices {
+                        rsx! {
+                            FormButton { onclick: sign_out_others, label: "Sign out other devices".to_string() }
+                        }
+                    }
+                }
+
+                aside { class: "header-wrapper mt8",
+                    h2 { class: "h-title-header", "Two-factor authentication" }
+                    p { class: "p-description", "Ask for a code from an authenticator app when signing in" }
+                }
+
+                section { class: "block-wrapper{dark} form-data p4 md:p8 my4 rounded-xl",
+                    if !recovery_codes.is_empty() {
+                        rsx! {
+                            p { class: "p-description", "Save these recovery codes. Each one signs you in once if you lose your phone, and they won't be shown again." }
+                            ul { class: "grid gap2 my4",
+                                recovery_codes.iter().map(|code| rsx! { li { key: "{code}", code { "{code}" } } })
+                            }
+                        }
+                    }
+                    if two_factor_enabled {
+                        rsx! {
+                            form { class: "grid gap4",
+                                FormInput {
+                                    oninput: move |s: FormData| two_factor_password.set(s.value),
+                                    placeholder: "Confirm your password".to_string()
+                                }
+                                FormButton { onclick: disable_two_factor, label: "Disable two-factor authentication".to_string() }
+                            }
+                        }
+                    } else if let Some(started) = enrollment.get() {
+                        rsx! {
+                            p { class: "p-description",
+                                "Scan or open "
+                                a { href: "{started.otpauth_uri}", "this link" }
+                                " with your authenticator app, or enter the key "
+                                code { "{started.secret}" }
+                            }
+                            form { class: "grid gap4 mt4",
+                                FormInput {
+                                    oninput: move |s: FormData| two_factor_code.set(s.value),
+                                    placeholder: "6-digit code".to_string()
+                                }
+                                FormButton { onclick: confirm_two_factor, label: "Confirm".to_string() }
+                            }
+                        }
+                    } else {
+                        rsx! {
+                            FormButton { onclick: start_two_factor, label: "Enable two-factor authentication".to_string() }
+                        }
+                    }
+                }
             }
         }
     })
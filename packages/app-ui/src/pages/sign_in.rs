@@ -2,17 +2,65 @@ use dioxus::{
     events::{FormData, MouseEvent},
     prelude::*,
 };
-use dioxus_router::Link;
+use api_shared::dto::{SignInForm, SignInOutcome, TwoFactorForm};
+use dioxus_router::{use_router, Link};
 
-use crate::{components::{FormButton, FormInput}, DarkMode};
+use crate::{api, components::{FormButton, FormInput}, DarkMode, ToastMessage};
 
 pub fn SignIn(cx: Scope) -> Element {
     let email = use_state(cx, String::new);
     let password = use_state(cx, String::new);
+    let error = use_state(cx, String::new);
+    // set once the password matched on an account with 2FA, or by the
+    // OIDC callback for such accounts
+    let challenge_token = use_state(cx, || api::take_fragment_param("challenge_token"));
+    let code = use_state(cx, String::new);
 
     let dark_mode = use_shared_state::<DarkMode>(cx).unwrap();
     let is_dark = dark_mode.read().0;
     let dark = if is_dark {"dark"} else {""};
+    let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+    let router = use_router(cx);
+    let error_class = if error.is_empty() {"invisible"} else {""};
+
+    let providers = use_future(cx, (), |_| async move { api::client().oidc_providers().await });
+    let providers = match providers.value() {
+        Some(Ok(list)) => list.providers.clone(),
+        _ => Vec::new(),
+    };
+
+    let sign_in = move |_: MouseEvent| {
+        to_owned![email, password, error, challenge_token, toast_message, router];
+        cx.spawn(async move {
+            let form = SignInForm { email: email.get().clone(), password: password.get().clone() };
+            match api::client().sign_in(&form).await {
+                Ok(SignInOutcome::SignedIn(_)) => {
+                    toast_message.write().0 = "Welcome back!";
+                    router.navigate_to("/");
+                }
+                Ok(SignInOutcome::TwoFactorRequired(challenge)) => {
+                    error.set(String::new());
+                    challenge_token.set(Some(challenge.challenge_token));
+                }
+                Err(err) => error.set(api::error_message(&err)),
+            }
+        });
+    };
+
+    let verify_code = move |_: MouseEvent| {
+        to_owned![error, challenge_token, code, toast_message, router];
+        cx.spawn(async move {
+            let Some(token) = challenge_token.get().clone() else { return };
+            let form = TwoFactorForm { challenge_token: token, code: code.get().clone() };
+            match api::client().verify_two_factor(&form).await {
+                Ok(_) => {
+                    toast_message.write().0 = "Welcome back!";
+                    router.navigate_to("/");
+                }
+                Err(err) => error.set(api::error_message(&err)),
+            }
+        });
+    };
 
     cx.render(rsx! {
         div { class: "@apply signin md:w-screen-sm lg:w-screen-md md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
@@ -24,25 +72,41 @@ pub fn SignIn(cx: Scope) -> Element {
                     p { class: "btn-transparent",
                         Link { to: "/signup", "Don't have an account?" }
                     }
-
-                    div { class: "error-message invisible",
-                        // TODO: toast message or message over the input
-                        p { "Invalid credentials" }
+                    p { class: "btn-transparent",
+                        Link { to: "/forgot_password", "Forgot your password?" }
                     }
 
-                    form { class: "grid gap4",
-                        FormInput { oninput: move |s: FormData| email.set(s.value), placeholder: "Email".to_string() }
-                        FormInput {
-                            oninput: move |s: FormData| password.set(s.value),
-                            placeholder: "Password".to_string()
+                    div { class: "error-message {error_class}", p { "{error}" } }
+
+                    if challenge_token.is_some() {
+                        rsx! {
+                            form { class: "grid gap4",
+                                p { class: "p-description", "Enter the code from your authenticator app, or one of your recovery codes" }
+                                FormInput { oninput: move |s: FormData| code.set(s.value), placeholder: "Code".to_string() }
+                                FormButton { onclick: verify_code, label: "Verify".to_string() }
+                            }
                         }
-                        FormButton {
-                            onclick: move |_: MouseEvent| {
-                                log::info!("[SignIn] button clicked. email: {}", email);
-                            },
-                            label: "Sign in".to_string()
+                    } else {
+                        rsx! {
+                            form { class: "grid gap4",
+                                FormInput { oninput: move |s: FormData| email.set(s.value), placeholder: "Email".to_string() }
+                                FormInput {
+                                    oninput: move |s: FormData| password.set(s.value),
+                                    placeholder: "Password".to_string()
+                                }
+                                FormButton { onclick: sign_in, label: "Sign in".to_string() }
+                            }
                         }
                     }
+
+                    div { class: "grid gap2 mt4",
+                        providers.iter().map(|provider| {
+                            let url = api::client().oidc_login_url(provider);
+                            rsx! {
+                                a { key: "{provider}", class: "btn-transparent", href: "{url}", "Sign in with {provider}" }
+                            }
+                        })
+                    }
                 }
             }
         }
@@ -2,18 +2,41 @@ use dioxus::{
     events::{FormData, MouseEvent},
     prelude::*,
 };
-use dioxus_router::Link;
+use api_shared::dto::UserForm;
+use dioxus_router::{use_router, Link};
 
-use crate::{components::{FormButton, FormInput}, DarkMode};
+use crate::{api, components::{FormButton, FormInput}, DarkMode, ToastMessage};
 
 pub fn SignUp(cx: Scope) -> Element {
     let name = use_state(cx, String::new);
     let email = use_state(cx, String::new);
     let password = use_state(cx, String::new);
+    let error = use_state(cx, String::new);
 
     let dark_mode = use_shared_state::<DarkMode>(cx).unwrap();
     let is_dark = dark_mode.read().0;
     let dark = if is_dark {"dark"} else {""};
+    let toast_message = use_shared_state::<ToastMessage>(cx).unwrap();
+    let router = use_router(cx);
+    let error_class = if error.is_empty() {"invisible"} else {""};
+
+    let sign_up = move |_: MouseEvent| {
+        to_owned![name, email, password, error, toast_message, router];
+        cx.spawn(async move {
+            let form = UserForm {
+                email: email.get().clone(),
+                password: password.get().clone(),
+                username: name.get().clone(),
+            };
+            match api::client().sign_up(&form).await {
+                Ok(()) => {
+                    toast_message.write().0 = "Account created, check your inbox to verify your email";
+                    router.navigate_to("/signin");
+                }
+                Err(err) => error.set(api::error_message(&err)),
+            }
+        });
+    };
 
     cx.render(rsx! {
         div { class: "@apply signup md:w-screen-sm lg:w-screen-md md:p8 mx6 md:mx16 md:ml32 xl:ml40 rounded-xl drop-shadow-xl md:shadow-xl",
@@ -25,7 +48,7 @@ pub fn SignUp(cx: Scope) -> Element {
                             Link { to: "/signin", "Have an account?" }
                         }
 
-                        div { class: "error-message invisible", p { "That email is already taken" } }
+                        div { class: "error-message {error_class}", p { "{error}" } }
 
                         form { class: "grid gap4",
                             FormInput { oninput: move |s: FormData| name.set(s.value), placeholder: "Your Name".to_string() }
@@ -34,12 +57,7 @@ pub fn SignUp(cx: Scope) -> Element {
                                 oninput: move |s: FormData| password.set(s.value),
                                 placeholder: "Password".to_string()
                             }
-                            FormButton {
-                                onclick: move |_: MouseEvent| {
-                                    log::info!("[SignUp] button clicked. name: {} | email: {}", name, email);
-                                },
-                                label: "Sign up".to_string()
-                            }
+                            FormButton { onclick: sign_up, label: "Sign up".to_string() }
                         }
                     }
                 }
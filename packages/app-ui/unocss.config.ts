@@ -48,6 +48,9 @@ export default defineConfig({
         "p-description": "text-gray-500",
         "p-descriptiondark": "text-gray-200",
         "text-field": "bg-white bg-opacity-50 placeholder-slate-300 filter-none hover:(filter ring ring-orange-600 bg-opacity-100) focus:bg-white p-3 rounded-md resize",
+        "tag-list": "flex flex-wrap gap2",
+        "chip": "inline-flex items-center gap1 text-white text-sm rounded-full px3 py1",
+        "chip-suggestions": "grid gap1 rounded-md bg-white bg-opacity-50 p2",
         "overview-chart": "",
     },
 });